@@ -0,0 +1,395 @@
+use crate::config::{
+    atomic_write, get_app_data_dir, get_backups_dir, get_shell_env_dir, read_json_file, write_json_file,
+};
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// 每个文件最多保留的快照数量（不含首个原始快照）
+const MAX_SNAPSHOTS_PER_FILE: usize = 20;
+
+/// 所有快照的总大小上限（字节）
+const MAX_TOTAL_BYTES: u64 = 50 * 1024 * 1024;
+
+/// 串行化快照目录的读写
+static BACKUP_LOCK: Mutex<()> = Mutex::new(());
+
+/// 单个快照的元数据
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub id: String,
+    /// 被覆盖的原文件路径
+    pub path: String,
+    /// 创建时间（毫秒时间戳）
+    pub created_at: u64,
+    /// 写入前文件是否存在；不存在时恢复即删除该文件
+    pub existed: bool,
+    pub size: u64,
+    /// 该文件第一次被 XG-switch 修改前的快照，清理时始终保留
+    #[serde(default)]
+    pub original: bool,
+}
+
+/// 快照索引（backups/index.json）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct BackupIndex {
+    #[serde(default)]
+    snapshots: Vec<Snapshot>,
+}
+
+fn index_path() -> PathBuf {
+    get_backups_dir().join("index.json")
+}
+
+fn snapshot_file(id: &str) -> PathBuf {
+    get_backups_dir().join(format!("{}.bak", id))
+}
+
+fn load_index() -> Result<BackupIndex, String> {
+    let path = index_path();
+    if !path.exists() {
+        return Ok(BackupIndex::default());
+    }
+    read_json_file(&path)
+}
+
+fn save_index(index: &BackupIndex) -> Result<(), String> {
+    write_json_file(&index_path(), index)
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn generate_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    format!("{}-{}", now_millis(), COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+/// XG-switch 自身的数据文件（配置库、密钥库、快照）和生成的环境变量文件（每次切换都会重写）不做快照
+fn is_internal(path: &Path) -> bool {
+    path.starts_with(get_app_data_dir()) || path.starts_with(get_shell_env_dir())
+}
+
+/// 快照可能包含密钥：Unix 下快照目录权限为 0700、快照文件为 0600
+fn write_private(path: &Path, data: &[u8]) -> Result<(), String> {
+    let dir = path.parent().ok_or_else(|| "无效的快照路径".to_string())?;
+    fs::create_dir_all(dir).map_err(|e| format!("创建备份目录失败: {}", e))?;
+
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))
+            .map_err(|e| format!("设置备份目录权限失败: {}", e))?;
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)
+            .map_err(|e| format!("写入快照失败: {}", e))?;
+        file.write_all(data).map_err(|e| format!("写入快照失败: {}", e))
+    }
+
+    #[cfg(not(unix))]
+    {
+        fs::write(path, data).map_err(|e| format!("写入快照失败: {}", e))
+    }
+}
+
+/// 读取快照内容，不存在的文件返回 None
+fn read_snapshot(snapshot: &Snapshot) -> Result<Option<Vec<u8>>, String> {
+    if !snapshot.existed {
+        return Ok(None);
+    }
+    fs::read(snapshot_file(&snapshot.id))
+        .map(Some)
+        .map_err(|e| format!("读取快照失败: {}", e))
+}
+
+/// 在覆盖文件之前为其创建快照；内容与最近一次快照相同则跳过
+pub fn snapshot_before_write(path: &Path) -> Result<Option<Snapshot>, String> {
+    if is_internal(path) {
+        return Ok(None);
+    }
+
+    let _guard = BACKUP_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut index = load_index()?;
+    let path_str = path.to_string_lossy().to_string();
+
+    let current = match fs::read(path) {
+        Ok(data) => Some(data),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("读取待备份文件失败: {}", e)),
+    };
+
+    let previous = index.snapshots.iter().rev().find(|s| s.path == path_str);
+    if let Some(prev) = previous {
+        if read_snapshot(prev)? == current {
+            return Ok(None);
+        }
+    }
+
+    let snapshot = Snapshot {
+        id: generate_id(),
+        path: path_str,
+        created_at: now_millis(),
+        existed: current.is_some(),
+        size: current.as_ref().map(|d| d.len() as u64).unwrap_or(0),
+        original: previous.is_none(),
+    };
+
+    if let Some(data) = &current {
+        write_private(&snapshot_file(&snapshot.id), data)?;
+    }
+
+    index.snapshots.push(snapshot.clone());
+    prune(&mut index);
+    save_index(&index)?;
+
+    log::info!("已备份 {} -> {}", snapshot.path, snapshot.id);
+    Ok(Some(snapshot))
+}
+
+/// 按数量和总大小清理旧快照，保留每个文件的原始快照
+fn prune(index: &mut BackupIndex) {
+    let mut removed = Vec::new();
+
+    // 每个文件只保留最近 MAX_SNAPSHOTS_PER_FILE 个快照
+    let paths: BTreeSet<String> = index.snapshots.iter().map(|s| s.path.clone()).collect();
+    for path in paths {
+        let ids: Vec<String> = index
+            .snapshots
+            .iter()
+            .filter(|s| s.path == path && !s.original)
+            .map(|s| s.id.clone())
+            .collect();
+        if ids.len() > MAX_SNAPSHOTS_PER_FILE {
+            removed.extend(ids[..ids.len() - MAX_SNAPSHOTS_PER_FILE].iter().cloned());
+        }
+    }
+    index.snapshots.retain(|s| !removed.contains(&s.id));
+
+    // 总大小超限时从最旧的快照开始删除
+    let mut total: u64 = index.snapshots.iter().map(|s| s.size).sum();
+    while total > MAX_TOTAL_BYTES {
+        let Some(pos) = index.snapshots.iter().position(|s| !s.original) else {
+            break;
+        };
+        let snapshot = index.snapshots.remove(pos);
+        total -= snapshot.size;
+        removed.push(snapshot.id);
+    }
+
+    for id in removed {
+        let _ = fs::remove_file(snapshot_file(&id));
+    }
+}
+
+/// 列出快照（最新的在前），可按原文件路径过滤
+pub fn list_snapshots(path: Option<&str>) -> Result<Vec<Snapshot>, String> {
+    let _guard = BACKUP_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut snapshots: Vec<Snapshot> = load_index()?
+        .snapshots
+        .into_iter()
+        .filter(|s| path.is_none_or(|p| s.path == p))
+        .collect();
+    snapshots.reverse();
+    Ok(snapshots)
+}
+
+/// 按 ID 查找快照
+pub fn get_snapshot(id: &str) -> Result<Snapshot, String> {
+    let _guard = BACKUP_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    load_index()?
+        .snapshots
+        .into_iter()
+        .find(|s| s.id == id)
+        .ok_or_else(|| format!("快照不存在: {}", id))
+}
+
+/// 查找文件第一次被 XG-switch 修改前的快照
+pub fn original_snapshot(path: &Path) -> Result<Option<Snapshot>, String> {
+    let path = path.to_string_lossy();
+    let _guard = BACKUP_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    Ok(load_index()?
+        .snapshots
+        .into_iter()
+        .find(|s| s.original && s.path == path))
+}
+
+fn describe(snapshot: &Snapshot) -> String {
+    format!("{} @ {}", snapshot.path, snapshot.id)
+}
+
+/// 生成快照与另一个快照（或原文件当前内容）之间的 unified diff
+pub fn diff_snapshot(id: &str, against: Option<&str>) -> Result<String, String> {
+    let snapshot = get_snapshot(id)?;
+    let old = read_snapshot(&snapshot)?.unwrap_or_default();
+
+    let (new, new_label) = match against {
+        Some(other_id) => {
+            let other = get_snapshot(other_id)?;
+            (read_snapshot(&other)?.unwrap_or_default(), describe(&other))
+        }
+        None => {
+            let current = fs::read(&snapshot.path).unwrap_or_default();
+            (current, format!("{} (当前)", snapshot.path))
+        }
+    };
+
+    let old = String::from_utf8_lossy(&old);
+    let new = String::from_utf8_lossy(&new);
+    Ok(TextDiff::from_lines(old.as_ref(), new.as_ref())
+        .unified_diff()
+        .header(&describe(&snapshot), &new_label)
+        .to_string())
+}
+
+/// 将原文件恢复为指定快照（恢复前会为当前内容再做一次快照）
+pub fn restore_snapshot(id: &str) -> Result<Snapshot, String> {
+    let snapshot = get_snapshot(id)?;
+    let path = PathBuf::from(&snapshot.path);
+
+    match read_snapshot(&snapshot)? {
+        Some(data) => atomic_write(&path, &data)?,
+        None => {
+            snapshot_before_write(&path)?;
+            if path.exists() {
+                fs::remove_file(&path).map_err(|e| format!("删除文件失败: {}", e))?;
+            }
+        }
+    }
+
+    log::info!("已从快照 {} 恢复: {}", snapshot.id, snapshot.path);
+    Ok(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_home;
+
+    fn snapshot(id: usize, path: &str, size: u64, original: bool) -> Snapshot {
+        Snapshot {
+            id: id.to_string(),
+            path: path.to_string(),
+            created_at: id as u64,
+            existed: true,
+            size,
+            original,
+        }
+    }
+
+    fn ids(index: &BackupIndex, path: &str) -> Vec<String> {
+        index.snapshots.iter().filter(|s| s.path == path).map(|s| s.id.clone()).collect()
+    }
+
+    #[test]
+    fn skips_snapshot_when_content_is_unchanged() {
+        let home = temp_home();
+        let path = home.write(".claude/settings.json", "{}");
+
+        let first = snapshot_before_write(&path).unwrap().unwrap();
+        assert!(first.original && first.existed);
+        assert!(snapshot_before_write(&path).unwrap().is_none());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |p: &Path| fs::metadata(p).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(&snapshot_file(&first.id)), 0o600);
+            assert_eq!(mode(&get_backups_dir()), 0o700);
+        }
+
+        fs::write(&path, "{\"env\": {}}").unwrap();
+        let second = snapshot_before_write(&path).unwrap().unwrap();
+        assert!(!second.original);
+        assert_eq!(list_snapshots(Some(&path.to_string_lossy())).unwrap().len(), 2);
+        assert_eq!(original_snapshot(&path).unwrap().unwrap().id, first.id);
+
+        // 自身的数据文件不做快照
+        assert!(snapshot_before_write(&get_app_data_dir().join("profiles.json")).unwrap().is_none());
+    }
+
+    #[test]
+    fn prune_caps_snapshots_per_file_and_keeps_original() {
+        let mut index = BackupIndex::default();
+        index.snapshots.push(snapshot(0, "a", 1, true));
+        index.snapshots.extend((1..=25).map(|i| snapshot(i, "a", 1, false)));
+        index.snapshots.extend((26..=28).map(|i| snapshot(i, "b", 1, false)));
+        prune(&mut index);
+
+        let kept = ids(&index, "a");
+        assert_eq!(kept.len(), 1 + MAX_SNAPSHOTS_PER_FILE);
+        assert_eq!(kept[0], "0");
+        assert_eq!(kept[1], "6");
+        assert_eq!(ids(&index, "b"), ["26", "27", "28"]);
+    }
+
+    #[test]
+    fn prune_caps_total_size_from_oldest_and_keeps_original() {
+        const MB: u64 = 1024 * 1024;
+        let mut index = BackupIndex::default();
+        index.snapshots.push(snapshot(0, "a", 30 * MB, true));
+        index.snapshots.push(snapshot(1, "b", 15 * MB, false));
+        index.snapshots.push(snapshot(2, "a", 10 * MB, false));
+        index.snapshots.push(snapshot(3, "b", 5 * MB, false));
+        prune(&mut index);
+
+        let kept: Vec<&str> = index.snapshots.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(kept, ["0", "2", "3"]);
+
+        // 只剩原始快照时即使超限也保留
+        let mut index = BackupIndex::default();
+        index.snapshots.push(snapshot(0, "a", 40 * MB, true));
+        index.snapshots.push(snapshot(1, "b", 40 * MB, true));
+        prune(&mut index);
+        assert_eq!(index.snapshots.len(), 2);
+    }
+
+    #[test]
+    fn restoring_missing_file_snapshot_deletes_file() {
+        let home = temp_home();
+        let path = home.path_of(".gemini/.env");
+
+        let snapshot = snapshot_before_write(&path).unwrap().unwrap();
+        assert!(!snapshot.existed);
+        home.write(".gemini/.env", "GEMINI_API_KEY=\"x\"\n");
+
+        restore_snapshot(&snapshot.id).unwrap();
+        assert!(!path.exists());
+        // 删除前的内容另存了一份快照，可以再恢复回来
+        let latest = &list_snapshots(Some(&path.to_string_lossy())).unwrap()[0];
+        assert!(latest.existed);
+        restore_snapshot(&latest.id).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "GEMINI_API_KEY=\"x\"\n");
+    }
+
+    #[test]
+    fn diff_against_current_file_and_other_snapshot() {
+        let home = temp_home();
+        let path = home.write("config.toml", "model = \"a\"\nkeep = 1\n");
+        let first = snapshot_before_write(&path).unwrap().unwrap();
+        fs::write(&path, "model = \"b\"\nkeep = 1\n").unwrap();
+        let second = snapshot_before_write(&path).unwrap().unwrap();
+        fs::write(&path, "model = \"c\"\nkeep = 1\n").unwrap();
+
+        let diff = diff_snapshot(&first.id, None).unwrap();
+        assert!(diff.contains(&format!("--- {} @ {}", path.display(), first.id)));
+        assert!(diff.contains(&format!("+++ {} (当前)", path.display())));
+        assert!(diff.contains("-model = \"a\"\n+model = \"c\"\n keep = 1\n"));
+
+        let diff = diff_snapshot(&first.id, Some(&second.id)).unwrap();
+        assert!(diff.contains("-model = \"a\"\n+model = \"b\"\n"));
+        assert!(diff_snapshot(&first.id, Some(&first.id)).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,3 @@
+fn main() {
+    std::process::exit(xg_switch_lib::cli::main())
+}
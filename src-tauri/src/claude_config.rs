@@ -1,65 +1,525 @@
 use crate::config::{get_claude_settings_path, read_json_file, write_json_file};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use std::collections::HashMap;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
 
 /// Claude Code settings.json 的结构
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ClaudeSettings {
+    #[serde(default)]
     pub env: HashMap<String, String>,
     #[serde(default)]
     pub permissions: Permissions,
+    /// 默认模型（如 "opus"、"sonnet" 或完整模型名）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// 生成认证令牌的脚本
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_helper: Option<String>,
+    /// 事件名 -> 匹配器列表
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub hooks: HashMap<String, Vec<HookMatcher>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_line: Option<StatusLine>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_co_authored_by: Option<bool>,
+    /// 本地会话记录保留天数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cleanup_period_days: Option<u32>,
+    /// 启用的项目 .mcp.json 服务器
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled_mcpjson_servers: Option<Vec<String>>,
+    /// 禁用的项目 .mcp.json 服务器
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disabled_mcpjson_servers: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enable_all_project_mcp_servers: Option<bool>,
+    /// 限制登录方式："claudeai" 或 "console"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub force_login_method: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_style: Option<String>,
     /// 保留未知字段，防止版本更新时丢失新字段
     #[serde(flatten)]
     pub extra: HashMap<String, Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Permissions {
     #[serde(default)]
     pub allow: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ask: Vec<String>,
     #[serde(default)]
     pub deny: Vec<String>,
+    /// 默认权限模式，见 PERMISSION_MODES
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_mode: Option<String>,
+    /// 除工作目录外允许访问的目录
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub additional_directories: Vec<String>,
+    /// 设为 "disable" 时禁止 bypassPermissions 模式
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disable_bypass_permissions_mode: Option<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// hooks 中某个事件下的一个匹配器
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HookMatcher {
+    /// 匹配的工具名（支持正则），为空时匹配全部
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matcher: Option<String>,
+    #[serde(default)]
+    pub hooks: Vec<HookCommand>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// 单个 hook；只校验 "command" 类型，其他类型（如 "prompt"）的字段原样保留在 extra 中
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HookCommand {
+    #[serde(rename = "type", default, skip_serializing_if = "String::is_empty")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub command: String,
+    /// 超时（秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// 自定义状态栏；只校验 "command" 类型，其他类型的字段原样保留在 extra 中
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatusLine {
+    #[serde(rename = "type", default, skip_serializing_if = "String::is_empty")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub padding: Option<u32>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// 配置使用的模型名与超时，写入 settings.json 的 env（中转站的模型名各不相同）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaudeModels {
+    /// ANTHROPIC_MODEL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// ANTHROPIC_SMALL_FAST_MODEL（后台任务使用的小模型）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub small_fast_model: Option<String>,
+    /// ANTHROPIC_DEFAULT_OPUS_MODEL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opus_model: Option<String>,
+    /// ANTHROPIC_DEFAULT_SONNET_MODEL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sonnet_model: Option<String>,
+    /// ANTHROPIC_DEFAULT_HAIKU_MODEL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub haiku_model: Option<String>,
+    /// API_TIMEOUT_MS（请求超时，毫秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+}
+
+/// ClaudeModels 对应的环境变量；写入的变量记录在 owned_keys 中，切换配置时只删除上一个配置写入的
+pub const MODEL_ENV_KEYS: [&str; 6] = [
+    "ANTHROPIC_MODEL",
+    "ANTHROPIC_SMALL_FAST_MODEL",
+    "ANTHROPIC_DEFAULT_OPUS_MODEL",
+    "ANTHROPIC_DEFAULT_SONNET_MODEL",
+    "ANTHROPIC_DEFAULT_HAIKU_MODEL",
+    "API_TIMEOUT_MS",
+];
+
+impl ClaudeModels {
+    /// 去掉模型名两端空白，空值视为未设置；全部未设置时返回 None
+    #[cfg(feature = "gui")]
+    pub fn normalize(self) -> Result<Option<Self>, String> {
+        let clean = |v: Option<String>| v.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        let models = ClaudeModels {
+            model: clean(self.model),
+            small_fast_model: clean(self.small_fast_model),
+            opus_model: clean(self.opus_model),
+            sonnet_model: clean(self.sonnet_model),
+            haiku_model: clean(self.haiku_model),
+            timeout_ms: self.timeout_ms,
+        };
+        if models.timeout_ms == Some(0) {
+            return Err("超时时间必须大于 0".to_string());
+        }
+        if models == ClaudeModels::default() {
+            return Ok(None);
+        }
+        Ok(Some(models))
+    }
+
+    /// 连通性探测使用的模型：优先选择配置的小模型，其次是主模型
+    pub fn probe_model(&self) -> Option<&str> {
+        [
+            &self.small_fast_model,
+            &self.haiku_model,
+            &self.model,
+            &self.sonnet_model,
+            &self.opus_model,
+        ]
+        .into_iter()
+        .find_map(|m| m.as_deref().map(str::trim).filter(|m| !m.is_empty()))
+    }
+
+    /// 已设置的 (变量名, 值)
+    pub fn env_vars(&self) -> Vec<(String, String)> {
+        let values = [
+            self.model.clone(),
+            self.small_fast_model.clone(),
+            self.opus_model.clone(),
+            self.sonnet_model.clone(),
+            self.haiku_model.clone(),
+            self.timeout_ms.map(|ms| ms.to_string()),
+        ];
+        MODEL_ENV_KEYS
+            .iter()
+            .zip(values)
+            .filter_map(|(key, value)| Some((key.to_string(), value?)))
+            .collect()
+    }
+}
+
+/// permissions.defaultMode 的可选值
+const PERMISSION_MODES: [&str; 4] = ["default", "acceptEdits", "plan", "bypassPermissions"];
+
+/// hooks 与 statusLine 中需要校验 command 的类型
+const COMMAND_KIND: &str = "command";
+
+/// forceLoginMethod 的可选值
+const LOGIN_METHODS: [&str; 2] = ["claudeai", "console"];
+
+/// 由配置的 Base URL 与密钥决定、不随配置内容切换的变量
+const CREDENTIAL_ENV_KEYS: [&str; 2] = ["ANTHROPIC_AUTH_TOKEN", "ANTHROPIC_BASE_URL"];
+
+fn require_non_empty(value: Option<&str>, field: &str) -> Result<(), String> {
+    if value.is_some_and(|v| v.trim().is_empty()) {
+        return Err(format!("{} 不能为空", field));
+    }
+    Ok(())
+}
+
+impl ClaudeSettings {
+    /// 校验配置内容中各字段的取值；Claude Code 新增的 hooks 事件与类型不做限制
+    pub fn validate(&self) -> Result<(), String> {
+        for key in self.env.keys() {
+            crate::env_manager::validate_env_key(key)?;
+        }
+        require_non_empty(self.model.as_deref(), "model")?;
+        require_non_empty(self.api_key_helper.as_deref(), "apiKeyHelper")?;
+        require_non_empty(self.output_style.as_deref(), "outputStyle")?;
+
+        for (event, matchers) in &self.hooks {
+            require_non_empty(Some(event), "hooks 事件名")?;
+            for hook in matchers.iter().flat_map(|m| &m.hooks) {
+                if hook.kind == COMMAND_KIND {
+                    require_non_empty(Some(&hook.command), &format!("hooks.{} 的 command", event))?;
+                }
+            }
+        }
+
+        if let Some(status_line) = &self.status_line {
+            if status_line.kind == COMMAND_KIND {
+                require_non_empty(Some(&status_line.command), "statusLine.command")?;
+            }
+        }
+
+        if let Some(method) = &self.force_login_method {
+            if !LOGIN_METHODS.contains(&method.as_str()) {
+                return Err(format!("forceLoginMethod 只能为 {}: {}", LOGIN_METHODS.join(" / "), method));
+            }
+        }
+
+        if let (Some(enabled), Some(disabled)) = (&self.enabled_mcpjson_servers, &self.disabled_mcpjson_servers) {
+            if let Some(server) = enabled.iter().find(|s| disabled.contains(s)) {
+                return Err(format!("MCP 服务器不能同时启用和禁用: {}", server));
+            }
+        }
+
+        self.permissions.validate()
+    }
+}
+
+impl Permissions {
+    fn validate(&self) -> Result<(), String> {
+        if let Some(mode) = &self.default_mode {
+            if !PERMISSION_MODES.contains(&mode.as_str()) {
+                return Err(format!(
+                    "permissions.defaultMode 只能为 {}: {}",
+                    PERMISSION_MODES.join(" / "),
+                    mode
+                ));
+            }
+        }
+        if let Some(value) = &self.disable_bypass_permissions_mode {
+            if value != "disable" {
+                return Err(format!("permissions.disableBypassPermissionsMode 只能为 disable: {}", value));
+            }
+        }
+        let rules = self.allow.iter().chain(&self.ask).chain(&self.deny);
+        for rule in rules.chain(&self.additional_directories) {
+            require_non_empty(Some(rule), "permissions 规则或目录")?;
+        }
+        Ok(())
+    }
+}
+
+/// 解析并校验 settings.json 内容
+pub fn parse_settings(content: &str) -> Result<ClaudeSettings, String> {
+    let settings: ClaudeSettings =
+        serde_json::from_str(content).map_err(|e| format!("配置内容格式错误: {}", e))?;
+    settings.validate()?;
+    Ok(settings)
+}
+
+/// 配置内容中随配置切换的设置：去掉凭据变量和空的权限列表
+fn profile_overlay(settings: &ClaudeSettings) -> Result<Map<String, Value>, String> {
+    let value = serde_json::to_value(settings).map_err(|e| format!("序列化配置失败: {}", e))?;
+    let Value::Object(mut overlay) = value else {
+        return Ok(Map::new());
+    };
+    if let Some(Value::Object(env)) = overlay.get_mut("env") {
+        for key in CREDENTIAL_ENV_KEYS {
+            env.remove(key);
+        }
+    }
+    if let Some(Value::Object(permissions)) = overlay.get_mut("permissions") {
+        permissions.retain(|_, v| !v.as_array().is_some_and(|a| a.is_empty()));
+    }
+    Ok(overlay)
+}
+
+/// permissions 中的列表名（allow、deny 等）-> XG-switch 添加的规则
+pub type PermissionRules = BTreeMap<String, Vec<String>>;
+
+/// 把配置携带的设置（模型、hooks、权限等）应用到 settings.json 的内容：
+/// 先撤销上一个配置设置过且未被修改的值，再写入新配置的值；env 与 permissions 按键合并，其余字段整体替换。
+/// permissions 中的规则列表按条目合并：owned_rules 为上次添加的规则，先删除它们，
+/// 再添加新配置中用户列表里还没有的规则；返回本次添加的规则
+pub fn apply_profile_settings(
+    current: &mut Map<String, Value>,
+    previous: Option<&ClaudeSettings>,
+    next: Option<&ClaudeSettings>,
+    owned_rules: &PermissionRules,
+) -> Result<PermissionRules, String> {
+    if let Some(Value::Object(permissions)) = current.get_mut("permissions") {
+        remove_rules(permissions, owned_rules);
+    }
+
+    if let Some(previous) = previous {
+        for (key, value) in profile_overlay(previous)? {
+            match value {
+                Value::Object(entries) if key == "env" || key == "permissions" => {
+                    if let Some(Value::Object(target)) = current.get_mut(&key) {
+                        for (k, v) in entries {
+                            // 规则列表已按 owned_rules 处理
+                            if !v.is_array() && target.get(&k) == Some(&v) {
+                                target.remove(&k);
+                            }
+                        }
+                    }
+                }
+                value => {
+                    if current.get(&key) == Some(&value) {
+                        current.remove(&key);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut added = PermissionRules::new();
+    if let Some(next) = next {
+        for (key, value) in profile_overlay(next)? {
+            match value {
+                Value::Object(entries) if key == "env" || key == "permissions" => {
+                    let target = current.entry(key).or_insert_with(|| Value::Object(Map::new()));
+                    if let Value::Object(target) = target {
+                        for (k, v) in entries {
+                            let Value::Array(items) = v else {
+                                target.insert(k, v);
+                                continue;
+                            };
+                            let existing = target.entry(k.clone()).or_insert_with(|| Value::Array(Vec::new()));
+                            let Value::Array(existing) = existing else {
+                                return Err(format!("settings.json 中的 permissions.{} 不是数组", k));
+                            };
+                            for item in items {
+                                if existing.contains(&item) {
+                                    continue;
+                                }
+                                if let Value::String(rule) = &item {
+                                    added.entry(k.clone()).or_default().push(rule.clone());
+                                }
+                                existing.push(item);
+                            }
+                        }
+                    }
+                }
+                value => {
+                    current.insert(key, value);
+                }
+            }
+        }
+    }
+
+    Ok(added)
 }
 
-impl Default for ClaudeSettings {
-    fn default() -> Self {
-        Self {
-            env: HashMap::new(),
-            permissions: Permissions::default(),
-            extra: HashMap::new(),
+/// 从 permissions 的规则列表中删除 rules 中的规则，其余规则保持不变
+fn remove_rules(permissions: &mut Map<String, Value>, rules: &PermissionRules) {
+    for (list, rules) in rules {
+        if let Some(Value::Array(existing)) = permissions.get_mut(list) {
+            existing.retain(|item| !item.as_str().is_some_and(|rule| rules.iter().any(|r| r == rule)));
         }
     }
 }
 
+/// XG-switch 写入 settings.json env 中的非 ANTHROPIC_* 变量
+const DISABLE_TRAFFIC_KEY: &str = "CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC";
+
+/// 切换前后 settings.json 中 env 的变化（只记录变量名，不含值）
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EnvDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl EnvDiff {
+    fn between(before: &Map<String, Value>, after: &Map<String, Value>) -> Self {
+        let mut diff = EnvDiff::default();
+        for (key, value) in after {
+            match before.get(key) {
+                None => diff.added.push(key.clone()),
+                Some(old) if old != value => diff.changed.push(key.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.removed = before.keys().filter(|k| !after.contains_key(*k)).cloned().collect();
+        diff.added.sort();
+        diff.removed.sort();
+        diff.changed.sort();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// 例如 "新增 ANTHROPIC_MODEL；删除 ANTHROPIC_API_KEY；修改 ANTHROPIC_AUTH_TOKEN"
+    pub fn summary(&self) -> String {
+        [("新增", &self.added), ("删除", &self.removed), ("修改", &self.changed)]
+            .iter()
+            .filter(|(_, keys)| !keys.is_empty())
+            .map(|(label, keys)| format!("{} {}", label, keys.join(", ")))
+            .collect::<Vec<_>>()
+            .join("；")
+    }
+}
+
+/// 写入 settings.json 的结果
+#[derive(Debug, Clone, Default)]
+pub struct ClaudeEnvUpdate {
+    /// 本次由 XG-switch 写入 env 的变量，下次切换前会先删除
+    pub owned_keys: Vec<String>,
+    /// 本次添加到 permissions 的规则，下次切换前会先删除
+    pub owned_rules: PermissionRules,
+    pub diff: EnvDiff,
+}
+
 /// 配置 Claude Code
-pub fn configure_claude_code(base_url: String, api_key: String) -> Result<(), String> {
-    let settings_path = get_claude_settings_path();
+pub fn configure_claude_code(
+    base_url: String,
+    api_key: String,
+    owned_keys: &[String],
+    owned_rules: &PermissionRules,
+    previous: Option<&ClaudeSettings>,
+    next: Option<&ClaudeSettings>,
+) -> Result<ClaudeEnvUpdate, String> {
+    configure_claude_with(&get_claude_settings_path(), base_url, api_key, owned_keys, owned_rules, previous, next)
+}
 
-    // 读取现有配置（如果存在），否则使用默认配置
-    let mut settings: ClaudeSettings = if settings_path.exists() {
-        read_json_file(&settings_path).unwrap_or_default()
+/// 写入 Base URL 与密钥，并把上一个配置携带的设置切换为新配置携带的设置；
+/// owned_keys / owned_rules 为上次切换时写入 env 的变量和添加的权限规则，会先全部删除
+pub fn configure_claude_with(
+    settings_path: &Path,
+    base_url: String,
+    api_key: String,
+    owned_keys: &[String],
+    owned_rules: &PermissionRules,
+    previous: Option<&ClaudeSettings>,
+    next: Option<&ClaudeSettings>,
+) -> Result<ClaudeEnvUpdate, String> {
+    // 读取现有配置（如果存在），否则使用默认配置；解析失败时不覆盖用户的文件。
+    // 用户文件按原始 JSON 合并，不套用 ClaudeSettings 的类型，
+    // 这样 Claude Code 能接受但类型与这里不一致的字段（如字符串形式的 hooks 超时）也会原样保留
+    let content = if settings_path.exists() {
+        std::fs::read_to_string(settings_path).map_err(|e| format!("读取 settings.json 失败: {}", e))?
+    } else {
+        String::new()
+    };
+    let mut settings: Map<String, Value> = if content.trim().is_empty() {
+        Map::new()
     } else {
-        ClaudeSettings::default()
+        serde_json::from_str(&content)
+            .map_err(|e| format!("解析 {} 失败，未做修改: {}", settings_path.display(), e))?
+    };
+    let before = match settings.get("env") {
+        None => Map::new(),
+        Some(Value::Object(env)) => env.clone(),
+        Some(_) => return Err(format!("{} 中的 env 不是对象，未做修改", settings_path.display())),
     };
 
+    // 删除上一个配置写入的变量（包括其模型映射），用户自己设置的变量保持不变
+    if let Some(Value::Object(env)) = settings.get_mut("env") {
+        for key in owned_keys {
+            env.remove(key);
+        }
+    }
+    let owned_rules = apply_profile_settings(&mut settings, previous, next, owned_rules)?;
+
     // 更新环境变量
-    settings
-        .env
-        .insert("ANTHROPIC_AUTH_TOKEN".to_string(), api_key);
-    settings
-        .env
-        .insert("ANTHROPIC_BASE_URL".to_string(), base_url);
-    settings.env.insert(
-        "CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC".to_string(),
-        "1".to_string(),
-    );
+    let env = settings.entry("env").or_insert_with(|| Value::Object(Map::new()));
+    let Value::Object(env) = env else {
+        return Err(format!("{} 中的 env 不是对象，未做修改", settings_path.display()));
+    };
+    env.insert("ANTHROPIC_AUTH_TOKEN".to_string(), Value::String(api_key));
+    env.insert("ANTHROPIC_BASE_URL".to_string(), Value::String(base_url));
+    env.insert(DISABLE_TRAFFIC_KEY.to_string(), Value::String("1".to_string()));
+
+    let mut owned_keys: Vec<String> = CREDENTIAL_ENV_KEYS
+        .iter()
+        .chain([&DISABLE_TRAFFIC_KEY])
+        .map(|k| k.to_string())
+        .chain(next.into_iter().flat_map(|n| n.env.keys().cloned()))
+        .collect();
+    owned_keys.sort();
+    owned_keys.dedup();
+    let diff = EnvDiff::between(&before, env);
 
     // 保存配置
-    write_json_file(&settings_path, &settings)?;
+    write_json_file(settings_path, &settings)?;
 
     log::info!("Claude Code 配置成功: {:?}", settings_path);
-    Ok(())
+    if !diff.is_empty() {
+        log::info!("  env: {}", diff.summary());
+    }
+    Ok(ClaudeEnvUpdate { owned_keys, owned_rules, diff })
 }
 
 /// 读取当前 Claude Code 配置
@@ -72,3 +532,173 @@ pub fn get_claude_config() -> Result<ClaudeSettings, String> {
 
     read_json_file(&settings_path)
 }
+
+/// 从 settings.json 内容中删除 XG-switch 每次都会写入的 ANTHROPIC_BASE_URL、ANTHROPIC_AUTH_TOKEN、
+/// CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC 和 owned_keys 中的变量、owned_rules 中的权限规则
+/// （用户自己设置的其他 ANTHROPIC_* 变量保留），以及首次写入时生成的空 env / permissions；
+/// 返回 None 表示文件已没有其他内容，可以删除
+pub fn remove_managed_settings(
+    content: &str,
+    owned_keys: &[String],
+    owned_rules: &PermissionRules,
+) -> Result<Option<String>, String> {
+    let mut settings: Map<String, Value> =
+        serde_json::from_str(content).map_err(|e| format!("解析 settings.json 失败: {}", e))?;
+
+    if let Some(Value::Object(env)) = settings.get_mut("env") {
+        env.retain(|key, _| {
+            !CREDENTIAL_ENV_KEYS.contains(&key.as_str()) && key != DISABLE_TRAFFIC_KEY && !owned_keys.contains(key)
+        });
+    }
+    if settings.get("env").is_some_and(|env| env.as_object().is_some_and(Map::is_empty)) {
+        settings.remove("env");
+    }
+    if let Some(Value::Object(permissions)) = settings.get_mut("permissions") {
+        remove_rules(permissions, owned_rules);
+    }
+    let empty_permissions = settings.get("permissions").is_some_and(|p| {
+        ["allow", "deny"]
+            .iter()
+            .all(|k| p.get(k).and_then(|v| v.as_array()).is_none_or(|a| a.is_empty()))
+            && p.as_object().is_some_and(|o| o.keys().all(|k| k == "allow" || k == "deny"))
+    });
+    if empty_permissions {
+        settings.remove("permissions");
+    }
+
+    if settings.is_empty() {
+        return Ok(None);
+    }
+    serde_json::to_string_pretty(&settings)
+        .map(Some)
+        .map_err(|e| format!("序列化 settings.json 失败: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_home;
+    use serde_json::json;
+
+    fn live(json: &str) -> Map<String, Value> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn probe_model_prefers_configured_small_model() {
+        let mut models = ClaudeModels { model: Some("relay-sonnet".into()), ..Default::default() };
+        assert_eq!(models.probe_model(), Some("relay-sonnet"));
+        models.small_fast_model = Some(" relay-haiku ".into());
+        assert_eq!(models.probe_model(), Some("relay-haiku"));
+        assert_eq!(ClaudeModels::default().probe_model(), None);
+    }
+
+    #[test]
+    fn profile_permissions_merge_with_user_rules() {
+        let mut current = live(r#"{"permissions": {"allow": ["Bash(ls)"], "deny": ["Read(.env)"]}}"#);
+        let work = parse_settings(r#"{"permissions": {"allow": ["Bash(npm test)", "Bash(ls)"]}}"#).unwrap();
+        let personal = parse_settings(r#"{"permissions": {"deny": ["WebFetch"]}}"#).unwrap();
+
+        let owned = apply_profile_settings(&mut current, None, Some(&work), &PermissionRules::new()).unwrap();
+        assert_eq!(current["permissions"]["allow"], json!(["Bash(ls)", "Bash(npm test)"]));
+        assert_eq!(current["permissions"]["deny"], json!(["Read(.env)"]));
+        assert_eq!(owned, PermissionRules::from([("allow".to_string(), vec!["Bash(npm test)".to_string()])]));
+
+        // 用户原有的 Bash(ls) 不随配置撤销
+        let owned = apply_profile_settings(&mut current, Some(&work), Some(&personal), &owned).unwrap();
+        assert_eq!(current["permissions"]["allow"], json!(["Bash(ls)"]));
+        assert_eq!(current["permissions"]["deny"], json!(["Read(.env)", "WebFetch"]));
+
+        apply_profile_settings(&mut current, Some(&personal), None, &owned).unwrap();
+        assert_eq!(current["permissions"]["allow"], json!(["Bash(ls)"]));
+        assert_eq!(current["permissions"]["deny"], json!(["Read(.env)"]));
+    }
+
+    #[test]
+    fn configure_keeps_live_fields_with_unexpected_types() {
+        let home = temp_home();
+        let path = home.write(
+            ".claude/settings.json",
+            r#"{
+                "env": {"MAX_THINKING_TOKENS": 8000},
+                "hooks": {"Stop": [{"hooks": [{"type": "command", "command": "notify", "timeout": "30"}]}]},
+                "statusLine": {"type": "command", "command": "status.sh", "padding": 1.5},
+                "cleanupPeriodDays": -1
+            }"#,
+        );
+        let next = parse_settings(r#"{"model": "opus", "env": {"ANTHROPIC_MODEL": "relay-opus"}}"#).unwrap();
+
+        let update = configure_claude_with(
+            &path,
+            "https://relay.example".to_string(),
+            "sk-test".to_string(),
+            &[],
+            &PermissionRules::new(),
+            None,
+            Some(&next),
+        )
+        .unwrap();
+        assert!(update.owned_keys.contains(&"ANTHROPIC_MODEL".to_string()));
+        assert_eq!(update.diff.added.len(), 4);
+
+        let written: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written["hooks"]["Stop"][0]["hooks"][0]["timeout"], json!("30"));
+        assert_eq!(written["statusLine"]["padding"], json!(1.5));
+        assert_eq!(written["cleanupPeriodDays"], json!(-1));
+        assert_eq!(written["model"], json!("opus"));
+        assert_eq!(written["env"]["MAX_THINKING_TOKENS"], json!(8000));
+        assert_eq!(written["env"]["ANTHROPIC_MODEL"], json!("relay-opus"));
+        assert_eq!(written["env"]["ANTHROPIC_AUTH_TOKEN"], json!("sk-test"));
+    }
+
+    #[test]
+    fn remove_managed_settings_keeps_user_env() {
+        let content = r#"{
+            "env": {
+                "ANTHROPIC_BASE_URL": "https://relay.example",
+                "ANTHROPIC_AUTH_TOKEN": "sk-test",
+                "ANTHROPIC_MODEL": "relay-opus",
+                "ANTHROPIC_CUSTOM_HEADERS": "x-team: infra",
+                "API_TIMEOUT_MS": "600000",
+                "CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC": "1"
+            },
+            "permissions": {"allow": ["Bash(ls)", "Bash(npm test)"], "deny": []}
+        }"#;
+        let owned_keys = vec!["ANTHROPIC_MODEL".to_string()];
+        let owned_rules = PermissionRules::from([("allow".to_string(), vec!["Bash(npm test)".to_string()])]);
+
+        let cleaned = remove_managed_settings(content, &owned_keys, &owned_rules).unwrap().unwrap();
+        let cleaned: Value = serde_json::from_str(&cleaned).unwrap();
+        assert_eq!(
+            cleaned,
+            json!({
+                "env": {"ANTHROPIC_CUSTOM_HEADERS": "x-team: infra", "API_TIMEOUT_MS": "600000"},
+                "permissions": {"allow": ["Bash(ls)"], "deny": []}
+            })
+        );
+
+        let only_managed = r#"{"env": {"ANTHROPIC_BASE_URL": "https://relay.example"}, "permissions": {"allow": [], "deny": []}}"#;
+        assert_eq!(remove_managed_settings(only_managed, &[], &PermissionRules::new()).unwrap(), None);
+    }
+
+    #[test]
+    fn hooks_and_status_line_keep_unknown_shapes() {
+        let content = r#"{
+            "hooks": {
+                "PermissionRequest": [{"hooks": [{"type": "prompt", "prompt": "check it"}]}],
+                "Stop": [{"hooks": [{"command": "notify-send done"}]}]
+            },
+            "statusLine": {"command": "~/.claude/status.sh"}
+        }"#;
+        let parsed = parse_settings(content).unwrap();
+        let value = serde_json::to_value(&parsed).unwrap();
+        assert_eq!(
+            value["hooks"]["PermissionRequest"][0]["hooks"][0],
+            json!({"type": "prompt", "prompt": "check it"})
+        );
+        assert_eq!(value["statusLine"], serde_json::json!({"command": "~/.claude/status.sh"}));
+
+        let empty_command = r#"{"hooks": {"Stop": [{"hooks": [{"type": "command", "command": " "}]}]}}"#;
+        assert!(parse_settings(empty_command).is_err());
+    }
+}
@@ -0,0 +1,557 @@
+use crate::backup;
+use crate::claude_config;
+use crate::codex_config;
+use crate::env_manager::{self, Shell};
+use crate::gemini_config;
+use crate::probe::{self, ProbeOutcome};
+use crate::profiles::{self, Profile, ProfileStore, Tool};
+use crate::projects;
+use crate::switcher;
+use crate::uninstall::{self, UnconfigureReport};
+use crate::vault;
+use crate::vscode;
+use clap::{Parser, Subcommand, ValueEnum};
+
+/// 解锁密钥库使用的口令环境变量（配置中引用了 vault: 密钥时需要）
+const VAULT_PASSPHRASE_ENV: &str = "XG_SWITCH_VAULT_PASSPHRASE";
+
+/// XG-switch 命令行：无需打开窗口即可切换站点配置
+#[derive(Debug, Parser)]
+#[command(name = "xg-switch-cli", version, about = "XG-switch 多站点配置切换工具（命令行）")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// 列出配置库中的配置（* 表示当前激活）
+    List {
+        #[arg(long, value_enum, default_value_t = ToolArg::All)]
+        tool: ToolArg,
+    },
+    /// 切换到指定配置（按 ID 或名称匹配）
+    Use {
+        profile: String,
+        #[arg(long, value_enum, default_value_t = ToolArg::All)]
+        tool: ToolArg,
+        /// 同时写入 VSCode 扩展配置
+        #[arg(long, conflicts_with = "project")]
+        vscode: bool,
+        /// 写入指定编辑器的扩展配置（可重复，all 表示全部已安装的编辑器），隐含 --vscode
+        #[arg(long = "editor", value_name = "ID", conflicts_with = "project")]
+        editors: Vec<String>,
+        /// 只写入指定名称的 VSCode profile（可重复，配合 --editor 限定编辑器），隐含 --vscode
+        #[arg(long = "vscode-profile", value_name = "NAME", conflicts_with = "project")]
+        vscode_profiles: Vec<String>,
+        /// 只为该项目目录切换（写入项目级配置）
+        #[arg(long)]
+        project: Option<String>,
+    },
+    /// 列出设置了项目级配置的项目
+    Projects,
+    /// 列出本机安装的 VS Code 系编辑器（含 Cursor、Windsurf、Flatpak 安装等）及其 profile
+    Editors,
+    /// 显示当前激活的配置与各工具实际生效的地址
+    Current,
+    /// 探测配置的连通性与密钥有效性（不指定配置时探测全部）
+    Probe {
+        profile: Option<String>,
+        #[arg(long, value_enum, default_value_t = ToolArg::All)]
+        tool: ToolArg,
+        /// 超时（毫秒）
+        #[arg(long)]
+        timeout: Option<u64>,
+    },
+    /// 管理覆盖前的备份快照
+    Backup {
+        #[command(subcommand)]
+        command: BackupCommand,
+    },
+    /// 输出配置的环境变量，用于 eval 只在当前终端生效
+    Env {
+        profile: String,
+        #[arg(long, value_enum, default_value_t = ToolArg::All)]
+        tool: ToolArg,
+        /// 输出语法（默认根据 $SHELL 判断）
+        #[arg(long, value_enum)]
+        shell: Option<Shell>,
+    },
+    /// 输出 shell 钩子脚本，切换后已打开的终端在下一个提示符自动生效（posix 只加载一次）
+    ShellInit {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// 撤销 XG-switch 对工具配置所做的修改（默认只删除 XG-switch 写入的键）
+    Unconfigure {
+        #[arg(long, value_enum, default_value_t = ToolArg::All)]
+        tool: ToolArg,
+        /// 恢复为第一次修改前的快照
+        #[arg(long)]
+        restore: bool,
+    },
+    /// 撤销 XG-switch 的全部修改，包括 shell 集成与环境变量文件（配置库保留）
+    Reset {
+        /// 恢复为第一次修改前的快照
+        #[arg(long)]
+        restore: bool,
+        /// 确认执行
+        #[arg(long)]
+        yes: bool,
+    },
+    /// 管理 shell 配置文件中加载环境变量文件的语句
+    Shell {
+        #[command(subcommand)]
+        command: ShellCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum BackupCommand {
+    /// 列出快照（最新的在前）
+    List {
+        /// 只显示该文件的快照
+        #[arg(long)]
+        path: Option<String>,
+    },
+    /// 显示快照与当前文件（或另一个快照）的差异
+    Diff {
+        id: String,
+        against: Option<String>,
+    },
+    /// 将文件恢复为指定快照
+    Restore { id: String },
+}
+
+#[derive(Debug, Subcommand)]
+enum ShellCommand {
+    /// 显示各 shell 的集成状态
+    Status,
+    /// 安装集成（不指定时安装到检测到的 shell）
+    Install {
+        #[arg(long = "shell", value_enum)]
+        shells: Vec<Shell>,
+    },
+    /// 从所有 shell 配置文件中移除集成
+    Uninstall,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ToolArg {
+    Claude,
+    Codex,
+    Gemini,
+    All,
+}
+
+impl ToolArg {
+    fn matches(self, tool: Tool) -> bool {
+        match self {
+            ToolArg::Claude => tool == Tool::Claude,
+            ToolArg::Codex => tool == Tool::Codex,
+            ToolArg::Gemini => tool == Tool::Gemini,
+            ToolArg::All => true,
+        }
+    }
+}
+
+/// 隐藏密钥中间部分，只用于输出；最多显示密钥长度的四分之一（首尾各不超过 4 个字符）
+fn mask_key(key: &str) -> String {
+    if key.starts_with(vault::SECRET_REF_PREFIX) {
+        return key.to_string();
+    }
+    let chars: Vec<char> = key.chars().collect();
+    let visible = (chars.len() / 4).min(8);
+    if visible == 0 {
+        return "*".repeat(chars.len());
+    }
+    let head: String = chars[..visible.div_ceil(2)].iter().collect();
+    let tail: String = chars[chars.len() - visible / 2..].iter().collect();
+    format!("{}****{}", head, tail)
+}
+
+/// 如果设置了口令环境变量，则解锁密钥库
+fn unlock_vault_from_env() -> Result<(), String> {
+    match std::env::var(VAULT_PASSPHRASE_ENV) {
+        Ok(passphrase) if !passphrase.is_empty() => vault::unlock_vault(&passphrase),
+        _ => Ok(()),
+    }
+}
+
+/// 按 ID 或名称查找配置；名称可能在不同工具下重复
+fn find_profiles<'a>(store: &'a ProfileStore, query: &str, tool: ToolArg) -> Vec<&'a Profile> {
+    let candidates = store.profiles.iter().filter(|p| tool.matches(p.tool));
+    if let Some(profile) = candidates.clone().find(|p| p.id == query) {
+        return vec![profile];
+    }
+    candidates.filter(|p| p.name == query).collect()
+}
+
+fn cmd_list(tool: ToolArg) -> Result<(), String> {
+    let store = profiles::list_profiles()?;
+    for profile in store.profiles.iter().filter(|p| tool.matches(p.tool)) {
+        let active = store.active.get(profile.tool) == Some(&profile.id);
+        println!(
+            "{} {:<7} {:<20} {:<40} {}",
+            if active { "*" } else { " " },
+            profile.tool.as_str(),
+            profile.name,
+            profile.base_url,
+            profile.id
+        );
+    }
+    Ok(())
+}
+
+fn cmd_use(
+    query: &str,
+    tool: ToolArg,
+    with_vscode: bool,
+    editors: &[String],
+    vscode_profiles: &[String],
+    project: Option<&str>,
+) -> Result<(), String> {
+    let store = profiles::list_profiles()?;
+    let matched = find_profiles(&store, query, tool);
+    if matched.is_empty() {
+        return Err(format!("未找到配置: {}", query));
+    }
+    for (i, a) in matched.iter().enumerate() {
+        if matched[..i].iter().any(|b| b.tool == a.tool) {
+            return Err(format!("{} 下有多个名为 {} 的配置，请改用配置 ID", a.tool.as_str(), query));
+        }
+    }
+
+    // 切换前先确认指定的编辑器和 profile 存在
+    let selected_profiles = if vscode_profiles.is_empty() {
+        if !editors.is_empty() {
+            vscode::resolve_editors(editors)?;
+        }
+        Vec::new()
+    } else {
+        vscode::resolve_profiles(editors, vscode_profiles)?
+    };
+
+    for profile in matched {
+        if let Some(project) = project {
+            println!("{}", projects::apply_to_project(&profile.id, project)?);
+            continue;
+        }
+        println!("{}", switcher::activate_profile(&profile.id)?);
+
+        if !selected_profiles.is_empty() {
+            if profile.tool == Tool::Gemini {
+                continue;
+            }
+            let api_key = vault::resolve_secret(&profile.api_key)?.trim().to_string();
+            let base_url = probe::base_url_or_default(profile.tool, &profile.base_url);
+            println!("{}", vscode::configure_profiles(profile.tool, &selected_profiles, base_url, api_key)?);
+        } else if with_vscode || !editors.is_empty() {
+            let api_key = vault::resolve_secret(&profile.api_key)?.trim().to_string();
+            let base_url = probe::base_url_or_default(profile.tool, &profile.base_url);
+            let message = match profile.tool {
+                Tool::Gemini => continue,
+                tool if !editors.is_empty() => vscode::configure_editors(tool, editors, base_url, api_key)?,
+                Tool::Claude => vscode::configure_vscode_claude(api_key, base_url)?,
+                Tool::Codex => {
+                    let env_key = profile.env_key.as_deref().unwrap_or(env_manager::DEFAULT_ENV_KEY);
+                    vscode::configure_vscode_codex(base_url, env_key)?
+                }
+            };
+            println!("{}", message);
+        }
+    }
+    Ok(())
+}
+
+fn cmd_editors() -> Result<(), String> {
+    let editors = vscode::discover_editors();
+    if editors.is_empty() {
+        println!("未找到已安装的 VS Code 系编辑器");
+    }
+    for editor in editors {
+        println!(
+            "{:<22} {:<26} {:<8} {}",
+            editor.id,
+            editor.name,
+            if editor.settings_exists { "" } else { "(未创建)" },
+            editor.settings_path
+        );
+        match vscode::list_editor_profiles(&editor) {
+            // 只有默认 profile 时不再重复列出
+            Ok(profiles) if profiles.len() > 1 => {
+                for profile in profiles {
+                    let note = if profile.shares_default_settings { "(沿用默认设置)" } else { "" };
+                    println!("  profile: {:<20} {:<14} {}", profile.name, note, profile.settings_path);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => println!("  {}", e),
+        }
+    }
+    Ok(())
+}
+
+fn cmd_projects() -> Result<(), String> {
+    for entry in projects::list_project_overrides()? {
+        println!(
+            "{:<7} {:<20} {:<40} {}",
+            entry.tool.as_str(),
+            entry.profile_name.as_deref().unwrap_or("(已删除)"),
+            entry.base_url.as_deref().unwrap_or("-"),
+            entry.path
+        );
+    }
+    Ok(())
+}
+
+fn cmd_current() -> Result<(), String> {
+    let store = profiles::list_profiles()?;
+    let active_name = |tool: Tool| {
+        store
+            .active
+            .get(tool)
+            .and_then(|id| store.profiles.iter().find(|p| &p.id == id))
+            .map(|p| p.name.clone())
+            .unwrap_or_else(|| "-".to_string())
+    };
+
+    let claude = claude_config::get_claude_config().unwrap_or_default();
+    println!("claude  配置: {}", active_name(Tool::Claude));
+    println!(
+        "        base_url: {}",
+        claude.env.get("ANTHROPIC_BASE_URL").map(String::as_str).unwrap_or("-")
+    );
+    println!(
+        "        api_key:  {}",
+        claude.env.get("ANTHROPIC_AUTH_TOKEN").map(|k| mask_key(k)).unwrap_or_else(|| "-".to_string())
+    );
+
+    let codex_base_url = codex_config::get_codex_base_url()?;
+    let codex_auth = codex_config::get_codex_auth()?;
+    println!("codex   配置: {}", active_name(Tool::Codex));
+    println!("        base_url: {}", codex_base_url.as_deref().unwrap_or("-"));
+    println!(
+        "        api_key:  {}",
+        codex_auth.map(|a| mask_key(&a.openai_api_key)).unwrap_or_else(|| "-".to_string())
+    );
+
+    let gemini = gemini_config::get_gemini_config()?;
+    println!("gemini  配置: {}", active_name(Tool::Gemini));
+    println!("        base_url: {}", gemini.base_url.as_deref().unwrap_or("(官方)"));
+    println!(
+        "        api_key:  {}",
+        gemini.api_key.as_deref().map(mask_key).unwrap_or_else(|| "-".to_string())
+    );
+    Ok(())
+}
+
+fn cmd_probe(query: Option<&str>, tool: ToolArg, timeout_ms: Option<u64>) -> Result<(), String> {
+    let store = profiles::list_profiles()?;
+    let targets: Vec<Profile> = match query {
+        Some(query) => find_profiles(&store, query, tool).into_iter().cloned().collect(),
+        None => store.profiles.iter().filter(|p| tool.matches(p.tool)).cloned().collect(),
+    };
+    if targets.is_empty() {
+        return Err("没有可探测的配置".to_string());
+    }
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| format!("创建异步运行时失败: {}", e))?;
+
+    let mut failed = false;
+    for profile in targets {
+        let api_key = match vault::resolve_secret(&profile.api_key) {
+            Ok(key) => key,
+            Err(e) => {
+                println!("{:<7} {:<20} 跳过: {}", profile.tool.as_str(), profile.name, e);
+                failed = true;
+                continue;
+            }
+        };
+        let result = runtime.block_on(probe::probe(
+            profile.tool,
+            &profile.base_url,
+            api_key.trim(),
+            profile.models.as_ref().and_then(|m| m.probe_model()),
+            timeout_ms,
+        ));
+        failed |= result.outcome != ProbeOutcome::Ok;
+        println!(
+            "{:<7} {:<20} {:>6}ms  {}",
+            profile.tool.as_str(),
+            profile.name,
+            result.latency_ms,
+            result.message
+        );
+    }
+
+    if failed {
+        return Err("部分配置探测失败".to_string());
+    }
+    Ok(())
+}
+
+fn cmd_backup(command: BackupCommand) -> Result<(), String> {
+    match command {
+        BackupCommand::List { path } => {
+            for snapshot in backup::list_snapshots(path.as_deref())? {
+                println!(
+                    "{}  {:>8}B  {}{}{}",
+                    snapshot.id,
+                    snapshot.size,
+                    snapshot.path,
+                    if snapshot.original { "  [原始]" } else { "" },
+                    if snapshot.existed { "" } else { "  [不存在]" }
+                );
+            }
+        }
+        BackupCommand::Diff { id, against } => {
+            print!("{}", backup::diff_snapshot(&id, against.as_deref())?);
+        }
+        BackupCommand::Restore { id } => {
+            let snapshot = backup::restore_snapshot(&id)?;
+            println!("已恢复 {} 到快照 {}", snapshot.path, snapshot.id);
+        }
+    }
+    Ok(())
+}
+
+fn cmd_env(query: &str, tool: ToolArg, shell: Option<Shell>) -> Result<(), String> {
+    let store = profiles::list_profiles()?;
+    let matched = find_profiles(&store, query, tool);
+    if matched.is_empty() {
+        return Err(format!("未找到配置: {}", query));
+    }
+    let shell = shell.or_else(Shell::current).unwrap_or(Shell::Bash);
+    for profile in matched {
+        for (key, value) in switcher::profile_env_vars(profile)? {
+            println!("{}", shell.render_var(&key, &value));
+        }
+    }
+    Ok(())
+}
+
+fn print_report(report: &UnconfigureReport) {
+    let sections = [
+        ("已恢复", &report.restored),
+        ("已清理", &report.cleaned),
+        ("已删除", &report.deleted),
+        ("已删除环境变量", &report.env_vars),
+        ("已跳过", &report.skipped),
+    ];
+    let mut empty = true;
+    for (label, items) in sections {
+        for item in items {
+            println!("{} {}", label, item);
+            empty = false;
+        }
+    }
+    if empty {
+        println!("没有需要撤销的修改");
+    }
+}
+
+fn cmd_unconfigure(tool: ToolArg, restore: bool) -> Result<(), String> {
+    for tool in Tool::ALL.into_iter().filter(|t| tool.matches(*t)) {
+        print_report(&uninstall::unconfigure_tool(tool, restore)?);
+    }
+    Ok(())
+}
+
+fn cmd_reset(restore: bool, yes: bool) -> Result<(), String> {
+    if !yes {
+        return Err("将撤销 XG-switch 对所有工具配置、VSCode 和 shell 配置文件的修改，确认请加 --yes".to_string());
+    }
+    print_report(&uninstall::reset_all(restore)?);
+    Ok(())
+}
+
+fn cmd_shell(command: ShellCommand) -> Result<(), String> {
+    let updated = match command {
+        ShellCommand::Status => {
+            for info in env_manager::list_shells()? {
+                println!(
+                    "{} {:<8} {}{}",
+                    if info.installed { "*" } else { " " },
+                    info.shell.as_str(),
+                    info.rc_path,
+                    if info.exists { "" } else { "  [不存在]" }
+                );
+            }
+            return Ok(());
+        }
+        ShellCommand::Install { shells } => {
+            let shells = if shells.is_empty() { env_manager::default_shells()? } else { shells };
+            env_manager::install_shell_integration(&shells)?
+        }
+        ShellCommand::Uninstall => env_manager::uninstall_shell_integration()?,
+    };
+
+    if updated.is_empty() {
+        println!("没有需要修改的配置文件");
+    }
+    for path in updated {
+        println!("已更新 {}", path);
+    }
+    Ok(())
+}
+
+/// 命令行入口，返回进程退出码
+pub fn main() -> i32 {
+    let cli = Cli::parse();
+
+    let result = unlock_vault_from_env().and_then(|_| match cli.command {
+        Command::List { tool } => cmd_list(tool),
+        Command::Use {
+            profile,
+            tool,
+            vscode,
+            editors,
+            vscode_profiles,
+            project,
+        } => cmd_use(&profile, tool, vscode, &editors, &vscode_profiles, project.as_deref()),
+        Command::Projects => cmd_projects(),
+        Command::Editors => cmd_editors(),
+        Command::Current => cmd_current(),
+        Command::Probe { profile, tool, timeout } => cmd_probe(profile.as_deref(), tool, timeout),
+        Command::Backup { command } => cmd_backup(command),
+        Command::Env { profile, tool, shell } => cmd_env(&profile, tool, shell),
+        Command::ShellInit { shell } => env_manager::shell_init_script(shell).map(|script| print!("{}", script)),
+        Command::Unconfigure { tool, restore } => cmd_unconfigure(tool, restore),
+        Command::Reset { restore, yes } => cmd_reset(restore, yes),
+        Command::Shell { command } => cmd_shell(command),
+    });
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mask_key_reveals_at_most_a_quarter() {
+        assert_eq!(mask_key("sk-ant-REDACTED"), "sk-a****wxyz");
+        assert_eq!(mask_key("sk-123456"), "s****6");
+        assert_eq!(mask_key("abcdefg"), "a****");
+        assert_eq!(mask_key("abc"), "***");
+        assert_eq!(mask_key(""), "");
+        assert_eq!(mask_key("密钥密钥密钥密钥"), "密****钥");
+        assert_eq!(mask_key("vault:relay"), "vault:relay");
+
+        for len in 1..64 {
+            let key: String = "k".repeat(len);
+            let shown = mask_key(&key).chars().filter(|c| *c == 'k').count();
+            assert!(shown * 4 <= len, "{} 个字符显示了 {} 个", len, shown);
+        }
+    }
+}
@@ -1,7 +1,10 @@
-use crate::config::{get_codex_auth_path, get_codex_config_path, write_json_file, write_text_file};
+use crate::config::{get_codex_auth_path, get_codex_config_dir, get_codex_config_path};
+use crate::transaction::Transaction;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
+use std::path::Path;
+use toml_edit::{DocumentMut, Item, Table};
 
 /// Codex auth.json 的结构
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,54 +16,575 @@ pub struct CodexAuth {
     pub extra: HashMap<String, Value>,
 }
 
-/// 生成 Codex config.toml 内容（使用用户提供的 base_url）
-fn generate_config_toml(base_url: &str) -> String {
-    format!(
-        r#"model_provider = "88code"
-model = "gpt-5-codex"
-model_reasoning_effort = "high"
-disable_response_storage = true
+/// 默认的模型提供方 ID（[model_providers.<id>]）
+pub const DEFAULT_PROVIDER_ID: &str = "88code";
 
-[model_providers.88code]
-name = "88code"
-base_url = "{}"
-wire_api = "responses"
-env_key = "key88"
-requires_openai_auth = true
-"#,
-        base_url
-    )
-}
-
-/// 配置 Codex
-pub fn configure_codex(base_url: String, api_key: String) -> Result<(), String> {
-    let auth_path = get_codex_auth_path();
+/// 新建 config.toml 时使用的默认值
+const DEFAULT_MODEL: &str = "gpt-5-codex";
+const DEFAULT_REASONING_EFFORT: &str = "high";
+const DEFAULT_WIRE_API: &str = "responses";
+
+/// config.toml 中的一个模型提供方
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodexProvider {
+    /// [model_providers.<id>] 中的 id
+    pub id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub base_url: String,
+    #[serde(default)]
+    pub wire_api: Option<String>,
+    /// Codex 读取密钥的环境变量，为空时使用 auth.json 中的密钥
+    #[serde(default)]
+    pub env_key: Option<String>,
+    #[serde(default)]
+    pub requires_openai_auth: bool,
+}
+
+impl CodexProvider {
+    /// 中转站使用的默认提供方
+    pub fn relay(base_url: &str, env_key: Option<&str>) -> Self {
+        Self {
+            id: DEFAULT_PROVIDER_ID.to_string(),
+            name: Some(DEFAULT_PROVIDER_ID.to_string()),
+            base_url: base_url.to_string(),
+            wire_api: Some(DEFAULT_WIRE_API.to_string()),
+            env_key: env_key.map(|k| k.to_string()),
+            requires_openai_auth: true,
+        }
+    }
+}
+
+/// config.toml 顶层的模型设置；未提供的字段保持原值（新文件使用默认值）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CodexModelSettings {
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub reasoning_effort: Option<String>,
+}
+
+/// config.toml 的模型提供方概览（用于前端显示）
+#[derive(Debug, Clone, Serialize)]
+pub struct CodexProviders {
+    /// 当前使用的提供方 ID（model_provider）
+    pub active: Option<String>,
+    pub model: Option<String>,
+    pub reasoning_effort: Option<String>,
+    pub providers: Vec<CodexProvider>,
+}
+
+fn parse_document(content: &str) -> Result<DocumentMut, String> {
+    content
+        .parse::<DocumentMut>()
+        .map_err(|e| format!("解析 config.toml 失败: {}", e))
+}
+
+/// 设置表中的值；键已存在时保留其前后的注释与空白
+fn set_value(table: &mut Table, key: &str, value: impl Into<toml_edit::Value>) {
+    let mut value = value.into();
+    match table.get_mut(key).and_then(|item| item.as_value_mut()) {
+        Some(existing) => {
+            *value.decor_mut() = existing.decor().clone();
+            *existing = value;
+        }
+        None => {
+            table.insert(key, Item::Value(value));
+        }
+    }
+}
+
+/// 在 config.toml 内容中新增或更新一个模型提供方并设为当前提供方，其余内容（包括注释）保持不变
+pub fn edit_config_toml(
+    content: &str,
+    provider: &CodexProvider,
+    settings: &CodexModelSettings,
+) -> Result<String, String> {
+    let is_new = content.trim().is_empty();
+    let mut doc = parse_document(content)?;
+
+    set_value(doc.as_table_mut(), "model_provider", provider.id.as_str());
+
+    let model = settings
+        .model
+        .as_deref()
+        .or(is_new.then_some(DEFAULT_MODEL));
+    if let Some(model) = model {
+        set_value(doc.as_table_mut(), "model", model);
+    }
+    let effort = settings
+        .reasoning_effort
+        .as_deref()
+        .or(is_new.then_some(DEFAULT_REASONING_EFFORT));
+    if let Some(effort) = effort {
+        set_value(doc.as_table_mut(), "model_reasoning_effort", effort);
+    }
+    if is_new {
+        set_value(doc.as_table_mut(), "disable_response_storage", true);
+    }
+
+    // [model_providers] 只作为父表存在，不单独输出表头
+    let providers = doc
+        .entry("model_providers")
+        .or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        })
+        .as_table_mut()
+        .ok_or("config.toml 中的 model_providers 不是表")?;
+    let entry = providers
+        .entry(&provider.id)
+        .or_insert_with(|| {
+            let mut table = Table::new();
+            table.decor_mut().set_prefix("\n");
+            Item::Table(table)
+        })
+        .as_table_mut()
+        .ok_or_else(|| format!("config.toml 中的 model_providers.{} 不是表", provider.id))?;
+
+    set_value(entry, "name", provider.name.as_deref().unwrap_or(&provider.id));
+    set_value(entry, "base_url", provider.base_url.as_str());
+    set_value(
+        entry,
+        "wire_api",
+        provider.wire_api.as_deref().unwrap_or(DEFAULT_WIRE_API),
+    );
+    match &provider.env_key {
+        Some(env_key) => set_value(entry, "env_key", env_key.as_str()),
+        None => {
+            entry.remove("env_key");
+        }
+    }
+    set_value(entry, "requires_openai_auth", provider.requires_openai_auth);
+
+    Ok(doc.to_string())
+}
+
+/// config.toml 中的一个 [profiles.<name>]
+#[cfg(feature = "gui")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodexProfile {
+    pub name: String,
+    #[serde(default)]
+    pub model_provider: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub reasoning_effort: Option<String>,
+}
+
+/// config.toml 中的 Codex profiles 概览（用于前端显示）
+#[cfg(feature = "gui")]
+#[derive(Debug, Clone, Serialize)]
+pub struct CodexProfiles {
+    /// 默认使用的 profile（顶层 profile 字段）
+    pub default: Option<String>,
+    pub profiles: Vec<CodexProfile>,
+}
+
+/// Codex 内置、无需在 model_providers 中声明的提供方
+#[cfg(feature = "gui")]
+const BUILTIN_PROVIDERS: &[&str] = &["openai", "oss"];
+
+/// 设置或删除表中的可选字符串值
+#[cfg(feature = "gui")]
+fn set_optional(table: &mut Table, key: &str, value: Option<&str>) {
+    match value {
+        Some(value) => set_value(table, key, value),
+        None => {
+            table.remove(key);
+        }
+    }
+}
+
+/// 获取 [profiles] 父表，不存在时创建（不单独输出表头）
+#[cfg(feature = "gui")]
+fn profiles_table(doc: &mut DocumentMut) -> Result<&mut Table, String> {
+    doc.entry("profiles")
+        .or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        })
+        .as_table_mut()
+        .ok_or_else(|| "config.toml 中的 profiles 不是表".to_string())
+}
+
+#[cfg(feature = "gui")]
+fn has_profile(doc: &DocumentMut, name: &str) -> bool {
+    doc.get("profiles")
+        .and_then(|p| p.as_table())
+        .is_some_and(|p| p.contains_key(name))
+}
+
+/// 在 config.toml 内容中新增或更新一个 profile，未涉及的字段与注释保持不变
+#[cfg(feature = "gui")]
+pub fn upsert_profile_toml(content: &str, profile: &CodexProfile) -> Result<String, String> {
+    let mut doc = parse_document(content)?;
+
+    if let Some(provider) = profile.model_provider.as_deref() {
+        let declared = doc
+            .get("model_providers")
+            .and_then(|p| p.as_table())
+            .is_some_and(|p| p.contains_key(provider));
+        if !declared && !BUILTIN_PROVIDERS.contains(&provider) {
+            return Err(format!("模型提供方不存在: {}", provider));
+        }
+    }
+
+    let entry = profiles_table(&mut doc)?
+        .entry(&profile.name)
+        .or_insert_with(|| {
+            let mut table = Table::new();
+            table.decor_mut().set_prefix("\n");
+            Item::Table(table)
+        })
+        .as_table_mut()
+        .ok_or_else(|| format!("config.toml 中的 profiles.{} 不是表", profile.name))?;
+
+    set_optional(entry, "model_provider", profile.model_provider.as_deref());
+    set_optional(entry, "model", profile.model.as_deref());
+    set_optional(entry, "model_reasoning_effort", profile.reasoning_effort.as_deref());
+
+    Ok(doc.to_string())
+}
+
+/// 重命名 profile（保持其在文件中的位置），默认 profile 指向它时一并更新
+#[cfg(feature = "gui")]
+pub fn rename_profile_toml(content: &str, old_name: &str, new_name: &str) -> Result<String, String> {
+    let mut doc = parse_document(content)?;
+    if !has_profile(&doc, old_name) {
+        return Err(format!("Codex profile 不存在: {}", old_name));
+    }
+    if has_profile(&doc, new_name) {
+        return Err(format!("Codex profile 已存在: {}", new_name));
+    }
+
+    let profiles = profiles_table(&mut doc)?;
+    if let Some(item) = profiles.remove(old_name) {
+        profiles.insert(new_name, item);
+    }
+    if doc.get("profile").and_then(|v| v.as_str()) == Some(old_name) {
+        set_value(doc.as_table_mut(), "profile", new_name);
+    }
+
+    Ok(doc.to_string())
+}
+
+/// 删除 profile，默认 profile 指向它时一并移除
+#[cfg(feature = "gui")]
+pub fn delete_profile_toml(content: &str, name: &str) -> Result<String, String> {
+    let mut doc = parse_document(content)?;
+    if !has_profile(&doc, name) {
+        return Err(format!("Codex profile 不存在: {}", name));
+    }
+
+    profiles_table(&mut doc)?.remove(name);
+    if doc.get("profile").and_then(|v| v.as_str()) == Some(name) {
+        doc.remove("profile");
+    }
+
+    Ok(doc.to_string())
+}
+
+/// 设置默认 profile（None 表示不使用 profile）
+#[cfg(feature = "gui")]
+pub fn set_default_profile_toml(content: &str, name: Option<&str>) -> Result<String, String> {
+    let mut doc = parse_document(content)?;
+    if let Some(name) = name {
+        if !has_profile(&doc, name) {
+            return Err(format!("Codex profile 不存在: {}", name));
+        }
+    }
+    set_optional(doc.as_table_mut(), "profile", name);
+    Ok(doc.to_string())
+}
+
+/// 读取、修改并写回全局 config.toml
+#[cfg(feature = "gui")]
+fn modify_config(edit: impl FnOnce(&str) -> Result<String, String>) -> Result<(), String> {
     let config_path = get_codex_config_path();
+    let content = read_config_content(&config_path)?;
+    let mut tx = Transaction::new();
+    tx.write_text(&config_path, &edit(&content)?);
+    tx.commit()
+}
+
+/// 列出全局 config.toml 中的 profiles
+#[cfg(feature = "gui")]
+pub fn list_profiles() -> Result<CodexProfiles, String> {
+    let content = read_config_content(&get_codex_config_path())?;
+    let config: toml::Value = toml::from_str(&content)
+        .map_err(|e| format!("解析 config.toml 失败: {}", e))?;
+    let get_str = |value: &toml::Value, key: &str| {
+        value.get(key).and_then(|v| v.as_str()).map(|s| s.to_string())
+    };
+
+    let profiles = config
+        .get("profiles")
+        .and_then(|v| v.as_table())
+        .map(|table| {
+            table
+                .iter()
+                .map(|(name, p)| CodexProfile {
+                    name: name.clone(),
+                    model_provider: get_str(p, "model_provider"),
+                    model: get_str(p, "model"),
+                    reasoning_effort: get_str(p, "model_reasoning_effort"),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(CodexProfiles {
+        default: get_str(&config, "profile"),
+        profiles,
+    })
+}
+
+/// 新增或更新 Codex profile
+#[cfg(feature = "gui")]
+pub fn save_profile(profile: &CodexProfile) -> Result<(), String> {
+    if profile.name.trim().is_empty() {
+        return Err("profile 名称不能为空".to_string());
+    }
+    modify_config(|content| upsert_profile_toml(content, profile))?;
+    log::info!("Codex profile 已保存: {}", profile.name);
+    Ok(())
+}
+
+/// 重命名 Codex profile
+#[cfg(feature = "gui")]
+pub fn rename_profile(old_name: &str, new_name: &str) -> Result<(), String> {
+    if new_name.trim().is_empty() {
+        return Err("profile 名称不能为空".to_string());
+    }
+    modify_config(|content| rename_profile_toml(content, old_name, new_name))?;
+    log::info!("Codex profile 已重命名: {} -> {}", old_name, new_name);
+    Ok(())
+}
+
+/// 删除 Codex profile
+#[cfg(feature = "gui")]
+pub fn delete_profile(name: &str) -> Result<(), String> {
+    modify_config(|content| delete_profile_toml(content, name))?;
+    log::info!("Codex profile 已删除: {}", name);
+    Ok(())
+}
+
+/// 设置默认 Codex profile
+#[cfg(feature = "gui")]
+pub fn set_default_profile(name: Option<&str>) -> Result<(), String> {
+    modify_config(|content| set_default_profile_toml(content, name))?;
+    log::info!("Codex 默认 profile: {}", name.unwrap_or("(无)"));
+    Ok(())
+}
+
+/// config.toml 内容中当前提供方读取密钥的环境变量
+#[cfg(feature = "gui")]
+pub fn active_env_key(content: &str) -> Option<String> {
+    let config: toml::Value = toml::from_str(content).ok()?;
+    let provider = config.get("model_provider")?.as_str()?;
+    config
+        .get("model_providers")?
+        .get(provider)?
+        .get("env_key")?
+        .as_str()
+        .map(|s| s.to_string())
+}
+
+/// 从 config.toml 内容中删除 XG-switch 添加的模型提供方（默认提供方及读取 env_keys 中变量的提供方）、
+/// 引用它们的 profiles 以及指向它们的 model_provider / profile，其余内容（包括注释）保持不变
+pub fn remove_managed_config_toml(content: &str, env_keys: &[String]) -> Result<String, String> {
+    let mut doc = parse_document(content)?;
+
+    let mut removed: Vec<String> = Vec::new();
+    if let Some(providers) = doc.get_mut("model_providers").and_then(|p| p.as_table_mut()) {
+        let ids: Vec<String> = providers
+            .iter()
+            .filter(|(id, item)| {
+                *id == DEFAULT_PROVIDER_ID
+                    || item
+                        .get("env_key")
+                        .and_then(|v| v.as_str())
+                        .is_some_and(|k| env_keys.iter().any(|key| key == k))
+            })
+            .map(|(id, _)| id.to_string())
+            .collect();
+        for id in &ids {
+            providers.remove(id);
+        }
+        removed = ids;
+    }
+    if doc.get("model_providers").and_then(|p| p.as_table()).is_some_and(|p| p.is_empty()) {
+        doc.remove("model_providers");
+    }
+
+    let uses_removed = |item: Option<&Item>| {
+        item.and_then(|v| v.as_str()).is_some_and(|id| removed.iter().any(|r| r == id))
+    };
+    if uses_removed(doc.get("model_provider")) {
+        doc.remove("model_provider");
+    }
+
+    let mut removed_profiles = Vec::new();
+    if let Some(profiles) = doc.get_mut("profiles").and_then(|p| p.as_table_mut()) {
+        let names: Vec<String> = profiles
+            .iter()
+            .filter(|(_, item)| uses_removed(item.get("model_provider")))
+            .map(|(name, _)| name.to_string())
+            .collect();
+        for name in &names {
+            profiles.remove(name);
+        }
+        removed_profiles = names;
+    }
+    if doc.get("profiles").and_then(|p| p.as_table()).is_some_and(|p| p.is_empty()) {
+        doc.remove("profiles");
+    }
+    let default_profile = doc.get("profile").and_then(|v| v.as_str());
+    if default_profile.is_some_and(|name| removed_profiles.iter().any(|p| p == name)) {
+        doc.remove("profile");
+    }
+
+    Ok(doc.to_string())
+}
+
+/// 从 auth.json 内容中删除 XG-switch 写入的 OPENAI_API_KEY；返回 None 表示文件已没有其他内容，可以删除
+pub fn remove_managed_auth(content: &str) -> Result<Option<String>, String> {
+    let mut auth: serde_json::Map<String, Value> =
+        serde_json::from_str(content).map_err(|e| format!("解析 auth.json 失败: {}", e))?;
+    auth.remove("OPENAI_API_KEY");
+    if auth.is_empty() {
+        return Ok(None);
+    }
+    serde_json::to_string_pretty(&auth)
+        .map(Some)
+        .map_err(|e| format!("序列化 auth.json 失败: {}", e))
+}
+
+/// 读取 config.toml 内容（不存在时为空）
+fn read_config_content(config_path: &Path) -> Result<String, String> {
+    if !config_path.exists() {
+        return Ok(String::new());
+    }
+    std::fs::read_to_string(config_path).map_err(|e| format!("读取 config.toml 失败: {}", e))
+}
+
+/// 将模型提供方的修改暂存到事务中
+pub fn stage_provider(
+    tx: &mut Transaction,
+    config_path: &Path,
+    provider: &CodexProvider,
+    settings: &CodexModelSettings,
+) -> Result<(), String> {
+    let content = read_config_content(config_path)?;
+    tx.write_text(config_path, &edit_config_toml(&content, provider, settings)?);
+    Ok(())
+}
+
+/// 新增或更新全局 config.toml 中的模型提供方并设为当前提供方
+#[cfg(feature = "gui")]
+pub fn configure_provider(provider: &CodexProvider, settings: &CodexModelSettings) -> Result<(), String> {
+    if provider.id.trim().is_empty() {
+        return Err("提供方 ID 不能为空".to_string());
+    }
+    if provider.base_url.trim().is_empty() {
+        return Err("Base URL 不能为空".to_string());
+    }
+
+    let mut tx = Transaction::new();
+    stage_provider(&mut tx, &get_codex_config_path(), provider, settings)?;
+    tx.commit()?;
+
+    log::info!("Codex 模型提供方已更新: {}", provider.id);
+    Ok(())
+}
+
+/// 列出全局 config.toml 中的模型提供方
+pub fn list_providers() -> Result<CodexProviders, String> {
+    let content = read_config_content(&get_codex_config_path())?;
+    let config: toml::Value = toml::from_str(&content)
+        .map_err(|e| format!("解析 config.toml 失败: {}", e))?;
+    let get_str = |value: &toml::Value, key: &str| {
+        value.get(key).and_then(|v| v.as_str()).map(|s| s.to_string())
+    };
+
+    let providers = config
+        .get("model_providers")
+        .and_then(|v| v.as_table())
+        .map(|table| {
+            table
+                .iter()
+                .map(|(id, p)| CodexProvider {
+                    id: id.clone(),
+                    name: get_str(p, "name"),
+                    base_url: get_str(p, "base_url").unwrap_or_default(),
+                    wire_api: get_str(p, "wire_api"),
+                    env_key: get_str(p, "env_key"),
+                    requires_openai_auth: p
+                        .get("requires_openai_auth")
+                        .and_then(|v| v.as_bool())
+                        .unwrap_or(false),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(CodexProviders {
+        active: get_str(&config, "model_provider"),
+        model: get_str(&config, "model"),
+        reasoning_effort: get_str(&config, "model_reasoning_effort"),
+        providers,
+    })
+}
+
+/// 将 Codex 配置（auth.json + config.toml）暂存到事务中，env_key 为读取密钥的环境变量
+pub fn stage_codex_config(
+    tx: &mut Transaction,
+    base_url: &str,
+    api_key: &str,
+    env_key: &str,
+) -> Result<(), String> {
+    stage_codex_config_in(tx, &get_codex_config_dir(), base_url, api_key, Some(env_key))
+}
+
+/// 将 Codex 配置暂存到指定的配置目录（全局 ~/.codex 或项目级 CODEX_HOME）
+pub fn stage_codex_config_in(
+    tx: &mut Transaction,
+    codex_dir: &Path,
+    base_url: &str,
+    api_key: &str,
+    env_key: Option<&str>,
+) -> Result<(), String> {
+    let auth_path = codex_dir.join("auth.json");
+    let config_path = codex_dir.join("config.toml");
 
     // 读取现有 auth.json（如果存在），保留 extra 字段
     let mut auth = if auth_path.exists() {
-        get_codex_auth()?.unwrap_or_else(|| CodexAuth {
-            openai_api_key: api_key.clone(),
+        read_codex_auth(&auth_path)?.unwrap_or_else(|| CodexAuth {
+            openai_api_key: api_key.to_string(),
             extra: HashMap::new(),
         })
     } else {
         CodexAuth {
-            openai_api_key: api_key.clone(),
+            openai_api_key: api_key.to_string(),
             extra: HashMap::new(),
         }
     };
 
     // 更新 API key
-    auth.openai_api_key = api_key.clone();
+    auth.openai_api_key = api_key.to_string();
 
-    // 写入 auth.json
-    write_json_file(&auth_path, &auth)?;
+    // 暂存 auth.json
+    tx.write_json(&auth_path, &auth)?;
 
-    // 生成并写入 config.toml (使用用户提供的 base_url)
-    let config_content = generate_config_toml(&base_url);
-    write_text_file(&config_path, &config_content)?;
+    // 更新 config.toml 中的提供方 (使用用户提供的 base_url)，保留其他内容
+    let provider = CodexProvider::relay(base_url, env_key);
+    stage_provider(tx, &config_path, &provider, &CodexModelSettings::default())?;
 
-    log::info!("Codex 配置成功");
+    log::info!("Codex 配置已暂存");
     log::info!("  base_url: {}", base_url);
     log::info!("  auth.json: {:?}", auth_path);
     log::info!("  config.toml: {:?}", config_path);
@@ -70,13 +594,16 @@ pub fn configure_codex(base_url: String, api_key: String) -> Result<(), String>
 
 /// 读取当前 Codex 配置
 pub fn get_codex_auth() -> Result<Option<CodexAuth>, String> {
-    let auth_path = get_codex_auth_path();
+    read_codex_auth(&get_codex_auth_path())
+}
 
+/// 读取指定路径的 auth.json
+fn read_codex_auth(auth_path: &Path) -> Result<Option<CodexAuth>, String> {
     if !auth_path.exists() {
         return Ok(None);
     }
 
-    let content = std::fs::read_to_string(&auth_path)
+    let content = std::fs::read_to_string(auth_path)
         .map_err(|e| format!("读取 auth.json 失败: {}", e))?;
 
     let auth: CodexAuth = serde_json::from_str(&content)
@@ -84,3 +611,173 @@ pub fn get_codex_auth() -> Result<Option<CodexAuth>, String> {
 
     Ok(Some(auth))
 }
+
+/// 读取 config.toml 中当前模型提供方的 base_url
+pub fn get_codex_base_url() -> Result<Option<String>, String> {
+    read_codex_base_url(&get_codex_config_path())
+}
+
+/// 读取指定 config.toml 中当前模型提供方的 base_url
+pub fn read_codex_base_url(config_path: &Path) -> Result<Option<String>, String> {
+    if !config_path.exists() {
+        return Ok(None);
+    }
+
+    let content = std::fs::read_to_string(config_path)
+        .map_err(|e| format!("读取 config.toml 失败: {}", e))?;
+
+    let config: toml::Value = toml::from_str(&content)
+        .map_err(|e| format!("解析 config.toml 失败: {}", e))?;
+
+    let base_url = config
+        .get("model_provider")
+        .and_then(|v| v.as_str())
+        .and_then(|provider| config.get("model_providers")?.get(provider))
+        .and_then(|p| p.get("base_url"))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+
+    Ok(base_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_config_toml_creates_default_config() {
+        let provider = CodexProvider::relay("https://relay.example/v1", Some("key88"));
+        let content = edit_config_toml("", &provider, &CodexModelSettings::default()).unwrap();
+        let doc = parse_document(&content).unwrap();
+
+        assert_eq!(doc["model_provider"].as_str(), Some(DEFAULT_PROVIDER_ID));
+        assert_eq!(doc["model"].as_str(), Some(DEFAULT_MODEL));
+        assert_eq!(doc["model_reasoning_effort"].as_str(), Some(DEFAULT_REASONING_EFFORT));
+        assert_eq!(doc["disable_response_storage"].as_bool(), Some(true));
+        let entry = &doc["model_providers"][DEFAULT_PROVIDER_ID];
+        assert_eq!(entry["base_url"].as_str(), Some("https://relay.example/v1"));
+        assert_eq!(entry["env_key"].as_str(), Some("key88"));
+        assert_eq!(entry["wire_api"].as_str(), Some(DEFAULT_WIRE_API));
+        assert!(!content.contains("[model_providers]\n"));
+    }
+
+    #[test]
+    fn edit_config_toml_keeps_comments_and_other_tables() {
+        let content = r#"# my codex config
+model = "o3" # keep this model
+model_provider = "openai"
+
+[model_providers.88code]
+name = "88code"
+base_url = "https://old.example/v1" # old relay
+env_key = "OLD_KEY"
+
+[model_providers.local]
+name = "local"
+base_url = "http://localhost:8080/v1"
+
+[mcp_servers.docs]
+command = "docs-mcp"
+"#;
+        let provider = CodexProvider::relay("https://new.example/v1", None);
+        let edited = edit_config_toml(content, &provider, &CodexModelSettings::default()).unwrap();
+
+        assert!(edited.starts_with("# my codex config\nmodel = \"o3\" # keep this model\nmodel_provider = \"88code\"\n"));
+        assert!(edited.contains("base_url = \"https://new.example/v1\" # old relay\n"));
+        assert!(!edited.contains("env_key"));
+        assert!(edited.contains("[model_providers.local]\nname = \"local\"\nbase_url = \"http://localhost:8080/v1\"\n"));
+        assert!(edited.contains("[mcp_servers.docs]\ncommand = \"docs-mcp\"\n"));
+        assert!(!edited.contains("disable_response_storage"));
+
+        let settings = CodexModelSettings {
+            model: Some("gpt-5".to_string()),
+            reasoning_effort: Some("medium".to_string()),
+        };
+        let edited = edit_config_toml(&edited, &provider, &settings).unwrap();
+        assert!(edited.contains("model = \"gpt-5\" # keep this model\n"));
+        assert!(edited.contains("model_reasoning_effort = \"medium\"\n"));
+    }
+
+    #[cfg(feature = "gui")]
+    fn profile(name: &str, provider: Option<&str>, model: Option<&str>) -> CodexProfile {
+        CodexProfile {
+            name: name.to_string(),
+            model_provider: provider.map(str::to_string),
+            model: model.map(str::to_string),
+            reasoning_effort: None,
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    const PROFILES_TOML: &str = r#"model_provider = "88code"
+profile = "fast"
+
+[model_providers.88code]
+name = "88code"
+
+# quick edits
+[profiles.fast]
+model = "gpt-5-mini" # cheap
+model_reasoning_effort = "low"
+
+[profiles.deep]
+model = "gpt-5"
+"#;
+
+    #[cfg(feature = "gui")]
+    #[test]
+    fn upsert_profile_toml_updates_in_place() {
+        let edited = upsert_profile_toml(PROFILES_TOML, &profile("fast", Some("88code"), Some("gpt-5-nano"))).unwrap();
+        assert!(edited.contains(
+            "# quick edits\n[profiles.fast]\nmodel = \"gpt-5-nano\" # cheap\nmodel_provider = \"88code\"\n"
+        ));
+        // 未提供的字段被删除
+        assert!(!edited.contains("model_reasoning_effort"));
+
+        let added = upsert_profile_toml(&edited, &profile("local", Some("oss"), None)).unwrap();
+        assert!(added.ends_with("\n[profiles.local]\nmodel_provider = \"oss\"\n"));
+
+        let err = upsert_profile_toml(PROFILES_TOML, &profile("x", Some("missing"), None)).unwrap_err();
+        assert_eq!(err, "模型提供方不存在: missing");
+
+        let fresh = upsert_profile_toml("", &profile("fast", None, Some("gpt-5"))).unwrap();
+        assert_eq!(fresh, "\n[profiles.fast]\nmodel = \"gpt-5\"\n");
+    }
+
+    #[cfg(feature = "gui")]
+    #[test]
+    fn rename_and_delete_profile_follow_default() {
+        let renamed = rename_profile_toml(PROFILES_TOML, "fast", "quick").unwrap();
+        assert!(renamed.contains("profile = \"quick\"\n"));
+        assert!(renamed.contains("[profiles.quick]\nmodel = \"gpt-5-mini\" # cheap\n"));
+        assert!(!renamed.contains("[profiles.fast]"));
+        assert_eq!(rename_profile_toml(PROFILES_TOML, "fast", "deep").unwrap_err(), "Codex profile 已存在: deep");
+        assert_eq!(rename_profile_toml(PROFILES_TOML, "nope", "x").unwrap_err(), "Codex profile 不存在: nope");
+
+        let deleted = delete_profile_toml(PROFILES_TOML, "fast").unwrap();
+        assert!(!deleted.contains("profile = "));
+        assert!(!deleted.contains("[profiles.fast]"));
+        assert!(deleted.contains("[profiles.deep]\nmodel = \"gpt-5\"\n"));
+
+        let kept = delete_profile_toml(PROFILES_TOML, "deep").unwrap();
+        assert!(kept.contains("profile = \"fast\"\n"));
+    }
+
+    #[cfg(feature = "gui")]
+    #[test]
+    fn set_default_profile_requires_existing_profile() {
+        let edited = set_default_profile_toml(PROFILES_TOML, Some("deep")).unwrap();
+        assert!(edited.contains("profile = \"deep\"\n"));
+        assert!(set_default_profile_toml(PROFILES_TOML, Some("nope")).is_err());
+
+        let cleared = set_default_profile_toml(PROFILES_TOML, None).unwrap();
+        assert!(!cleared.contains("profile = "));
+    }
+
+    #[test]
+    fn edit_config_toml_rejects_invalid_toml() {
+        let provider = CodexProvider::relay("https://relay.example/v1", None);
+        let err = edit_config_toml("model = \n", &provider, &CodexModelSettings::default()).unwrap_err();
+        assert!(err.starts_with("解析 config.toml 失败"), "{}", err);
+    }
+}
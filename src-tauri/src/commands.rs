@@ -1,64 +1,48 @@
+use crate::backup;
 use crate::claude_config;
-use crate::codex_config;
-use crate::config;
+use crate::codex_config::{
+    self, CodexModelSettings, CodexProfile, CodexProfiles, CodexProvider, CodexProviders,
+};
+use crate::config::{self, DEFAULT_CODEX_BASE_URL};
 use crate::env_manager;
+use crate::gemini_config;
+use crate::health::{self, ProfileHealth};
+use crate::probe::{self, ProbeResult};
+use crate::profiles::{
+    self, AutoSwitchSettings, Profile, ProfileInput, ProfileStore, ProfileUpdate, Tool,
+};
+use crate::projects::{self, ProjectOverrideInfo};
+use crate::proxy::{self, ProxyStatus};
+use crate::switcher;
+use crate::transaction::Transaction;
+use crate::uninstall::{self, UnconfigureReport};
+use crate::vault;
 use crate::vscode;
 
-/// 默认的 Base URL
-const DEFAULT_CLAUDE_BASE_URL: &str = "https://www.88code.org/api";
-const DEFAULT_CODEX_BASE_URL: &str = "https://88code.org/openai/v1";
-
 /// 配置 Claude Code
 #[tauri::command]
 pub async fn configure_claude_code(base_url: String, api_key: String) -> Result<String, String> {
-    // 验证输入
-    if api_key.trim().is_empty() {
-        return Err("API 密钥不能为空".to_string());
-    }
-
-    // 如果 base_url 为空，使用 Claude 默认值
-    let base_url = if base_url.trim().is_empty() {
-        DEFAULT_CLAUDE_BASE_URL.to_string()
-    } else {
-        base_url.trim().to_string()
-    };
-
-    // 配置 Claude Code
-    claude_config::configure_claude_code(base_url, api_key)?;
-
-    Ok("Claude Code 配置成功！".to_string())
+    switcher::apply_claude(&base_url, &api_key)
 }
 
 /// 配置 Codex 并设置环境变量
 #[tauri::command]
-pub async fn configure_codex(base_url: String, api_key: String) -> Result<String, String> {
-    // 验证输入
-    if api_key.trim().is_empty() {
-        return Err("API 密钥不能为空".to_string());
-    }
-
-    // 如果 base_url 为空，使用 Codex 默认值
-    let base_url = if base_url.trim().is_empty() {
-        DEFAULT_CODEX_BASE_URL.to_string()
-    } else {
-        base_url.trim().to_string()
-    };
-
-    // 配置 Codex
-    codex_config::configure_codex(base_url, api_key.clone())?;
-
-    // 设置环境变量 key88
-    env_manager::set_key88_env(api_key)?;
-
-    #[cfg(windows)]
-    {
-        Ok("Codex 配置成功！环境变量 key88 已设置，请重启 Codex 以使环境变量生效。".to_string())
-    }
+pub async fn configure_codex(
+    base_url: String,
+    api_key: String,
+    env_key: Option<String>,
+) -> Result<String, String> {
+    switcher::apply_codex(&base_url, &api_key, env_key.as_deref())
+}
 
-    #[cfg(not(windows))]
-    {
-        Ok("Codex 配置成功！环境变量 key88 已添加到 shell 配置文件，请重启终端或运行 'source ~/.zshrc' (或相应的配置文件) 以使环境变量生效。".to_string())
-    }
+/// 配置 Gemini CLI
+#[tauri::command]
+pub async fn configure_gemini(
+    base_url: String,
+    api_key: String,
+    model: Option<String>,
+) -> Result<String, String> {
+    switcher::apply_gemini(&base_url, &api_key, model.as_deref())
 }
 
 /// 获取配置路径信息
@@ -82,6 +66,12 @@ pub async fn get_current_codex_auth() -> Result<Option<codex_config::CodexAuth>,
     codex_config::get_codex_auth()
 }
 
+/// 读取当前 Gemini 配置
+#[tauri::command]
+pub async fn get_current_gemini_config() -> Result<gemini_config::GeminiConfig, String> {
+    gemini_config::get_gemini_config()
+}
+
 /// 配置 VSCode Claude 扩展
 #[tauri::command]
 pub async fn configure_vscode_claude(base_url: String, api_key: String) -> Result<String, String> {
@@ -89,28 +79,86 @@ pub async fn configure_vscode_claude(base_url: String, api_key: String) -> Resul
     let api_key = if api_key.trim().is_empty() {
         "key".to_string()
     } else {
-        api_key.trim().to_string()
+        vault::resolve_secret(&api_key)?.trim().to_string()
     };
 
-    // VSCode Claude 扩展只需要 API Key，base_url 不做检查
+    // base_url 为空时使用 Claude 默认值
     vscode::configure_vscode_claude(api_key, base_url)
 }
 
 /// 配置 VSCode Codex 扩展
 #[tauri::command]
-pub async fn configure_vscode_codex(base_url: String, api_key: String) -> Result<String, String> {
+pub async fn configure_vscode_codex(
+    base_url: String,
+    api_key: String,
+    env_key: Option<String>,
+) -> Result<String, String> {
     if api_key.trim().is_empty() {
         return Err("API 密钥不能为空".to_string());
     }
+    let env_key = env_key
+        .as_deref()
+        .map(str::trim)
+        .filter(|k| !k.is_empty())
+        .unwrap_or(env_manager::DEFAULT_ENV_KEY);
 
     // 如果 base_url 为空，使用 Codex 默认值
-    let base_url = if base_url.trim().is_empty() {
-        DEFAULT_CODEX_BASE_URL.to_string()
-    } else {
-        base_url.trim().to_string()
-    };
+    let base_url = probe::base_url_or_default(Tool::Codex, &base_url);
+
+    vscode::configure_vscode_codex(base_url, env_key)
+}
+
+/// 列出本机安装的 VS Code 系编辑器
+#[tauri::command]
+pub async fn list_editors() -> Result<Vec<vscode::EditorInstall>, String> {
+    Ok(vscode::discover_editors())
+}
+
+/// 为多个编辑器配置 Claude 或 Codex 扩展，editors 为空时配置全部已安装的编辑器
+#[tauri::command]
+pub async fn configure_editors(
+    tool: Tool,
+    base_url: String,
+    api_key: String,
+    editors: Vec<String>,
+) -> Result<String, String> {
+    if api_key.trim().is_empty() {
+        return Err("API 密钥不能为空".to_string());
+    }
+    let api_key = vault::resolve_secret(&api_key)?;
+
+    // 如果 base_url 为空，使用工具的默认值
+    let base_url = probe::base_url_or_default(tool, &base_url);
+
+    vscode::configure_editors(tool, &editors, base_url, api_key)
+}
+
+/// 列出所有已安装编辑器的 VSCode profile
+#[tauri::command]
+pub async fn list_vscode_profiles() -> Result<Vec<vscode::EditorProfile>, String> {
+    Ok(vscode::discover_profiles())
+}
+
+/// 为指定名称的 VSCode profile 写入 Claude 或 Codex 扩展配置，editors 为空时在全部已安装的编辑器中查找
+#[tauri::command]
+pub async fn configure_vscode_profiles(
+    tool: Tool,
+    base_url: String,
+    api_key: String,
+    editors: Vec<String>,
+    profiles: Vec<String>,
+) -> Result<String, String> {
+    if api_key.trim().is_empty() {
+        return Err("API 密钥不能为空".to_string());
+    }
+    if profiles.is_empty() {
+        return Err("请选择至少一个 profile".to_string());
+    }
+    let api_key = vault::resolve_secret(&api_key)?.trim().to_string();
+    let base_url = probe::base_url_or_default(tool, &base_url);
 
-    vscode::configure_vscode_codex(base_url, api_key)
+    let selected = vscode::resolve_profiles(&editors, &profiles)?;
+    vscode::configure_profiles(tool, &selected, base_url, api_key)
 }
 
 /// 获取 VSCode 配置路径
@@ -124,9 +172,8 @@ pub async fn get_vscode_paths() -> Result<Vec<String>, String> {
 pub async fn configure_claude_with_content(config_content: String) -> Result<String, String> {
     use crate::config::{get_claude_settings_path, write_text_file};
 
-    // 验证 JSON 格式
-    let _: serde_json::Value = serde_json::from_str(&config_content)
-        .map_err(|e| format!("配置内容格式错误: {}", e))?;
+    // 验证 JSON 格式与各字段取值
+    claude_config::parse_settings(&config_content)?;
 
     // 获取配置文件路径
     let settings_path = get_claude_settings_path();
@@ -141,7 +188,7 @@ pub async fn configure_claude_with_content(config_content: String) -> Result<Str
 /// 使用自定义内容配置 Codex
 #[tauri::command]
 pub async fn configure_codex_with_content(config_content: String) -> Result<String, String> {
-    use crate::config::{get_codex_config_path, get_codex_auth_path, write_text_file, write_json_file};
+    use crate::config::{get_codex_config_path, get_codex_auth_path};
     use crate::codex_config::CodexAuth;
     use serde_json::Value;
     use std::collections::HashMap;
@@ -150,6 +197,10 @@ pub async fn configure_codex_with_content(config_content: String) -> Result<Stri
     let config_json: Value = serde_json::from_str(&config_content)
         .map_err(|e| format!("配置内容格式错误: {}", e))?;
 
+    // 所有文件和环境变量在同一事务中提交
+    let mut tx = Transaction::new();
+    let mut env_key = None;
+
     // 检查是否包含authJson和configToml（来自前端高级配置）
     if let (Some(auth_json_str), Some(config_toml_str)) =
         (config_json.get("authJson").and_then(|v| v.as_str()),
@@ -165,43 +216,31 @@ pub async fn configure_codex_with_content(config_content: String) -> Result<Stri
                 extra: HashMap::new(),
             };
             let auth_path = get_codex_auth_path();
-            write_json_file(&auth_path, &auth)?;
+            tx.write_json(&auth_path, &auth)?;
 
-            // 设置环境变量 key88
-            env_manager::set_key88_env(api_key.to_string())?;
+            // 设置 config.toml 中当前提供方引用的环境变量（未声明时为 key88）
+            let key = codex_config::active_env_key(config_toml_str)
+                .unwrap_or_else(|| env_manager::DEFAULT_ENV_KEY.to_string());
+            env_manager::stage_env(&mut tx, &key, api_key.to_string())?;
+            env_key = Some(key);
         }
 
-        // 写入config.toml
+        // 暂存config.toml
         let config_path = get_codex_config_path();
-        write_text_file(&config_path, config_toml_str)?;
+        tx.write_text(&config_path, config_toml_str);
 
     } else if let Some(provider) = config_json.get("provider") {
         // 旧格式的兼容处理
         let config_path = get_codex_config_path();
 
-        // 生成 config.toml 内容
+        // 更新 config.toml 中的提供方，保留其他内容
         let base_url = provider.get("api")
             .and_then(|api| api.get("base_url"))
             .and_then(|url| url.as_str())
             .unwrap_or(DEFAULT_CODEX_BASE_URL);
 
-        let config_toml = format!(
-            r#"model_provider = "88code"
-model = "gpt-5-codex"
-model_reasoning_effort = "high"
-disable_response_storage = true
-
-[model_providers.88code]
-name = "88code"
-base_url = "{}"
-wire_api = "responses"
-env_key = "key88"
-requires_openai_auth = true
-"#,
-            base_url
-        );
-
-        write_text_file(&config_path, &config_toml)?;
+        let relay = CodexProvider::relay(base_url, Some(env_manager::DEFAULT_ENV_KEY));
+        codex_config::stage_provider(&mut tx, &config_path, &relay, &CodexModelSettings::default())?;
 
         // 如果有 api_key，也写入 auth.json
         if let Some(api_key) = provider.get("api")
@@ -212,15 +251,321 @@ requires_openai_auth = true
                 extra: HashMap::new(),
             };
             let auth_path = get_codex_auth_path();
-            write_json_file(&auth_path, &auth)?;
+            tx.write_json(&auth_path, &auth)?;
 
             // 设置环境变量 key88
-            env_manager::set_key88_env(api_key.to_string())?;
+            env_manager::stage_env(&mut tx, env_manager::DEFAULT_ENV_KEY, api_key.to_string())?;
+            env_key = Some(env_manager::DEFAULT_ENV_KEY.to_string());
         }
     } else {
         return Err("配置格式不正确".to_string());
     }
 
+    tx.commit()?;
+    if let Some(key) = env_key {
+        profiles::add_managed_env_key(&key)?;
+    }
+
     log::info!("Codex 自定义配置成功");
     Ok("Codex 配置成功（使用自定义内容）！".to_string())
 }
+
+/// 列出配置库中的所有配置
+#[tauri::command]
+pub async fn list_profiles() -> Result<ProfileStore, String> {
+    profiles::list_profiles()
+}
+
+/// 新建配置
+#[tauri::command]
+pub async fn create_profile(profile: ProfileInput) -> Result<Profile, String> {
+    profiles::create_profile(profile)
+}
+
+/// 更新配置
+#[tauri::command]
+pub async fn update_profile(id: String, updates: ProfileUpdate) -> Result<Profile, String> {
+    let profile = profiles::update_profile(&id, updates)?;
+    if let Err(e) = switcher::cleanup_env_keys() {
+        log::warn!("清理环境变量失败: {}", e);
+    }
+    Ok(profile)
+}
+
+/// 删除配置
+#[tauri::command]
+pub async fn delete_profile(id: String) -> Result<(), String> {
+    profiles::delete_profile(&id)?;
+    if let Err(e) = switcher::cleanup_env_keys() {
+        log::warn!("清理环境变量失败: {}", e);
+    }
+    Ok(())
+}
+
+/// 批量导入配置
+#[tauri::command]
+pub async fn import_profiles(profiles: Vec<Profile>) -> Result<usize, String> {
+    profiles::import_profiles(profiles)
+}
+
+/// 应用并激活配置
+#[tauri::command]
+pub async fn activate_profile(id: String) -> Result<String, String> {
+    switcher::activate_profile(&id)
+}
+
+/// 获取密钥库状态
+#[tauri::command]
+pub async fn vault_status() -> Result<vault::VaultStatus, String> {
+    vault::vault_status()
+}
+
+/// 初始化密钥库
+#[tauri::command]
+pub async fn vault_init(passphrase: String) -> Result<(), String> {
+    vault::init_vault(&passphrase)
+}
+
+/// 解锁密钥库
+#[tauri::command]
+pub async fn vault_unlock(passphrase: String) -> Result<(), String> {
+    vault::unlock_vault(&passphrase)
+}
+
+/// 锁定密钥库
+#[tauri::command]
+pub async fn vault_lock() -> Result<(), String> {
+    vault::lock_vault();
+    Ok(())
+}
+
+/// 设置密钥库自动锁定时间（秒）
+#[tauri::command]
+pub async fn vault_set_auto_lock(secs: u64) -> Result<(), String> {
+    vault::set_auto_lock(secs)
+}
+
+/// 存储密钥，返回 vault:<name> 引用
+#[tauri::command]
+pub async fn vault_store_secret(name: String, value: String) -> Result<String, String> {
+    vault::store_secret(&name, &value)
+}
+
+/// 删除密钥
+#[tauri::command]
+pub async fn vault_delete_secret(name: String) -> Result<(), String> {
+    vault::delete_secret(&name)
+}
+
+/// 将配置中的明文 API 密钥移入密钥库，配置中只保留引用
+#[tauri::command]
+pub async fn protect_profile_key(id: String) -> Result<Profile, String> {
+    let profile = profiles::get_profile(&id)?;
+    if profile.api_key.starts_with(vault::SECRET_REF_PREFIX) {
+        return Ok(profile);
+    }
+    if profile.api_key.trim().is_empty() {
+        return Err("该配置没有 API 密钥".to_string());
+    }
+
+    let reference = vault::store_secret(&format!("profile-{}", profile.id), &profile.api_key)?;
+    profiles::update_profile(
+        &id,
+        ProfileUpdate {
+            api_key: Some(reference),
+            ..Default::default()
+        },
+    )
+}
+
+/// 列出备份快照，可按原文件路径过滤
+#[tauri::command]
+pub async fn list_backups(path: Option<String>) -> Result<Vec<backup::Snapshot>, String> {
+    backup::list_snapshots(path.as_deref())
+}
+
+/// 对比快照与当前文件（或另一个快照）
+#[tauri::command]
+pub async fn diff_backup(id: String, against: Option<String>) -> Result<String, String> {
+    backup::diff_snapshot(&id, against.as_deref())
+}
+
+/// 从快照恢复文件
+#[tauri::command]
+pub async fn restore_backup(id: String) -> Result<String, String> {
+    let snapshot = backup::restore_snapshot(&id)?;
+    Ok(format!("已恢复: {}", snapshot.path))
+}
+
+/// 探测服务连通性和 API 密钥有效性（不修改任何配置）
+#[tauri::command]
+pub async fn probe_endpoint(
+    tool: Tool,
+    base_url: String,
+    api_key: String,
+    model: Option<String>,
+    timeout_ms: Option<u64>,
+) -> Result<ProbeResult, String> {
+    if api_key.trim().is_empty() {
+        return Err("API 密钥不能为空".to_string());
+    }
+    let api_key = vault::resolve_secret(&api_key)?;
+
+    Ok(probe::probe(tool, &base_url, api_key.trim(), model.as_deref(), timeout_ms).await)
+}
+
+/// 探测已保存配置的连通性
+#[tauri::command]
+pub async fn probe_profile(id: String, timeout_ms: Option<u64>) -> Result<ProbeResult, String> {
+    let profile = profiles::get_profile(&id)?;
+    let model = profile.models.as_ref().and_then(|m| m.probe_model()).map(str::to_string);
+    probe_endpoint(profile.tool, profile.base_url, profile.api_key, model, timeout_ms).await
+}
+
+/// 获取所有配置的健康状况（最近的探测统计）
+#[tauri::command]
+pub async fn get_health_report() -> Result<Vec<ProfileHealth>, String> {
+    Ok(health::health_report(&profiles::list_profiles()?))
+}
+
+/// 立即探测所有配置，并对开启自动模式的工具切换到最健康的配置
+#[tauri::command]
+pub async fn run_health_check() -> Result<Vec<ProfileHealth>, String> {
+    let (report, _) = health::run_cycle().await?;
+    Ok(report)
+}
+
+/// 开启或关闭某个工具的自动切换
+#[tauri::command]
+pub async fn set_auto_switch(tool: Tool, enabled: bool) -> Result<AutoSwitchSettings, String> {
+    profiles::set_auto_switch(tool, enabled)
+}
+
+/// 设置健康检查间隔（秒）
+#[tauri::command]
+pub async fn set_health_interval(secs: u64) -> Result<AutoSwitchSettings, String> {
+    if secs < health::MIN_INTERVAL_SECS {
+        return Err(format!("健康检查间隔不能小于 {} 秒", health::MIN_INTERVAL_SECS));
+    }
+    profiles::set_health_interval(secs)
+}
+
+/// 获取本地代理状态
+#[tauri::command]
+pub async fn get_proxy_status() -> Result<ProxyStatus, String> {
+    proxy::status()
+}
+
+/// 开启或关闭工具的本地代理模式
+#[tauri::command]
+pub async fn set_proxy_mode(tool: Tool, enabled: bool) -> Result<String, String> {
+    switcher::set_proxy_mode(tool, enabled)
+}
+
+/// 将配置应用到指定项目目录（只影响该项目）
+#[tauri::command]
+pub async fn activate_profile_for_project(id: String, project_dir: String) -> Result<String, String> {
+    projects::apply_to_project(&id, &project_dir)
+}
+
+/// 列出设置了项目级配置的项目
+#[tauri::command]
+pub async fn list_project_overrides() -> Result<Vec<ProjectOverrideInfo>, String> {
+    projects::list_project_overrides()
+}
+
+/// 列出 Codex config.toml 中的模型提供方
+#[tauri::command]
+pub async fn get_codex_providers() -> Result<CodexProviders, String> {
+    codex_config::list_providers()
+}
+
+/// 新增或更新 Codex 模型提供方并设为当前提供方
+#[tauri::command]
+pub async fn configure_codex_provider(
+    provider: CodexProvider,
+    settings: Option<CodexModelSettings>,
+) -> Result<String, String> {
+    codex_config::configure_provider(&provider, &settings.unwrap_or_default())?;
+    Ok(format!("Codex 模型提供方 {} 已更新", provider.id))
+}
+
+/// 列出 Codex config.toml 中的 profiles
+#[tauri::command]
+pub async fn get_codex_profiles() -> Result<CodexProfiles, String> {
+    codex_config::list_profiles()
+}
+
+/// 新增或更新 Codex profile
+#[tauri::command]
+pub async fn save_codex_profile(profile: CodexProfile) -> Result<String, String> {
+    codex_config::save_profile(&profile)?;
+    Ok(format!("Codex profile {} 已保存", profile.name))
+}
+
+/// 重命名 Codex profile
+#[tauri::command]
+pub async fn rename_codex_profile(old_name: String, new_name: String) -> Result<String, String> {
+    codex_config::rename_profile(&old_name, new_name.trim())?;
+    Ok(format!("Codex profile 已重命名为 {}", new_name.trim()))
+}
+
+/// 删除 Codex profile
+#[tauri::command]
+pub async fn delete_codex_profile(name: String) -> Result<String, String> {
+    codex_config::delete_profile(&name)?;
+    Ok(format!("Codex profile {} 已删除", name))
+}
+
+/// 设置默认 Codex profile（为空时不使用 profile）
+#[tauri::command]
+pub async fn set_default_codex_profile(name: Option<String>) -> Result<String, String> {
+    let name = name.as_deref().map(str::trim).filter(|n| !n.is_empty());
+    codex_config::set_default_profile(name)?;
+    Ok(match name {
+        Some(name) => format!("Codex 默认 profile 已设为 {}", name),
+        None => "已取消 Codex 默认 profile".to_string(),
+    })
+}
+
+/// 清理不再被任何配置引用的环境变量
+#[tauri::command]
+pub async fn cleanup_env_vars() -> Result<Vec<String>, String> {
+    switcher::cleanup_env_keys()
+}
+
+/// 获取各 shell 配置文件及 XG-switch 托管块的状态
+#[tauri::command]
+pub async fn get_shell_integrations() -> Result<Vec<env_manager::ShellInfo>, String> {
+    env_manager::list_shells()
+}
+
+/// 在 shell 配置文件中安装 source 环境变量文件的语句（不指定时安装到检测到的 shell）
+#[tauri::command]
+pub async fn install_shell_integration(
+    shells: Option<Vec<env_manager::Shell>>,
+) -> Result<Vec<String>, String> {
+    let shells = match shells {
+        Some(shells) => shells,
+        None => env_manager::default_shells()?,
+    };
+    env_manager::install_shell_integration(&shells)
+}
+
+/// 从 shell 配置文件中移除 XG-switch 托管块
+#[tauri::command]
+pub async fn uninstall_shell_integration() -> Result<Vec<String>, String> {
+    env_manager::uninstall_shell_integration()
+}
+
+/// 撤销 XG-switch 对某个工具所做的修改（restore 为 true 时恢复首次修改前的快照）
+#[tauri::command]
+pub async fn unconfigure_tool(tool: Tool, restore: bool) -> Result<UnconfigureReport, String> {
+    uninstall::unconfigure_tool(tool, restore)
+}
+
+/// 撤销 XG-switch 对所有工具所做的修改
+#[tauri::command]
+pub async fn reset_all_config(restore: bool) -> Result<UnconfigureReport, String> {
+    uninstall::reset_all(restore)
+}
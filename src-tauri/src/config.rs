@@ -3,6 +3,12 @@ use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
+/// 默认的 Base URL
+pub const DEFAULT_CLAUDE_BASE_URL: &str = "https://www.88code.org/api";
+pub const DEFAULT_CODEX_BASE_URL: &str = "https://88code.org/openai/v1";
+/// Gemini 未指定 Base URL 时使用 Google 官方服务
+pub const DEFAULT_GEMINI_BASE_URL: &str = "https://generativelanguage.googleapis.com";
+
 /// 获取 Claude Code 配置目录路径
 pub fn get_claude_config_dir() -> PathBuf {
     dirs::home_dir()
@@ -32,13 +38,91 @@ pub fn get_codex_config_path() -> PathBuf {
     get_codex_config_dir().join("config.toml")
 }
 
+/// 获取 Gemini CLI 配置目录路径
+pub fn get_gemini_config_dir() -> PathBuf {
+    dirs::home_dir()
+        .expect("无法获取用户主目录")
+        .join(".gemini")
+}
+
+/// 获取 Gemini CLI settings.json 路径
+pub fn get_gemini_settings_path() -> PathBuf {
+    get_gemini_config_dir().join("settings.json")
+}
+
+/// 获取 Gemini CLI .env 路径
+pub fn get_gemini_env_path() -> PathBuf {
+    get_gemini_config_dir().join(".env")
+}
+
+/// 获取项目级 Claude Code 配置路径（仅对该项目生效，优先级高于全局配置）
+pub fn get_project_claude_settings_path(project_dir: &Path) -> PathBuf {
+    project_dir.join(".claude").join("settings.local.json")
+}
+
+/// 获取项目级 Codex 配置目录（运行 Codex 时通过 CODEX_HOME 指定）
+pub fn get_project_codex_dir(project_dir: &Path) -> PathBuf {
+    project_dir.join(".codex")
+}
+
+/// 获取项目级 Gemini CLI 配置目录（Gemini CLI 会优先读取项目目录下的 .gemini）
+pub fn get_project_gemini_dir(project_dir: &Path) -> PathBuf {
+    project_dir.join(".gemini")
+}
+
+/// 获取 XG-switch 自身的数据目录（与 Tauri 的 app_data_dir 一致）
+pub fn get_app_data_dir() -> PathBuf {
+    dirs::data_dir()
+        .or_else(dirs::home_dir)
+        .expect("无法获取用户数据目录")
+        .join("com.xgswitch.app")
+}
+
+/// 获取配置库 profiles.json 路径
+pub fn get_profiles_path() -> PathBuf {
+    get_app_data_dir().join("profiles.json")
+}
+
+/// 获取加密密钥库 vault.json 路径
+pub fn get_vault_path() -> PathBuf {
+    get_app_data_dir().join("vault.json")
+}
+
+/// 获取 shell 环境变量文件目录 (~/.config/xg-switch)，shell 配置文件只需 source 其中的文件
+pub fn get_shell_env_dir() -> PathBuf {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| dirs::home_dir().map(|home| home.join(".config")))
+        .expect("无法获取用户主目录")
+        .join("xg-switch")
+}
+
+/// 获取备份快照目录路径
+pub fn get_backups_dir() -> PathBuf {
+    get_app_data_dir().join("backups")
+}
+
 /// 原子写入：写入临时文件后 rename 替换，避免半写状态
 pub fn atomic_write(path: &Path, data: &[u8]) -> Result<(), String> {
+    write_atomically(path, data, false)
+}
+
+/// private 为 true 时（文件包含密钥）Unix 下以 0600 权限创建，否则沿用原文件的权限
+fn write_atomically(path: &Path, data: &[u8], private: bool) -> Result<(), String> {
+    #[cfg(not(unix))]
+    let _ = private;
+
     // 确保父目录存在
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent).map_err(|e| format!("创建目录失败: {}", e))?;
     }
 
+    // 覆盖前为原文件创建快照，备份失败不阻止写入
+    if let Err(e) = crate::backup::snapshot_before_write(path) {
+        log::warn!("备份 {:?} 失败: {}", path, e);
+    }
+
     // 生成临时文件路径（带纳秒时间戳）
     let parent = path.parent().ok_or_else(|| "无效的路径".to_string())?;
     let mut tmp = parent.to_path_buf();
@@ -55,7 +139,15 @@ pub fn atomic_write(path: &Path, data: &[u8]) -> Result<(), String> {
 
     // 写入临时文件
     {
-        let mut f = fs::File::create(&tmp)
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        if private {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut f = options
+            .open(&tmp)
             .map_err(|e| format!("创建临时文件失败: {}", e))?;
         f.write_all(data)
             .map_err(|e| format!("写入临时文件失败: {}", e))?;
@@ -65,7 +157,7 @@ pub fn atomic_write(path: &Path, data: &[u8]) -> Result<(), String> {
 
     // Unix 系统：复制原文件权限
     #[cfg(unix)]
-    {
+    if !private {
         use std::os::unix::fs::PermissionsExt;
         if let Ok(meta) = fs::metadata(path) {
             let perm = meta.permissions().mode();
@@ -101,7 +193,15 @@ pub fn write_json_file<T: Serialize>(path: &Path, data: &T) -> Result<(), String
     atomic_write(path, json.as_bytes())
 }
 
+/// 写入包含密钥的 JSON 文件，Unix 下权限为 0600
+pub fn write_private_json_file<T: Serialize>(path: &Path, data: &T) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(data)
+        .map_err(|e| format!("序列化 JSON 失败: {}", e))?;
+    write_atomically(path, json.as_bytes(), true)
+}
+
 /// 原子写入文本文件（用于 TOML/纯文本）
+#[cfg(feature = "gui")]
 pub fn write_text_file(path: &Path, data: &str) -> Result<(), String> {
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)
@@ -124,6 +224,7 @@ pub fn read_json_file<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<T, St
 }
 
 /// 获取当前系统的配置路径信息（用于前端显示）
+#[cfg(feature = "gui")]
 #[derive(Serialize)]
 pub struct ConfigPaths {
     pub claude_dir: String,
@@ -131,8 +232,12 @@ pub struct ConfigPaths {
     pub codex_dir: String,
     pub codex_auth: String,
     pub codex_config: String,
+    pub gemini_dir: String,
+    pub gemini_settings: String,
+    pub gemini_env: String,
 }
 
+#[cfg(feature = "gui")]
 pub fn get_config_paths_info() -> ConfigPaths {
     ConfigPaths {
         claude_dir: get_claude_config_dir().to_string_lossy().to_string(),
@@ -140,5 +245,8 @@ pub fn get_config_paths_info() -> ConfigPaths {
         codex_dir: get_codex_config_dir().to_string_lossy().to_string(),
         codex_auth: get_codex_auth_path().to_string_lossy().to_string(),
         codex_config: get_codex_config_path().to_string_lossy().to_string(),
+        gemini_dir: get_gemini_config_dir().to_string_lossy().to_string(),
+        gemini_settings: get_gemini_settings_path().to_string_lossy().to_string(),
+        gemini_env: get_gemini_env_path().to_string_lossy().to_string(),
     }
 }
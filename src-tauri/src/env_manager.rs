@@ -1,25 +1,104 @@
+use crate::transaction::Transaction;
+use serde::{Deserialize, Serialize};
+#[cfg(windows)]
 use std::process::Command;
 
 #[cfg(not(windows))]
 use std::env;
 #[cfg(not(windows))]
-use std::fs::{self, OpenOptions};
+use crate::config::{atomic_write, get_shell_env_dir};
 #[cfg(not(windows))]
-use std::io::{BufRead, BufReader, Write};
+use std::fs;
 #[cfg(not(windows))]
 use std::path::PathBuf;
 
-/// 设置环境变量 key88 (跨平台)
-pub fn set_key88_env(api_key: String) -> Result<(), String> {
+/// 托管块的起止标记
+#[cfg(not(windows))]
+const BLOCK_BEGIN: &str = "# >>> XG-switch managed block >>>";
+#[cfg(not(windows))]
+const BLOCK_END: &str = "# <<< XG-switch managed block <<<";
+#[cfg(not(windows))]
+const BLOCK_NOTICE: &str = "# 由 XG-switch 自动维护，请勿手动修改此块";
+
+/// 环境变量文件开头的说明
+#[cfg(not(windows))]
+const ENV_FILE_NOTICE: &str = "# 由 XG-switch 自动生成，切换配置时会被覆盖，请勿手动修改";
+
+/// shell 钩子读取的状态文件和 nushell 使用的环境变量文件
+#[cfg(not(windows))]
+const STATE_FILE: &str = "state";
+#[cfg(not(windows))]
+const ENV_JSON_FILE: &str = "env.json";
+
+/// 旧版本追加 export 时使用的标记注释
+#[cfg(not(windows))]
+const LEGACY_MARKER: &str = "# Added by 88code-desktop";
+
+/// 未指定时 Codex 读取 API 密钥的环境变量名
+pub const DEFAULT_ENV_KEY: &str = "key88";
+
+/// 校验环境变量名（字母或下划线开头，只包含字母、数字和下划线）
+pub fn validate_env_key(key: &str) -> Result<(), String> {
+    let mut chars = key.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(format!("环境变量名无效: {}", key));
+    }
+    Ok(())
+}
+
+/// 设置用户级环境变量 (跨平台)
+pub fn set_env(key: &str, value: &str) -> Result<(), String> {
+    validate_env_key(key)?;
+
+    #[cfg(windows)]
+    {
+        set_windows_env(key, value)
+    }
+
+    #[cfg(not(windows))]
+    {
+        set_unix_env(key, value)
+    }
+}
+
+/// 删除用户级环境变量 (跨平台)
+pub fn remove_env(key: &str) -> Result<(), String> {
+    validate_env_key(key)?;
+
     #[cfg(windows)]
     {
-        set_windows_env("key88", &api_key)
+        remove_windows_env(key)
     }
 
     #[cfg(not(windows))]
     {
-        set_unix_env("key88", &api_key)
+        remove_unix_env(key)
+    }
+}
+
+/// 将设置环境变量的步骤加入事务，Unix 下同时保护环境变量文件（首次写入时还有 shell 配置文件）
+pub fn stage_env(tx: &mut Transaction, key: &str, value: String) -> Result<(), String> {
+    validate_env_key(key)?;
+
+    #[cfg(not(windows))]
+    {
+        for path in env_file_paths() {
+            tx.guard(&path);
+        }
+        if !Shell::Bash.env_file_path().exists() {
+            for shell in target_shells()? {
+                tx.guard(&shell.rc_path()?);
+            }
+        }
     }
+
+    let key = key.to_string();
+    tx.step(format!("设置环境变量 {}", key), move || set_env(&key, &value));
+    Ok(())
 }
 
 /// Windows: 使用 setx 命令设置用户环境变量
@@ -40,131 +119,710 @@ fn set_windows_env(key: &str, value: &str) -> Result<(), String> {
     Ok(())
 }
 
-/// Unix/macOS: 写入 shell 配置文件
-#[cfg(not(windows))]
-fn set_unix_env(key: &str, value: &str) -> Result<(), String> {
-    let shell_config = detect_shell_config()?;
-    append_env_to_shell_config(&shell_config, key, value)?;
-
-    log::info!(
-        "Unix 环境变量已添加到: {:?}, {}={}",
-        shell_config,
-        key,
-        value
-    );
+/// Windows: 从注册表删除用户环境变量
+#[cfg(windows)]
+fn remove_windows_env(key: &str) -> Result<(), String> {
+    let output = Command::new("reg")
+        .args(["delete", "HKCU\\Environment", "/v", key, "/f"])
+        .output()
+        .map_err(|e| format!("执行 reg 命令失败: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("删除环境变量失败: {}", stderr));
+    }
+
+    log::info!("Windows 环境变量已删除: {}", key);
     Ok(())
 }
 
-/// 检测当前使用的 shell 配置文件
+/// 支持 shell 集成的 shell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Nushell,
+    /// POSIX 登录 shell 读取的 ~/.profile
+    Posix,
+}
+
+/// 某个 shell 的集成状态（用于前端显示）
+#[derive(Debug, Clone, Serialize)]
+pub struct ShellInfo {
+    pub shell: Shell,
+    pub rc_path: String,
+    pub exists: bool,
+    /// 配置文件中是否已有 source 环境变量文件的托管块
+    pub installed: bool,
+    /// 该 shell 读取的环境变量文件
+    pub env_path: String,
+}
+
+impl Shell {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+            Shell::Nushell => "nushell",
+            Shell::Posix => "posix",
+        }
+    }
+
+    /// 生成设置环境变量的语句
+    pub fn render_var(self, key: &str, value: &str) -> String {
+        match self {
+            Shell::Bash | Shell::Zsh | Shell::Posix => format!("export {}={}", key, sh_quote(value)),
+            Shell::Fish => format!("set -gx {} {}", key, fish_quote(value)),
+            Shell::Nushell => format!("$env.{} = {}", key, nu_quote(value)),
+        }
+    }
+
+    /// 根据 $SHELL 识别当前 shell
+    pub fn current() -> Option<Shell> {
+        let shell = std::env::var("SHELL").ok()?;
+        match shell.rsplit('/').next().unwrap_or_default() {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            "nu" => Some(Shell::Nushell),
+            "sh" | "dash" | "ksh" => Some(Shell::Posix),
+            _ => None,
+        }
+    }
+}
+
 #[cfg(not(windows))]
-fn detect_shell_config() -> Result<PathBuf, String> {
-    let home = dirs::home_dir().ok_or_else(|| "无法获取用户主目录".to_string())?;
+impl Shell {
+    const ALL: [Shell; 5] = [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::Nushell, Shell::Posix];
+
+    /// shell 的配置文件路径
+    fn rc_path(self) -> Result<PathBuf, String> {
+        let home = dirs::home_dir().ok_or_else(|| "无法获取用户主目录".to_string())?;
+        let config_dir = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".config"));
 
-    // 检查 SHELL 环境变量
-    if let Ok(shell) = env::var("SHELL") {
-        if shell.contains("zsh") {
-            return Ok(home.join(".zshrc"));
-        } else if shell.contains("bash") {
-            // macOS 优先使用 .bash_profile
-            #[cfg(target_os = "macos")]
-            {
-                let bash_profile = home.join(".bash_profile");
-                if bash_profile.exists() {
-                    return Ok(bash_profile);
+        Ok(match self {
+            Shell::Bash => {
+                // macOS 终端默认启动登录 shell，优先使用 .bash_profile
+                #[cfg(target_os = "macos")]
+                {
+                    let bash_profile = home.join(".bash_profile");
+                    if bash_profile.exists() {
+                        return Ok(bash_profile);
+                    }
                 }
+                home.join(".bashrc")
             }
+            Shell::Zsh => env::var_os("ZDOTDIR")
+                .map(PathBuf::from)
+                .unwrap_or(home)
+                .join(".zshrc"),
+            Shell::Fish => config_dir.join("fish").join("config.fish"),
+            Shell::Nushell => dirs::config_dir()
+                .unwrap_or(config_dir)
+                .join("nushell")
+                .join("env.nu"),
+            Shell::Posix => home.join(".profile"),
+        })
+    }
 
-            return Ok(home.join(".bashrc"));
-        } else if shell.contains("fish") {
-            return Ok(home.join(".config/fish/config.fish"));
+    /// shell 读取的环境变量文件（bash、zsh 与 POSIX shell 共用 env.sh）
+    fn env_file_path(self) -> PathBuf {
+        let name = match self {
+            Shell::Bash | Shell::Zsh | Shell::Posix => "env.sh",
+            Shell::Fish => "env.fish",
+            Shell::Nushell => "env.nu",
+        };
+        get_shell_env_dir().join(name)
+    }
+
+    /// 解析由 render_var 生成的语句
+    fn parse_var(self, line: &str) -> Option<(String, String)> {
+        let line = line.trim();
+        match self {
+            Shell::Bash | Shell::Zsh | Shell::Posix => {
+                let (key, value) = line.strip_prefix("export ")?.split_once('=')?;
+                let value = value.strip_prefix('\'')?.strip_suffix('\'')?;
+                Some((key.to_string(), value.replace(r"'\''", "'")))
+            }
+            Shell::Fish => {
+                let (key, value) = line.strip_prefix("set -gx ")?.split_once(' ')?;
+                let value = value.strip_prefix('\'')?.strip_suffix('\'')?;
+                Some((key.to_string(), unescape(value)))
+            }
+            Shell::Nushell => {
+                let (key, value) = line.strip_prefix("$env.")?.split_once(" = ")?;
+                let value = value.strip_prefix('"')?.strip_suffix('"')?;
+                Some((key.to_string(), unescape(value)))
+            }
         }
     }
 
-    // 默认尝试 zsh (大多数现代系统)
-    let zshrc = home.join(".zshrc");
-    if zshrc.exists() {
-        return Ok(zshrc);
+    /// 生成加载环境变量文件的语句（托管块的内容）
+    fn render_source(self) -> String {
+        let path = self.env_file_path().to_string_lossy().to_string();
+        match self {
+            Shell::Bash | Shell::Zsh | Shell::Posix => {
+                let path = sh_quote(&path);
+                format!("[ -f {} ] && . {}", path, path)
+            }
+            Shell::Fish => {
+                let path = fish_quote(&path);
+                format!("test -f {}; and source {}", path, path)
+            }
+            // nushell 在解析时加载文件，安装时会确保文件存在
+            Shell::Nushell => format!("source {}", nu_quote(&path)),
+        }
     }
+}
+
+/// 用单引号包裹，供 POSIX shell 使用
+fn sh_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
 
-    // 回退到 bashrc
-    Ok(home.join(".bashrc"))
+/// 用单引号包裹并转义，供 fish 使用
+fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+/// 用双引号包裹并转义，供 nushell 使用
+fn nu_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', r"\\").replace('"', "\\\""))
+}
+
+/// 还原反斜杠转义
+#[cfg(not(windows))]
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(next) = chars.next() {
+                result.push(next);
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
 }
 
-/// 将环境变量添加到 shell 配置文件（避免重复）
+/// 默认安装集成的 shell：当前 $SHELL 对应的 shell，以及配置文件已存在的其他 shell
 #[cfg(not(windows))]
-fn append_env_to_shell_config(config_path: &PathBuf, key: &str, value: &str) -> Result<(), String> {
-    // 创建配置文件(如果不存在)
-    if !config_path.exists() {
-        if let Some(parent) = config_path.parent() {
-            fs::create_dir_all(parent).map_err(|e| format!("创建配置目录失败: {}", e))?;
+fn target_shells() -> Result<Vec<Shell>, String> {
+    let current = Shell::current();
+
+    let mut shells = Vec::new();
+    for shell in Shell::ALL {
+        if Some(shell) == current || shell.rc_path()?.exists() {
+            shells.push(shell);
         }
-        fs::File::create(config_path).map_err(|e| format!("创建配置文件失败: {}", e))?;
     }
 
-    // 读取现有内容，检查是否已存在该变量
-    let file = fs::File::open(config_path).map_err(|e| format!("打开配置文件失败: {}", e))?;
-    let reader = BufReader::new(file);
+    // 无法识别当前 shell 且没有任何配置文件时，回退到 bash
+    if shells.is_empty() {
+        shells.push(Shell::Bash);
+    }
+    Ok(shells)
+}
+
+/// 读取托管块中直接设置的变量（早期版本把变量写在托管块里，安装时迁移到环境变量文件）
+#[cfg(not(windows))]
+fn read_block(shell: Shell, content: &str) -> Vec<(String, String)> {
+    let mut inside = false;
+    let mut vars = Vec::new();
+    for line in content.lines() {
+        match line.trim() {
+            BLOCK_BEGIN => inside = true,
+            BLOCK_END => inside = false,
+            other if inside => vars.extend(shell.parse_var(other)),
+            _ => {}
+        }
+    }
+    vars
+}
 
-    let marker = format!("export {}=", key);
-    let mut already_exists = false;
+/// 用新的托管块替换文件中的托管块（没有则追加到末尾），body 为 None 时删除托管块；
+/// 托管块以外的内容保持不变。托管块缺少结束标记时报错，避免把开始标记之后的内容当作块内容删除
+#[cfg(not(windows))]
+fn write_block(content: &str, body: Option<&str>) -> Result<String, String> {
+    let block = body
+        .map(|body| format!("{}\n{}\n{}\n{}\n", BLOCK_BEGIN, BLOCK_NOTICE, body, BLOCK_END))
+        .unwrap_or_default();
 
-    for line in reader.lines() {
-        if let Ok(line) = line {
-            if line.trim().starts_with(&marker) {
-                already_exists = true;
-                break;
+    let mut before = String::new();
+    let mut after = String::new();
+    let mut state = 0; // 0: 块之前, 1: 块内, 2: 块之后
+    let mut begin_line = 0;
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        match (state, line.trim()) {
+            (0, BLOCK_BEGIN) => {
+                state = 1;
+                begin_line = index + 1;
             }
+            (1, BLOCK_BEGIN) => break,
+            (1, BLOCK_END) => state = 2,
+            (0, _) => before.push_str(line),
+            (2, _) => after.push_str(line),
+            _ => {}
+        }
+    }
+
+    if state == 1 {
+        return Err(format!(
+            "第 {} 行的 XG-switch 托管块缺少结束标记 \"{}\"，请手动修复后重试",
+            begin_line, BLOCK_END
+        ));
+    }
+
+    if state == 0 {
+        // 没有托管块：追加到末尾，与已有内容之间空一行
+        if block.is_empty() {
+            return Ok(content.to_string());
+        }
+        let mut result = content.to_string();
+        if !result.is_empty() && !result.ends_with('\n') {
+            result.push('\n');
+        }
+        if !result.is_empty() && !result.ends_with("\n\n") {
+            result.push('\n');
+        }
+        result.push_str(&block);
+        return Ok(result);
+    }
+
+    // 删除位于末尾的托管块时，一并去掉追加时插入的空行
+    if block.is_empty() && after.is_empty() && before.ends_with("\n\n") {
+        before.pop();
+    }
+    Ok(format!("{}{}{}", before, block, after))
+}
+
+/// 删除旧版本追加的 `# Added by 88code-desktop` 标记及其下的 `export KEY="value"` 行
+/// （连同追加时插入的空行），返回删除后的内容和其中设置的变量；其余内容保持不变
+#[cfg(not(windows))]
+fn strip_legacy_env_lines(content: &str) -> (String, Vec<(String, String)>) {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let mut result = String::with_capacity(content.len());
+    let mut vars = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let legacy = (lines[i].trim() == LEGACY_MARKER)
+            .then(|| lines.get(i + 1))
+            .flatten()
+            .and_then(|line| line.trim().strip_prefix("export "))
+            .and_then(|assign| assign.split_once('='));
+        let Some((key, value)) = legacy else {
+            result.push_str(lines[i]);
+            i += 1;
+            continue;
+        };
+
+        let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+        vars.push((key.to_string(), value.to_string()));
+        i += 2;
+        // 旧版本在标记前插入了空行：已删除段落前后都是空行时只保留一个
+        let blank_before = result.is_empty() || result.ends_with("\n\n");
+        if blank_before && lines.get(i).is_some_and(|l| l.trim().is_empty()) {
+            i += 1;
         }
     }
+    (result, vars)
+}
 
-    // 如果已存在，先删除旧的，再添加新的
-    if already_exists {
-        remove_env_from_shell_config(config_path, key)?;
+/// 读取环境变量文件中的变量（以 env.sh 为准）
+#[cfg(not(windows))]
+fn read_env_vars() -> Result<Vec<(String, String)>, String> {
+    let path = Shell::Bash.env_file_path();
+    if !path.exists() {
+        return Ok(Vec::new());
     }
+    let content = fs::read_to_string(&path).map_err(|e| format!("读取环境变量文件失败: {}", e))?;
+    Ok(content.lines().filter_map(|line| Shell::Bash.parse_var(line)).collect())
+}
+
+/// 各自拥有独立环境变量文件的 shell（zsh 与 posix 共用 env.sh）
+#[cfg(not(windows))]
+const ENV_FILE_SHELLS: [Shell; 3] = [Shell::Bash, Shell::Fish, Shell::Nushell];
 
-    // 追加新的环境变量
-    let mut file = OpenOptions::new()
-        .append(true)
-        .open(config_path)
-        .map_err(|e| format!("打开配置文件失败: {}", e))?;
+/// write_env_files 写入的所有文件，事务回滚时需要一并恢复
+#[cfg(not(windows))]
+fn env_file_paths() -> Vec<PathBuf> {
+    let dir = get_shell_env_dir();
+    ENV_FILE_SHELLS
+        .iter()
+        .map(|shell| shell.env_file_path())
+        .chain([dir.join(ENV_JSON_FILE), dir.join(STATE_FILE)])
+        .collect()
+}
 
-    let env_line = format!("\n# Added by 88code-desktop\nexport {}=\"{}\"\n", key, value);
-    file.write_all(env_line.as_bytes())
-        .map_err(|e| format!("写入配置文件失败: {}", e))?;
+/// 原子写入 env.sh、env.fish、env.nu 和供 nushell 钩子读取的 env.json，
+/// 最后更新状态文件通知 shell 钩子重新加载
+#[cfg(not(windows))]
+fn write_env_files(vars: &[(String, String)]) -> Result<(), String> {
+    let dir = get_shell_env_dir();
+    fs::create_dir_all(&dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    for shell in ENV_FILE_SHELLS {
+        let mut content = format!("{}\n", ENV_FILE_NOTICE);
+        for (key, value) in vars {
+            content.push_str(&shell.render_var(key, value));
+            content.push('\n');
+        }
+        atomic_write(&shell.env_file_path(), content.as_bytes())?;
+    }
+
+    let json: serde_json::Map<String, serde_json::Value> = vars
+        .iter()
+        .map(|(k, v)| (k.clone(), serde_json::Value::String(v.clone())))
+        .collect();
+    let json = serde_json::to_string_pretty(&json).map_err(|e| format!("序列化环境变量失败: {}", e))?;
+    atomic_write(&dir.join(ENV_JSON_FILE), json.as_bytes())?;
 
+    // 状态文件：第一行为版本号（每次写入都会变化），第二行为当前设置的变量名
+    let generation = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let keys: Vec<&str> = vars.iter().map(|(k, _)| k.as_str()).collect();
+    let state = format!("{}\n{}\n", generation, keys.join(" "));
+    atomic_write(&dir.join(STATE_FILE), state.as_bytes())
+}
+
+/// 在环境变量文件中设置或删除变量，首次写入时自动为检测到的 shell 安装集成
+#[cfg(not(windows))]
+fn update_unix_env(key: &str, value: Option<&str>) -> Result<(), String> {
+    let first_use = !Shell::Bash.env_file_path().exists();
+    if first_use && value.is_none() {
+        return Ok(());
+    }
+    let mut vars = read_env_vars()?;
+    match (value, vars.iter_mut().find(|(k, _)| k == key)) {
+        (Some(value), Some(existing)) => existing.1 = value.to_string(),
+        (Some(value), None) => vars.push((key.to_string(), value.to_string())),
+        (None, _) => vars.retain(|(k, _)| k != key),
+    }
+    write_env_files(&vars)?;
+
+    if first_use {
+        install_shell_integration(&target_shells()?)?;
+    }
     Ok(())
 }
 
-/// 从 shell 配置文件中删除指定环境变量
+/// Unix/macOS: 写入环境变量文件，新开的终端生效
 #[cfg(not(windows))]
-fn remove_env_from_shell_config(config_path: &PathBuf, key: &str) -> Result<(), String> {
-    let content = fs::read_to_string(config_path)
-        .map_err(|e| format!("读取配置文件失败: {}", e))?;
+fn set_unix_env(key: &str, value: &str) -> Result<(), String> {
+    update_unix_env(key, Some(value))?;
+    log::info!("Unix 环境变量 {} 已写入 {:?}", key, get_shell_env_dir());
+    Ok(())
+}
 
-    let marker = format!("export {}=", key);
-    let mut new_lines = Vec::new();
-    let mut skip_next_blank = false;
+/// Unix/macOS: 从环境变量文件中删除
+#[cfg(not(windows))]
+fn remove_unix_env(key: &str) -> Result<(), String> {
+    update_unix_env(key, None)?;
+    log::info!("Unix 环境变量 {} 已删除", key);
+    Ok(())
+}
 
-    for line in content.lines() {
-        if line.trim().starts_with(&marker) {
-            skip_next_blank = true; // 跳过环境变量行
-            continue;
+/// 在 shell 配置文件中安装 source 环境变量文件的托管块（已安装时不重复写入），
+/// 并把旧版本直接写在配置文件中的变量迁移到环境变量文件；返回被修改的配置文件
+pub fn install_shell_integration(shells: &[Shell]) -> Result<Vec<String>, String> {
+    #[cfg(windows)]
+    {
+        let _ = shells;
+        Err("Windows 使用用户环境变量，无需安装 shell 集成".to_string())
+    }
+
+    #[cfg(not(windows))]
+    {
+        let mut vars = read_env_vars()?;
+        let mut rc_files = Vec::new();
+
+        for &shell in shells {
+            let rc_path = shell.rc_path()?;
+            let content = if rc_path.exists() {
+                fs::read_to_string(&rc_path).map_err(|e| format!("读取配置文件失败: {}", e))?
+            } else {
+                String::new()
+            };
+
+            // 迁移旧版本写入的变量，环境变量文件中已有的变量优先
+            let (stripped, legacy_vars) = strip_legacy_env_lines(&content);
+            for (key, value) in read_block(shell, &content).into_iter().chain(legacy_vars) {
+                if !vars.iter().any(|(k, _)| *k == key) {
+                    vars.push((key, value));
+                }
+            }
+
+            let new_content = write_block(&stripped, Some(&shell.render_source()))
+                .map_err(|e| format!("{}: {}", rc_path.display(), e))?;
+            rc_files.push((rc_path, content, new_content));
         }
 
-        if skip_next_blank && line.trim() == "" {
-            skip_next_blank = false; // 跳过后面的空行
-            continue;
+        // 先写入环境变量文件，保证 source 的文件存在（nushell 要求）
+        write_env_files(&vars)?;
+
+        let mut updated = Vec::new();
+        for (rc_path, content, new_content) in rc_files {
+            if new_content == content {
+                continue;
+            }
+            if let Some(parent) = rc_path.parent() {
+                fs::create_dir_all(parent).map_err(|e| format!("创建目录失败: {}", e))?;
+            }
+            atomic_write(&rc_path, new_content.as_bytes())?;
+            updated.push(rc_path.to_string_lossy().to_string());
+        }
+
+        log::info!("已安装 shell 集成: {:?}", updated);
+        Ok(updated)
+    }
+}
+
+/// 从所有 shell 配置文件中移除托管块（环境变量文件保留）；返回被修改的配置文件
+pub fn uninstall_shell_integration() -> Result<Vec<String>, String> {
+    #[cfg(windows)]
+    {
+        Err("Windows 使用用户环境变量，无需卸载 shell 集成".to_string())
+    }
+
+    #[cfg(not(windows))]
+    {
+        let mut updated = Vec::new();
+        for shell in Shell::ALL {
+            let rc_path = shell.rc_path()?;
+            if !rc_path.exists() {
+                continue;
+            }
+            let content =
+                fs::read_to_string(&rc_path).map_err(|e| format!("读取配置文件失败: {}", e))?;
+            let new_content =
+                write_block(&content, None).map_err(|e| format!("{}: {}", rc_path.display(), e))?;
+            if new_content != content {
+                atomic_write(&rc_path, new_content.as_bytes())?;
+                updated.push(rc_path.to_string_lossy().to_string());
+            }
         }
 
-        if line.trim() == "# Added by 88code-desktop" {
-            continue; // 跳过标记注释
+        log::info!("已卸载 shell 集成: {:?}", updated);
+        Ok(updated)
+    }
+}
+
+/// 生成 shell 钩子脚本：每次显示提示符前检查状态文件，变化时重新加载环境变量，
+/// 使已打开的终端也能立即使用切换后的配置（用法见 `xg-switch-cli shell-init`）。
+/// POSIX sh 没有提示符钩子（dash 也不保证支持 local），只输出一次性加载 env.sh 的语句
+pub fn shell_init_script(shell: Shell) -> Result<String, String> {
+    #[cfg(windows)]
+    {
+        let _ = shell;
+        Err("Windows 不支持 shell 钩子".to_string())
+    }
+
+    #[cfg(not(windows))]
+    {
+        let dir = get_shell_env_dir();
+        let state = dir.join(STATE_FILE).to_string_lossy().to_string();
+        let env_file = shell.env_file_path().to_string_lossy().to_string();
+
+        let script = match shell {
+            Shell::Posix => format!("[ -f {env} ] && . {env}\n", env = sh_quote(&env_file)),
+            Shell::Bash | Shell::Zsh => {
+                let (split, register) = match shell {
+                    Shell::Zsh => (
+                        "${=_XG_SWITCH_KEYS-}",
+                        "autoload -Uz add-zsh-hook\nadd-zsh-hook precmd _xg_switch_hook".to_string(),
+                    ),
+                    _ => (
+                        "${_XG_SWITCH_KEYS-}",
+                        [
+                            "case \";${PROMPT_COMMAND-};\" in",
+                            "  *\";_xg_switch_hook;\"*) ;;",
+                            "  *) PROMPT_COMMAND=\"_xg_switch_hook${PROMPT_COMMAND:+;$PROMPT_COMMAND}\" ;;",
+                            "esac",
+                        ]
+                        .join("\n"),
+                    ),
+                };
+                format!(
+                    r#"_xg_switch_hook() {{
+  local state keys k
+  {{ IFS= read -r state && IFS= read -r keys; }} 2>/dev/null < {state} || return 0
+  [ "$state" = "${{_XG_SWITCH_STATE-}}" ] && return 0
+  for k in {split}; do unset "$k"; done
+  _XG_SWITCH_STATE=$state
+  _XG_SWITCH_KEYS=$keys
+  [ -f {env} ] && . {env}
+}}
+{register}
+_xg_switch_hook
+"#,
+                    state = sh_quote(&state),
+                    env = sh_quote(&env_file),
+                    split = split,
+                    register = register,
+                )
+            }
+            Shell::Fish => {
+                format!(
+                    r#"function __xg_switch_hook --on-event fish_prompt
+    test -f {state}; or return
+    set -l lines
+    while read -l line
+        set -a lines $line
+    end < {state}
+    test "$lines[1]" = "$__xg_switch_state"; and return
+    for k in $__xg_switch_keys
+        set -eg $k
+    end
+    set -g __xg_switch_state $lines[1]
+    set -g __xg_switch_keys (string split -n ' ' -- "$lines[2]")
+    test -f {env}; and source {env}
+end
+__xg_switch_hook
+"#,
+                    state = fish_quote(&state),
+                    env = fish_quote(&env_file),
+                )
+            }
+            Shell::Nushell => {
+                let env_json = dir.join(ENV_JSON_FILE).to_string_lossy().to_string();
+                format!(
+                    r#"$env.config = ($env.config | upsert hooks.pre_prompt (($env.config.hooks.pre_prompt? | default []) | append {{||
+    let state = (try {{ open --raw {state} | lines | first }} catch {{ "" }})
+    if $state == "" or $state == ($env.XG_SWITCH_STATE? | default "") {{ return }}
+    hide-env --ignore-errors ...($env.XG_SWITCH_KEYS? | default [])
+    let vars = (open {env_json})
+    load-env $vars
+    $env.XG_SWITCH_STATE = $state
+    $env.XG_SWITCH_KEYS = ($vars | columns)
+}}))
+"#,
+                    state = nu_quote(&state),
+                    env_json = nu_quote(&env_json),
+                )
+            }
+        };
+        Ok(script)
+    }
+}
+
+/// 删除环境变量文件目录（~/.config/xg-switch），返回是否删除了目录
+pub fn remove_env_files() -> Result<bool, String> {
+    #[cfg(windows)]
+    {
+        Ok(false)
+    }
+
+    #[cfg(not(windows))]
+    {
+        let dir = get_shell_env_dir();
+        if !dir.exists() {
+            return Ok(false);
+        }
+        fs::remove_dir_all(&dir).map_err(|e| format!("删除环境变量文件失败: {}", e))?;
+        log::info!("已删除环境变量文件: {:?}", dir);
+        Ok(true)
+    }
+}
+
+/// 默认安装 shell 集成的 shell
+pub fn default_shells() -> Result<Vec<Shell>, String> {
+    #[cfg(windows)]
+    {
+        Ok(Vec::new())
+    }
+
+    #[cfg(not(windows))]
+    {
+        target_shells()
+    }
+}
+
+/// 列出各 shell 的集成状态
+pub fn list_shells() -> Result<Vec<ShellInfo>, String> {
+    #[cfg(windows)]
+    {
+        Ok(Vec::new())
+    }
+
+    #[cfg(not(windows))]
+    {
+        let mut list = Vec::new();
+        for shell in Shell::ALL {
+            let rc_path = shell.rc_path()?;
+            let content = fs::read_to_string(&rc_path).unwrap_or_default();
+            list.push(ShellInfo {
+                shell,
+                rc_path: rc_path.to_string_lossy().to_string(),
+                exists: rc_path.exists(),
+                installed: content.lines().any(|l| l.trim() == BLOCK_BEGIN),
+                env_path: shell.env_file_path().to_string_lossy().to_string(),
+            });
         }
+        Ok(list)
+    }
+}
 
-        new_lines.push(line);
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_block_appends_and_replaces() {
+        let content = "alias ll='ls -l'\n";
+        let installed = write_block(content, Some("source ~/env.sh")).unwrap();
+        assert_eq!(
+            installed,
+            format!(
+                "alias ll='ls -l'\n\n{}\n{}\nsource ~/env.sh\n{}\n",
+                BLOCK_BEGIN, BLOCK_NOTICE, BLOCK_END
+            )
+        );
+
+        let replaced = write_block(&format!("{}export A=1\n", installed), Some("source ~/env.fish")).unwrap();
+        assert!(replaced.contains("source ~/env.fish"));
+        assert!(!replaced.contains("source ~/env.sh"));
+        assert!(replaced.starts_with("alias ll='ls -l'\n"));
+        assert!(replaced.ends_with(&format!("{}\nexport A=1\n", BLOCK_END)));
     }
 
-    fs::write(config_path, new_lines.join("\n"))
-        .map_err(|e| format!("写入配置文件失败: {}", e))?;
+    #[test]
+    fn write_block_removes_block_and_blank_line() {
+        let content = "alias ll='ls -l'\n";
+        let installed = write_block(content, Some("source ~/env.sh")).unwrap();
+        assert_eq!(write_block(&installed, None).unwrap(), content);
+        assert_eq!(write_block(content, None).unwrap(), content);
+    }
 
-    Ok(())
+    #[test]
+    fn posix_shell_init_sources_env_once() {
+        let _home = crate::test_support::temp_home();
+        let env_file = sh_quote(&Shell::Posix.env_file_path().to_string_lossy());
+        let script = shell_init_script(Shell::Posix).unwrap();
+        assert_eq!(script, format!("[ -f {} ] && . {}\n", env_file, env_file));
+
+        let bash = shell_init_script(Shell::Bash).unwrap();
+        assert!(bash.contains("PROMPT_COMMAND=") && bash.contains("local state keys k"));
+        let zsh = shell_init_script(Shell::Zsh).unwrap();
+        assert!(zsh.contains("add-zsh-hook precmd _xg_switch_hook") && !zsh.contains("PROMPT_COMMAND"));
+    }
+
+    #[test]
+    fn write_block_rejects_unterminated_block() {
+        let content = format!("alias ll='ls -l'\n{}\nsource ~/env.sh\nexport PATH=$HOME/bin:$PATH\n", BLOCK_BEGIN);
+        let err = write_block(&content, Some("source ~/env.sh")).unwrap_err();
+        assert!(err.contains("第 2 行"), "{}", err);
+        assert!(write_block(&content, None).is_err());
+
+        let nested = format!("{}\n{}\nexport A=1\n{}\n", BLOCK_BEGIN, BLOCK_BEGIN, BLOCK_END);
+        assert!(write_block(&nested, None).is_err());
+    }
 }
@@ -0,0 +1,403 @@
+use crate::config::{get_gemini_config_dir, get_gemini_env_path, get_gemini_settings_path, read_json_file};
+use crate::transaction::Transaction;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Gemini CLI 读取的环境变量
+pub const ENV_API_KEY: &str = "GEMINI_API_KEY";
+pub const ENV_BASE_URL: &str = "GOOGLE_GEMINI_BASE_URL";
+const ENV_MODEL: &str = "GEMINI_MODEL";
+
+/// 使用 API Key 认证时 settings.json 中的认证类型
+const AUTH_TYPE_API_KEY: &str = "gemini-api-key";
+
+/// Gemini CLI settings.json 的结构（只关心认证与模型，其余字段原样保留）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GeminiSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub security: Option<Value>,
+    /// 旧版本 Gemini CLI 使用的顶层认证类型字段
+    #[serde(
+        rename = "selectedAuthType",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub selected_auth_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<Value>,
+    /// 保留未知字段，防止版本更新时丢失新字段
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// 当前 Gemini 配置（用于前端显示）
+#[derive(Debug, Clone, Serialize)]
+pub struct GeminiConfig {
+    pub base_url: Option<String>,
+    pub api_key: Option<String>,
+    pub model: Option<String>,
+    pub auth_type: Option<String>,
+}
+
+/// 解析 .env 中的一行，返回 (键, 值)
+fn parse_env_line(line: &str) -> Option<(String, String)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let line = line.strip_prefix("export ").unwrap_or(line);
+    let (key, value) = line.split_once('=')?;
+    let value = value.trim();
+    let value = if let Some(inner) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        unescape_env_value(inner)
+    } else {
+        value
+            .strip_prefix('\'')
+            .and_then(|v| v.strip_suffix('\''))
+            .unwrap_or(value)
+            .to_string()
+    };
+
+    Some((key.trim().to_string(), value))
+}
+
+/// 还原双引号值中的转义（\n、\r、\"、\\、\$）
+fn unescape_env_value(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some(c @ ('"' | '\\' | '$')) => result.push(c),
+            Some(c) => {
+                result.push('\\');
+                result.push(c);
+            }
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// 生成 .env 中的一行：优先使用单引号（dotenv 按字面读取，不做转义和变量展开）；
+/// 值本身含单引号或换行时改用双引号并转义，保证值始终占一行
+fn format_env_line(key: &str, value: &str) -> String {
+    if !value.contains(['\'', '\n', '\r']) {
+        return format!("{}='{}'", key, value);
+    }
+    let mut escaped = String::with_capacity(value.len() + 2);
+    for c in value.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '"' | '\\' | '$' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    format!("{}=\"{}\"", key, escaped)
+}
+
+/// 读取 .env 文件中的变量
+pub fn read_env_file(path: &Path) -> Result<HashMap<String, String>, String> {
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+
+    let content = fs::read_to_string(path).map_err(|e| format!("读取 .env 失败: {}", e))?;
+    Ok(content.lines().filter_map(parse_env_line).collect())
+}
+
+/// 更新 .env 内容：已有的键原地替换，值为 None 的键删除，新键追加到末尾，其余行保持不变
+fn update_env_content(content: &str, updates: &[(&str, Option<&str>)]) -> String {
+    let mut applied = Vec::new();
+    let mut lines = Vec::new();
+
+    for line in content.lines() {
+        let key = parse_env_line(line).map(|(k, _)| k);
+        match updates.iter().find(|(k, _)| Some(*k) == key.as_deref()) {
+            Some((k, Some(v))) => {
+                if !applied.contains(k) {
+                    lines.push(format_env_line(k, v));
+                    applied.push(*k);
+                }
+            }
+            Some((_, None)) => {}
+            None => lines.push(line.to_string()),
+        }
+    }
+
+    for (k, v) in updates {
+        if let Some(v) = v {
+            if !applied.contains(k) {
+                lines.push(format_env_line(k, v));
+            }
+        }
+    }
+
+    let mut result = lines.join("\n");
+    result.push('\n');
+    result
+}
+
+/// 将 settings.json 的认证方式设为 API Key，并按需设置模型
+fn apply_settings(settings: &mut GeminiSettings, model: Option<&str>) {
+    let mut security = match settings.security.take() {
+        Some(Value::Object(map)) => map,
+        _ => Map::new(),
+    };
+    let mut auth = match security.remove("auth") {
+        Some(Value::Object(map)) => map,
+        _ => Map::new(),
+    };
+    auth.insert(
+        "selectedType".to_string(),
+        Value::String(AUTH_TYPE_API_KEY.to_string()),
+    );
+    security.insert("auth".to_string(), Value::Object(auth));
+    settings.security = Some(Value::Object(security));
+
+    // 旧版本字段存在时同步更新
+    if settings.selected_auth_type.is_some() {
+        settings.selected_auth_type = Some(AUTH_TYPE_API_KEY.to_string());
+    }
+
+    if let Some(model) = model {
+        settings.model = Some(match settings.model.take() {
+            // 新版本格式: "model": { "name": "..." }
+            Some(Value::Object(mut map)) => {
+                map.insert("name".to_string(), Value::String(model.to_string()));
+                Value::Object(map)
+            }
+            // 旧版本格式: "model": "..."
+            Some(Value::String(_)) => Value::String(model.to_string()),
+            _ => serde_json::json!({ "name": model }),
+        });
+    }
+}
+
+/// 将 Gemini 配置（settings.json + .env）暂存到事务中
+pub fn stage_gemini_config(
+    tx: &mut Transaction,
+    base_url: Option<&str>,
+    api_key: &str,
+    model: Option<&str>,
+) -> Result<(), String> {
+    stage_gemini_config_in(tx, &get_gemini_config_dir(), base_url, api_key, model)
+}
+
+/// 将 Gemini 配置暂存到指定的 .gemini 目录（全局或项目级）
+pub fn stage_gemini_config_in(
+    tx: &mut Transaction,
+    gemini_dir: &Path,
+    base_url: Option<&str>,
+    api_key: &str,
+    model: Option<&str>,
+) -> Result<(), String> {
+    let settings_path = gemini_dir.join("settings.json");
+    let env_path = gemini_dir.join(".env");
+
+    // 读取现有配置（如果存在），否则使用默认配置
+    let mut settings: GeminiSettings = if settings_path.exists() {
+        read_json_file(&settings_path)?
+    } else {
+        GeminiSettings::default()
+    };
+    apply_settings(&mut settings, model);
+    tx.write_json(&settings_path, &settings)?;
+
+    // 更新 .env，未指定 base_url 时移除自定义地址以使用官方服务
+    let env_content = if env_path.exists() {
+        fs::read_to_string(&env_path).map_err(|e| format!("读取 .env 失败: {}", e))?
+    } else {
+        String::new()
+    };
+    let mut updates = vec![(ENV_API_KEY, Some(api_key)), (ENV_BASE_URL, base_url)];
+    if let Some(model) = model {
+        updates.push((ENV_MODEL, Some(model)));
+    }
+    tx.write_text(&env_path, &update_env_content(&env_content, &updates));
+
+    log::info!("Gemini 配置已暂存");
+    log::info!("  base_url: {}", base_url.unwrap_or("(官方)"));
+    log::info!("  settings.json: {:?}", settings_path);
+    log::info!("  .env: {:?}", env_path);
+
+    Ok(())
+}
+
+/// 配置 Gemini CLI
+pub fn configure_gemini(base_url: Option<&str>, api_key: &str, model: Option<&str>) -> Result<(), String> {
+    let mut tx = Transaction::new();
+    stage_gemini_config(&mut tx, base_url, api_key, model)?;
+    tx.commit()
+}
+
+/// 读取当前 Gemini 配置
+pub fn get_gemini_config() -> Result<GeminiConfig, String> {
+    let settings_path = get_gemini_settings_path();
+    let settings: GeminiSettings = if settings_path.exists() {
+        read_json_file(&settings_path)?
+    } else {
+        GeminiSettings::default()
+    };
+    let env = read_env_file(&get_gemini_env_path())?;
+
+    let auth_type = settings
+        .security
+        .as_ref()
+        .and_then(|s| s.pointer("/auth/selectedType"))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+        .or(settings.selected_auth_type);
+
+    let model = env.get(ENV_MODEL).cloned().or_else(|| match &settings.model {
+        Some(Value::String(name)) => Some(name.clone()),
+        Some(Value::Object(map)) => map.get("name").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        _ => None,
+    });
+
+    Ok(GeminiConfig {
+        base_url: env.get(ENV_BASE_URL).cloned(),
+        api_key: env.get(ENV_API_KEY).cloned(),
+        model,
+        auth_type,
+    })
+}
+
+/// 从 .env 内容中删除 XG-switch 写入的变量；返回 None 表示文件已没有其他内容，可以删除
+pub fn remove_managed_env(content: &str) -> Option<String> {
+    let updated = update_env_content(
+        content,
+        &[(ENV_API_KEY, None), (ENV_BASE_URL, None), (ENV_MODEL, None)],
+    );
+    if updated.trim().is_empty() {
+        return None;
+    }
+    Some(updated)
+}
+
+/// 从 settings.json 内容中删除 XG-switch 设置的 API Key 认证方式，其余字段保持不变；
+/// 返回 None 表示文件已没有其他内容，可以删除
+pub fn remove_managed_settings(content: &str) -> Result<Option<String>, String> {
+    let mut settings: GeminiSettings =
+        serde_json::from_str(content).map_err(|e| format!("解析 settings.json 失败: {}", e))?;
+
+    if let Some(Value::Object(security)) = settings.security.as_mut() {
+        if let Some(Value::Object(auth)) = security.get_mut("auth") {
+            if auth.get("selectedType").and_then(|v| v.as_str()) == Some(AUTH_TYPE_API_KEY) {
+                auth.remove("selectedType");
+            }
+            if auth.is_empty() {
+                security.remove("auth");
+            }
+        }
+        if security.is_empty() {
+            settings.security = None;
+        }
+    }
+    if settings.selected_auth_type.as_deref() == Some(AUTH_TYPE_API_KEY) {
+        settings.selected_auth_type = None;
+    }
+
+    let value = serde_json::to_value(&settings).map_err(|e| format!("序列化 settings.json 失败: {}", e))?;
+    if value.as_object().is_some_and(Map::is_empty) {
+        return Ok(None);
+    }
+    serde_json::to_string_pretty(&value)
+        .map(Some)
+        .map_err(|e| format!("序列化 settings.json 失败: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const USER_ENV: &str = "# 个人配置\nexport GEMINI_API_KEY=\"old\"\nOTHER_TOOL=keep-me\n\n\
+                            GOOGLE_GEMINI_BASE_URL='https://old.example'\n";
+
+    #[test]
+    fn env_updates_keep_unrelated_lines_and_comments() {
+        let updated = update_env_content(
+            USER_ENV,
+            &[(ENV_API_KEY, Some("sk-new")), (ENV_BASE_URL, None), (ENV_MODEL, Some("gemini-2.5-pro"))],
+        );
+        assert_eq!(
+            updated,
+            "# 个人配置\nGEMINI_API_KEY='sk-new'\nOTHER_TOOL=keep-me\n\nGEMINI_MODEL='gemini-2.5-pro'\n"
+        );
+
+        let removed = remove_managed_env(&updated).unwrap();
+        assert_eq!(removed, "# 个人配置\nOTHER_TOOL=keep-me\n\n");
+        assert_eq!(remove_managed_env("GEMINI_API_KEY='sk'\nGEMINI_MODEL='m'\n"), None);
+    }
+
+    #[test]
+    fn env_values_are_quoted_and_round_trip() {
+        for value in ["sk-$HOME\\x", "it's \"quoted\" $PATH", "line1\nGEMINI_MODEL=evil\r"] {
+            let content = update_env_content("", &[(ENV_API_KEY, Some(value))]);
+            assert_eq!(content.lines().count(), 1, "{content:?}");
+            let parsed: HashMap<_, _> = content.lines().filter_map(parse_env_line).collect();
+            assert_eq!(parsed.get(ENV_API_KEY).map(String::as_str), Some(value));
+            assert!(!parsed.contains_key(ENV_MODEL));
+        }
+        assert_eq!(format_env_line(ENV_API_KEY, "sk-$x\\y"), "GEMINI_API_KEY='sk-$x\\y'");
+    }
+
+    #[test]
+    fn settings_switch_auth_to_api_key_and_back() {
+        let mut settings: GeminiSettings = serde_json::from_value(json!({
+            "security": {
+                "auth": { "selectedType": "oauth-personal", "useExternal": false },
+                "folderTrust": true
+            },
+            "selectedAuthType": "oauth-personal",
+            "model": { "name": "gemini-2.0", "maxSessionTurns": 10 },
+            "theme": "Dracula"
+        }))
+        .unwrap();
+        apply_settings(&mut settings, Some("gemini-2.5-pro"));
+
+        let value = serde_json::to_value(&settings).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "security": {
+                    "auth": { "selectedType": AUTH_TYPE_API_KEY, "useExternal": false },
+                    "folderTrust": true
+                },
+                "selectedAuthType": AUTH_TYPE_API_KEY,
+                "model": { "name": "gemini-2.5-pro", "maxSessionTurns": 10 },
+                "theme": "Dracula"
+            })
+        );
+
+        let removed = remove_managed_settings(&value.to_string()).unwrap().unwrap();
+        let removed: Value = serde_json::from_str(&removed).unwrap();
+        assert_eq!(
+            removed,
+            json!({
+                "security": { "auth": { "useExternal": false }, "folderTrust": true },
+                "model": { "name": "gemini-2.5-pro", "maxSessionTurns": 10 },
+                "theme": "Dracula"
+            })
+        );
+
+        let mut fresh = GeminiSettings::default();
+        apply_settings(&mut fresh, None);
+        let fresh = serde_json::to_string(&fresh).unwrap();
+        assert_eq!(remove_managed_settings(&fresh).unwrap(), None);
+    }
+}
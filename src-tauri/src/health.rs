@@ -0,0 +1,345 @@
+use crate::probe::{self, ProbeOutcome, ProbeResult};
+use crate::profiles::{self, Profile, ProfileStore, Tool};
+use crate::switcher;
+use crate::vault;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+#[cfg(feature = "gui")]
+use tauri::{AppHandle, Emitter};
+
+/// 每个配置保留的最近探测次数
+const WINDOW_SIZE: usize = 20;
+
+/// 健康检查的最短间隔（秒），防止过于频繁地请求中转站
+pub const MIN_INTERVAL_SECS: u64 = 30;
+
+/// 当前配置的平均延迟超过最优配置的倍数时才切换，避免来回抖动
+const LATENCY_SWITCH_RATIO: f64 = 1.5;
+
+/// 自动切换后发送给前端的事件名
+pub const AUTO_SWITCH_EVENT: &str = "profile-auto-switched";
+
+/// 单次探测记录
+#[derive(Debug, Clone)]
+struct Sample {
+    ok: bool,
+    latency_ms: u64,
+}
+
+/// 单个配置的滚动统计
+#[derive(Debug, Clone, Default)]
+struct ProfileStats {
+    samples: VecDeque<Sample>,
+    last: Option<ProbeResult>,
+    last_checked_at: Option<u64>,
+}
+
+static STATS: Mutex<Option<HashMap<String, ProfileStats>>> = Mutex::new(None);
+
+/// 单个配置的健康状况（用于前端显示）
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileHealth {
+    pub profile_id: String,
+    pub tool: Tool,
+    pub name: String,
+    pub samples: usize,
+    /// 最近窗口内的失败比例（0.0 - 1.0）
+    pub error_rate: f64,
+    /// 最近窗口内成功探测的平均延迟
+    pub avg_latency_ms: Option<u64>,
+    pub last_outcome: Option<ProbeOutcome>,
+    pub last_message: Option<String>,
+    pub last_checked_at: Option<u64>,
+}
+
+/// 一次自动切换（作为事件负载发送给前端）
+#[derive(Debug, Clone, Serialize)]
+pub struct AutoSwitch {
+    pub tool: Tool,
+    pub from: Option<String>,
+    pub to: String,
+    pub name: String,
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn record(profile_id: &str, result: ProbeResult) {
+    let mut guard = STATS.lock().unwrap_or_else(|e| e.into_inner());
+    let stats = guard
+        .get_or_insert_with(HashMap::new)
+        .entry(profile_id.to_string())
+        .or_default();
+
+    stats.samples.push_back(Sample {
+        ok: result.outcome == ProbeOutcome::Ok,
+        latency_ms: result.latency_ms,
+    });
+    while stats.samples.len() > WINDOW_SIZE {
+        stats.samples.pop_front();
+    }
+    stats.last = Some(result);
+    stats.last_checked_at = Some(now_millis());
+}
+
+fn health_of(profile: &Profile, stats: Option<&ProfileStats>) -> ProfileHealth {
+    let samples = stats.map(|s| s.samples.len()).unwrap_or(0);
+    let (error_rate, avg_latency_ms) = match stats {
+        Some(stats) if samples > 0 => {
+            let ok: Vec<u64> = stats
+                .samples
+                .iter()
+                .filter(|s| s.ok)
+                .map(|s| s.latency_ms)
+                .collect();
+            let error_rate = 1.0 - ok.len() as f64 / samples as f64;
+            let avg = (!ok.is_empty()).then(|| ok.iter().sum::<u64>() / ok.len() as u64);
+            (error_rate, avg)
+        }
+        _ => (0.0, None),
+    };
+
+    ProfileHealth {
+        profile_id: profile.id.clone(),
+        tool: profile.tool,
+        name: profile.name.clone(),
+        samples,
+        error_rate,
+        avg_latency_ms,
+        last_outcome: stats.and_then(|s| s.last.as_ref()).map(|r| r.outcome),
+        last_message: stats.and_then(|s| s.last.as_ref()).map(|r| r.message.clone()),
+        last_checked_at: stats.and_then(|s| s.last_checked_at),
+    }
+}
+
+/// 获取所有配置的健康状况
+pub fn health_report(store: &ProfileStore) -> Vec<ProfileHealth> {
+    let guard = STATS.lock().unwrap_or_else(|e| e.into_inner());
+    store
+        .profiles
+        .iter()
+        .map(|p| health_of(p, guard.as_ref().and_then(|m| m.get(&p.id))))
+        .collect()
+}
+
+/// 比较两个配置的健康程度：错误率低者优先，其次是平均延迟低者
+pub(crate) fn healthier(a: &ProfileHealth, b: &ProfileHealth) -> std::cmp::Ordering {
+    a.error_rate
+        .partial_cmp(&b.error_rate)
+        .unwrap_or(std::cmp::Ordering::Equal)
+        .then(a.avg_latency_ms.unwrap_or(u64::MAX).cmp(&b.avg_latency_ms.unwrap_or(u64::MAX)))
+}
+
+/// 选出某个工具下最健康的配置（至少成功过一次）
+fn pick_healthiest(report: &[ProfileHealth], tool: Tool) -> Option<&ProfileHealth> {
+    report
+        .iter()
+        .filter(|h| h.tool == tool && h.avg_latency_ms.is_some())
+        .min_by(|a, b| healthier(a, b))
+}
+
+/// 判断是否值得从当前配置切换到最优配置；当前配置没有探测数据（例如密钥库锁定时被跳过）时不切换
+fn should_switch(current: Option<&ProfileHealth>, best: &ProfileHealth) -> bool {
+    let Some(current) = current else {
+        return true;
+    };
+    if current.profile_id == best.profile_id || current.samples == 0 {
+        return false;
+    }
+    if current.last_outcome != Some(ProbeOutcome::Ok) || current.error_rate > best.error_rate {
+        return true;
+    }
+    match (current.avg_latency_ms, best.avg_latency_ms) {
+        (Some(cur), Some(best)) => cur as f64 > best as f64 * LATENCY_SWITCH_RATIO,
+        _ => false,
+    }
+}
+
+/// 探测单个配置；密钥无法解析（例如密钥库已锁定）时跳过
+async fn probe_profile(profile: Profile) -> Option<(String, ProbeResult)> {
+    let api_key = match vault::resolve_secret(&profile.api_key) {
+        Ok(key) => key,
+        Err(e) => {
+            log::warn!("跳过配置 {} 的健康检查: {}", profile.name, e);
+            return None;
+        }
+    };
+    let model = profile.models.as_ref().and_then(|m| m.probe_model());
+    let result = probe::probe(profile.tool, &profile.base_url, api_key.trim(), model, None).await;
+    Some((profile.id, result))
+}
+
+/// 并发探测指定工具下的所有配置并记录结果
+pub async fn check_tools(tools: &[Tool]) -> Result<Vec<ProfileHealth>, String> {
+    let store = profiles::list_profiles()?;
+
+    let handles: Vec<_> = store
+        .profiles
+        .iter()
+        .filter(|p| tools.contains(&p.tool))
+        .cloned()
+        .map(|p| tokio::spawn(probe_profile(p)))
+        .collect();
+
+    for handle in handles {
+        if let Ok(Some((id, result))) = handle.await {
+            record(&id, result);
+        }
+    }
+
+    Ok(health_report(&store))
+}
+
+/// 对开启了自动模式的工具，切换到最健康的配置
+pub fn auto_switch(store: &ProfileStore, report: &[ProfileHealth]) -> Vec<AutoSwitch> {
+    let mut switched = Vec::new();
+
+    for &tool in &store.auto_switch.tools {
+        let Some(best) = pick_healthiest(report, tool) else {
+            continue;
+        };
+        let current_id = store.active.get(tool);
+        let current = current_id.and_then(|id| report.iter().find(|h| &h.profile_id == id));
+        if !should_switch(current, best) {
+            continue;
+        }
+
+        match switcher::activate_profile(&best.profile_id) {
+            Ok(_) => {
+                log::info!("自动切换 {} 配置: {}", tool.as_str(), best.name);
+                switched.push(AutoSwitch {
+                    tool,
+                    from: current_id.cloned(),
+                    to: best.profile_id.clone(),
+                    name: best.name.clone(),
+                });
+            }
+            Err(e) => log::warn!("自动切换 {} 配置失败: {}", tool.as_str(), e),
+        }
+    }
+
+    switched
+}
+
+/// 执行一轮健康检查，并按需自动切换
+pub async fn run_cycle() -> Result<(Vec<ProfileHealth>, Vec<AutoSwitch>), String> {
+    let store = profiles::list_profiles()?;
+    let tools = [Tool::Claude, Tool::Codex, Tool::Gemini];
+    let report = check_tools(&tools).await?;
+    let switched = auto_switch(&store, &report);
+    Ok((report, switched))
+}
+
+/// 启动后台健康检查：仅在有工具开启自动模式时探测
+#[cfg(feature = "gui")]
+pub fn start(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let store = profiles::list_profiles().unwrap_or_default();
+            let interval = store.auto_switch.interval_secs.max(MIN_INTERVAL_SECS);
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+
+            let store = match profiles::list_profiles() {
+                Ok(store) => store,
+                Err(e) => {
+                    log::warn!("健康检查读取配置库失败: {}", e);
+                    continue;
+                }
+            };
+            if store.auto_switch.tools.is_empty() {
+                continue;
+            }
+
+            match check_tools(&store.auto_switch.tools).await {
+                Ok(report) => {
+                    for switch in auto_switch(&store, &report) {
+                        let _ = app.emit(AUTO_SWITCH_EVENT, &switch);
+                    }
+                }
+                Err(e) => log::warn!("健康检查失败: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health(id: &str, tool: Tool, samples: usize, error_rate: f64, avg_latency_ms: Option<u64>) -> ProfileHealth {
+        let last_outcome = match samples {
+            0 => None,
+            _ if error_rate < 1.0 => Some(ProbeOutcome::Ok),
+            _ => Some(ProbeOutcome::Timeout),
+        };
+        ProfileHealth {
+            profile_id: id.to_string(),
+            tool,
+            name: id.to_string(),
+            samples,
+            error_rate,
+            avg_latency_ms,
+            last_outcome,
+            last_message: None,
+            last_checked_at: None,
+        }
+    }
+
+    #[test]
+    fn healthier_prefers_lower_error_rate_then_latency() {
+        let reliable = health("a", Tool::Claude, 10, 0.0, Some(900));
+        let flaky = health("b", Tool::Claude, 10, 0.3, Some(100));
+        let fast = health("c", Tool::Claude, 10, 0.0, Some(200));
+        let unknown = health("d", Tool::Claude, 0, 0.0, None);
+
+        assert_eq!(healthier(&reliable, &flaky), std::cmp::Ordering::Less);
+        assert_eq!(healthier(&fast, &reliable), std::cmp::Ordering::Less);
+        assert_eq!(healthier(&fast, &unknown), std::cmp::Ordering::Less);
+        assert_eq!(healthier(&fast, &fast.clone()), std::cmp::Ordering::Equal);
+    }
+
+    #[test]
+    fn pick_healthiest_skips_other_tools_and_profiles_without_success() {
+        let report = vec![
+            health("never-probed", Tool::Claude, 0, 0.0, None),
+            health("failing", Tool::Claude, 5, 1.0, None),
+            health("slow", Tool::Claude, 5, 0.0, Some(800)),
+            health("fast", Tool::Claude, 5, 0.0, Some(300)),
+            health("codex", Tool::Codex, 5, 0.0, Some(10)),
+        ];
+        assert_eq!(pick_healthiest(&report, Tool::Claude).unwrap().profile_id, "fast");
+
+        // 全部失败时没有可切换的配置
+        let all_failing = vec![health("a", Tool::Claude, 5, 1.0, None), health("b", Tool::Claude, 3, 1.0, None)];
+        assert!(pick_healthiest(&all_failing, Tool::Claude).is_none());
+
+        // 完全相同时取第一个
+        let tie = vec![health("a", Tool::Gemini, 5, 0.0, Some(300)), health("b", Tool::Gemini, 5, 0.0, Some(300))];
+        assert_eq!(pick_healthiest(&tie, Tool::Gemini).unwrap().profile_id, "a");
+    }
+
+    #[test]
+    fn should_switch_requires_evidence_against_current() {
+        let best = health("best", Tool::Claude, 5, 0.0, Some(300));
+
+        // 没有探测数据：保留用户选择的配置
+        assert!(!should_switch(Some(&health("current", Tool::Claude, 0, 0.0, None)), &best));
+        // 当前配置就是最优
+        assert!(!should_switch(Some(&best), &best));
+        // 当前健康且延迟差距在阈值内
+        assert!(!should_switch(Some(&health("current", Tool::Claude, 5, 0.0, Some(400))), &best));
+        // 同样健康、同样延迟
+        assert!(!should_switch(Some(&health("current", Tool::Claude, 5, 0.0, Some(300))), &best));
+
+        assert!(should_switch(None, &best));
+        assert!(should_switch(Some(&health("current", Tool::Claude, 5, 1.0, None)), &best));
+        assert!(should_switch(Some(&health("current", Tool::Claude, 5, 0.2, Some(300))), &best));
+        assert!(should_switch(Some(&health("current", Tool::Claude, 5, 0.0, Some(1000))), &best));
+    }
+}
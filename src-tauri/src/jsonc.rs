@@ -0,0 +1,584 @@
+//! JSONC（带注释和尾随逗号的 JSON，如 VSCode settings.json）的读取与原地编辑：
+//! 只改动目标键所在的文本，注释、键顺序、缩进和换行保持不变
+
+use serde_json::{Map, Value};
+
+/// 解析得到的语法树，只记录各节点在原文中的位置
+enum Node {
+    Object(Object),
+    Array { items: Vec<Node> },
+    Scalar { start: usize, end: usize },
+}
+
+struct Object {
+    /// `{` 的位置
+    open: usize,
+    /// `}` 的位置
+    close: usize,
+    members: Vec<Member>,
+}
+
+struct Member {
+    key: String,
+    /// 键的起始位置（引号处）
+    start: usize,
+    value_start: usize,
+    value_end: usize,
+    value: Node,
+    /// 成员后逗号的位置
+    comma: Option<usize>,
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(src: &'a str) -> Self {
+        Self {
+            src,
+            bytes: src.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    /// 带行列号的错误信息
+    fn error(&self, message: &str) -> String {
+        let mut pos = self.pos.min(self.src.len());
+        while !self.src.is_char_boundary(pos) {
+            pos -= 1;
+        }
+        let before = &self.src[..pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+        format!("第 {} 行第 {} 列: {}", line, column, message)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    /// 跳过空白和注释
+    fn skip_trivia(&mut self) -> Result<(), String> {
+        loop {
+            match self.peek() {
+                Some(b' ' | b'\t' | b'\r' | b'\n') => self.pos += 1,
+                Some(b'/') if self.bytes.get(self.pos + 1) == Some(&b'/') => {
+                    while self.peek().is_some_and(|b| b != b'\n') {
+                        self.pos += 1;
+                    }
+                }
+                Some(b'/') if self.bytes.get(self.pos + 1) == Some(&b'*') => {
+                    match self.src[self.pos + 2..].find("*/") {
+                        Some(offset) => self.pos += offset + 4,
+                        None => return Err(self.error("注释没有结束")),
+                    }
+                }
+                // UTF-8 BOM
+                Some(0xEF) if self.pos == 0 && self.src.starts_with('\u{feff}') => self.pos += 3,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Node, String> {
+        match self.peek() {
+            Some(b'{') => self.parse_object().map(Node::Object),
+            Some(b'[') => self.parse_array(),
+            Some(b'"') => {
+                let (start, end) = self.parse_string()?;
+                serde_json::from_str::<String>(&self.src[start..end])
+                    .map_err(|e| self.error(&format!("字符串无效: {}", e)))?;
+                Ok(Node::Scalar { start, end })
+            }
+            Some(_) => self.parse_literal(),
+            None => Err(self.error("内容意外结束")),
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Object, String> {
+        let open = self.pos;
+        self.pos += 1;
+        let mut members: Vec<Member> = Vec::new();
+        loop {
+            self.skip_trivia()?;
+            match self.peek() {
+                Some(b'}') => break,
+                Some(b'"') => {}
+                _ => return Err(self.error("应为键或 }")),
+            }
+            if members.last().is_some_and(|m| m.comma.is_none()) {
+                return Err(self.error("缺少逗号"));
+            }
+
+            let (start, end) = self.parse_string()?;
+            let key: String = serde_json::from_str(&self.src[start..end])
+                .map_err(|e| self.error(&format!("键无效: {}", e)))?;
+            self.skip_trivia()?;
+            if self.peek() != Some(b':') {
+                return Err(self.error("缺少冒号"));
+            }
+            self.pos += 1;
+            self.skip_trivia()?;
+            let value_start = self.pos;
+            let value = self.parse_value()?;
+            let value_end = self.pos;
+            self.skip_trivia()?;
+            let comma = if self.peek() == Some(b',') {
+                self.pos += 1;
+                Some(self.pos - 1)
+            } else {
+                None
+            };
+            members.push(Member {
+                key,
+                start,
+                value_start,
+                value_end,
+                value,
+                comma,
+            });
+        }
+        let close = self.pos;
+        self.pos += 1;
+        Ok(Object { open, close, members })
+    }
+
+    fn parse_array(&mut self) -> Result<Node, String> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        let mut expect_comma = false;
+        loop {
+            self.skip_trivia()?;
+            match self.peek() {
+                Some(b']') => break,
+                Some(b',') if expect_comma => {
+                    self.pos += 1;
+                    expect_comma = false;
+                }
+                Some(_) if !expect_comma => {
+                    items.push(self.parse_value()?);
+                    expect_comma = true;
+                }
+                Some(_) => return Err(self.error("缺少逗号")),
+                None => return Err(self.error("数组没有结束")),
+            }
+        }
+        self.pos += 1;
+        Ok(Node::Array { items })
+    }
+
+    /// 返回字符串（含引号）的起止位置
+    fn parse_string(&mut self) -> Result<(usize, usize), String> {
+        let start = self.pos;
+        self.pos += 1;
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok((start, self.pos));
+                }
+                Some(b'\\') => self.pos += 2,
+                Some(b'\n') | None => return Err(self.error("字符串没有结束")),
+                Some(_) => self.pos += 1,
+            }
+        }
+    }
+
+    /// 数字、true、false、null
+    fn parse_literal(&mut self) -> Result<Node, String> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'+' | b'.'))
+        {
+            self.pos += 1;
+        }
+        let end = self.pos;
+        if start == end || serde_json::from_str::<Value>(&self.src[start..end]).is_err() {
+            self.pos = start;
+            return Err(self.error("无效的值"));
+        }
+        Ok(Node::Scalar { start, end })
+    }
+}
+
+/// 解析整个文档，顶层必须是对象；空文档视为 {}
+fn parse_root(src: &str) -> Result<Option<Object>, String> {
+    let mut parser = Parser::new(src);
+    parser.skip_trivia()?;
+    if parser.peek().is_none() {
+        return Ok(None);
+    }
+    if parser.peek() != Some(b'{') {
+        return Err(parser.error("顶层应为对象"));
+    }
+    let root = parser.parse_object()?;
+    parser.skip_trivia()?;
+    if parser.peek().is_some() {
+        return Err(parser.error("对象结束后还有多余内容"));
+    }
+    Ok(Some(root))
+}
+
+fn to_value(src: &str, node: &Node) -> Value {
+    match node {
+        Node::Object(object) => Value::Object(object_to_map(src, object)),
+        Node::Array { items } => Value::Array(items.iter().map(|n| to_value(src, n)).collect()),
+        // 解析时已校验过
+        Node::Scalar { start, end } => serde_json::from_str(&src[*start..*end]).unwrap_or(Value::Null),
+    }
+}
+
+fn object_to_map(src: &str, object: &Object) -> Map<String, Value> {
+    object
+        .members
+        .iter()
+        .map(|m| (m.key.clone(), to_value(src, &m.value)))
+        .collect()
+}
+
+/// 解析 JSONC 文档，顶层必须是对象；空文档视为空对象
+pub fn parse(content: &str) -> Result<Map<String, Value>, String> {
+    Ok(parse_root(content)?
+        .map(|root| object_to_map(content, &root))
+        .unwrap_or_default())
+}
+
+/// 文档使用的换行符
+fn line_ending(src: &str) -> &'static str {
+    if src.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    }
+}
+
+/// pos 所在行的行首位置
+fn line_start(src: &str, pos: usize) -> usize {
+    src[..pos].rfind('\n').map_or(0, |i| i + 1)
+}
+
+/// pos 所在行开头的缩进
+fn indent_at(src: &str, pos: usize) -> &str {
+    let start = line_start(src, pos);
+    let line = &src[start..];
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+/// 文档的缩进单位：取第一个有缩进的成员，默认 4 个空格（VSCode 默认）
+fn indent_unit(src: &str, root: Option<&Object>) -> String {
+    root.and_then(|r| r.members.first())
+        .map(|m| indent_at(src, m.start))
+        .filter(|i| !i.is_empty())
+        .unwrap_or("    ")
+        .to_string()
+}
+
+/// 按文档的缩进风格序列化值，续行以 base 缩进开头
+fn render(value: &Value, unit: &str, base: &str, eol: &str) -> String {
+    let mut buf = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(unit.as_bytes());
+    let mut serializer = serde_json::Serializer::with_formatter(&mut buf, formatter);
+    serde::Serialize::serialize(value, &mut serializer).expect("序列化 JSON 值不会失败");
+    let text = String::from_utf8(buf).expect("serde_json 输出为 UTF-8");
+    text.split('\n').collect::<Vec<_>>().join(&format!("{}{}", eol, base))
+}
+
+/// 成员所在行的缩进；成员与 { 在同一行时按对象缩进加一级
+fn member_indent(src: &str, object: &Object, unit: &str) -> String {
+    match object.members.first() {
+        Some(m) if line_start(src, m.start) != line_start(src, object.open) => indent_at(src, m.start).to_string(),
+        _ => format!("{}{}", indent_at(src, object.open), unit),
+    }
+}
+
+/// 在对象末尾追加成员
+fn insert_member(src: &str, object: &Object, key: &str, value: &Value, unit: &str) -> String {
+    let eol = line_ending(src);
+    let indent = member_indent(src, object, unit);
+    let key = Value::String(key.to_string()).to_string();
+    let member = format!("{}: {}", key, render(value, unit, &indent, eol));
+
+    let Some(last) = object.members.last() else {
+        // 空对象：成员独占一行，} 放回对象原来的缩进
+        let closing = indent_at(src, object.open);
+        let inner = &src[object.open + 1..object.close];
+        let (head, tail) = if inner.trim().is_empty() {
+            (&src[..object.open + 1], &src[object.close..])
+        } else {
+            // 对象中只有注释时保留注释，成员与最后一行注释对齐
+            (&src[..object.close], &src[object.close..])
+        };
+        let head = head.trim_end_matches([' ', '\t', '\r', '\n']);
+        let comment_line = line_start(src, head.len());
+        let indent = if comment_line > line_start(src, object.open) {
+            indent_at(src, comment_line).to_string()
+        } else {
+            indent
+        };
+        let member = format!("{}: {}", key, render(value, unit, &indent, eol));
+        return format!("{}{}{}{}{}{}{}", head, eol, indent, member, eol, closing, tail);
+    };
+
+    let anchor = last.comma.map_or(last.value_end, |c| c + 1);
+    let comma = if last.comma.is_some() { "" } else { "," };
+    // 插在最后一个成员之后、同一行的注释之后，逗号紧跟在值后面
+    let insert_at = line_trivia_end(src, anchor);
+
+    // 单行对象保持单行
+    let (separator, member) = if line_start(src, object.open) == line_start(src, object.close) {
+        (" ".to_string(), format!("{}: {}", key, value))
+    } else {
+        (format!("{}{}", eol, indent), member)
+    };
+    format!(
+        "{}{}{}{}{}{}",
+        &src[..last.value_end],
+        comma,
+        &src[last.value_end..insert_at],
+        separator,
+        member,
+        &src[insert_at..]
+    )
+}
+
+/// pos 之后同一行内的注释结束的位置；后面只剩空白时取行尾（不含换行符）
+fn line_trivia_end(src: &str, mut pos: usize) -> usize {
+    let bytes = src.as_bytes();
+    let mut end = pos;
+    loop {
+        match bytes.get(pos) {
+            Some(b' ' | b'\t') => pos += 1,
+            Some(b'\r' | b'\n') | None => return pos,
+            Some(b'/') if bytes.get(pos + 1) == Some(&b'/') => {
+                return src[pos..].find(['\r', '\n']).map_or(src.len(), |offset| pos + offset);
+            }
+            Some(b'/') if bytes.get(pos + 1) == Some(&b'*') => match src[pos + 2..].find("*/") {
+                Some(offset) => {
+                    pos += offset + 4;
+                    end = pos;
+                }
+                None => return end,
+            },
+            Some(_) => return end,
+        }
+    }
+}
+
+/// 设置 path 指向的值，中间缺失或不是对象的层级会被创建为对象；其余内容保持不变
+pub fn set(content: &str, path: &[&str], value: &Value) -> Result<String, String> {
+    let Some((first, rest)) = path.split_first() else {
+        return Err("键路径不能为空".to_string());
+    };
+    let root = parse_root(content)?;
+    let unit = indent_unit(content, root.as_ref());
+
+    let Some(root) = root else {
+        // 空文档或只有注释：保留原有注释，新对象接在后面
+        let eol = line_ending(content);
+        let mut nested = value.clone();
+        for key in rest.iter().rev() {
+            nested = Value::Object(Map::from_iter([(key.to_string(), nested)]));
+        }
+        let object = Value::Object(Map::from_iter([(first.to_string(), nested)]));
+        let prefix = content.trim_end();
+        let separator = if prefix.trim_start_matches('\u{feff}').is_empty() { "" } else { eol };
+        return Ok(format!("{}{}{}{}", prefix, separator, render(&object, &unit, "", eol), eol));
+    };
+
+    let mut object = &root;
+    let mut path = path;
+    loop {
+        let (key, rest) = path.split_first().expect("路径非空");
+        let member = object.members.iter().rev().find(|m| m.key == *key);
+        match (member, rest.is_empty()) {
+            (Some(member), false) if matches!(member.value, Node::Object(_)) => {
+                let Node::Object(child) = &member.value else { unreachable!() };
+                object = child;
+                path = rest;
+            }
+            (Some(member), _) => {
+                let mut nested = value.clone();
+                for key in rest.iter().rev() {
+                    nested = Value::Object(Map::from_iter([(key.to_string(), nested)]));
+                }
+                let indent = indent_at(content, member.start);
+                let rendered = render(&nested, &unit, indent, line_ending(content));
+                return Ok(format!(
+                    "{}{}{}",
+                    &content[..member.value_start],
+                    rendered,
+                    &content[member.value_end..]
+                ));
+            }
+            (None, _) => {
+                let mut nested = value.clone();
+                for key in rest.iter().rev() {
+                    nested = Value::Object(Map::from_iter([(key.to_string(), nested)]));
+                }
+                return Ok(insert_member(content, object, key, &nested, &unit));
+            }
+        }
+    }
+}
+
+/// 删除 path 指向的成员（同名键全部删除），成员独占一行时连同整行删除；不存在时返回原内容
+pub fn remove(content: &str, path: &[&str]) -> Result<String, String> {
+    let mut result = content.to_string();
+    while let Some(updated) = remove_last(&result, path)? {
+        result = updated;
+    }
+    Ok(result)
+}
+
+/// 删除最后一个匹配的成员，没有匹配时返回 None
+fn remove_last(content: &str, path: &[&str]) -> Result<Option<String>, String> {
+    let Some((last_key, parents)) = path.split_last() else {
+        return Err("键路径不能为空".to_string());
+    };
+    let Some(root) = parse_root(content)? else {
+        return Ok(None);
+    };
+
+    let mut object = &root;
+    for key in parents {
+        match object.members.iter().rev().find(|m| m.key == *key).map(|m| &m.value) {
+            Some(Node::Object(child)) => object = child,
+            _ => return Ok(None),
+        }
+    }
+    let Some(index) = object.members.iter().rposition(|m| m.key == *last_key) else {
+        return Ok(None);
+    };
+    let member = &object.members[index];
+
+    let mut result = content.to_string();
+    let mut start = member.start;
+    let mut end = member.comma.map_or(member.value_end, |c| c + 1);
+
+    // 最后一个成员没有逗号时，删除前一个成员的逗号
+    if member.comma.is_none() && index > 0 {
+        if let Some(comma) = object.members[index - 1].comma {
+            result.replace_range(comma..comma + 1, "");
+            start -= 1;
+            end -= 1;
+        }
+    }
+
+    // 成员独占一行时删除整行（包括行尾注释），否则只删除成员本身和紧随的空格
+    let line = line_start(&result, start);
+    let line_end = result[end..].find('\n').map_or(result.len(), |i| end + i);
+    let trailing = result[end..line_end].trim();
+    if result[line..start].trim().is_empty() && (trailing.is_empty() || trailing.starts_with("//")) {
+        start = line;
+        end = (line_end + 1).min(result.len());
+    } else if member.comma.is_none() && index > 0 {
+        // 删除的是同一行的最后一个成员，连同它前面的空格
+        start = result[..start].trim_end_matches([' ', '\t']).len();
+    } else {
+        end += result[end..].len() - result[end..].trim_start_matches([' ', '\t']).len();
+    }
+    result.replace_range(start..end, "");
+    Ok(Some(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SETTINGS: &str = r#"{
+  // 编辑器
+  "editor.fontSize": 14, // 字号
+  /* 主题 */
+  "workbench.colorTheme": "Default Dark+",
+  "chatgpt.config": {
+    "model": "gpt-5",
+  },
+}
+"#;
+
+    #[test]
+    fn parse_accepts_comments_and_trailing_commas() {
+        let map = parse(SETTINGS).unwrap();
+        assert_eq!(map["editor.fontSize"], json!(14));
+        assert_eq!(map["chatgpt.config"], json!({"model": "gpt-5"}));
+        assert!(parse("  // 只有注释\n").unwrap().is_empty());
+
+        let err = parse("{\n  \"a\": 1\n  \"b\": 2\n}").unwrap_err();
+        assert_eq!(err, "第 3 行第 3 列: 缺少逗号");
+        assert!(parse("[1, 2]").is_err());
+        assert!(parse("{\"a\": 1} x").is_err());
+    }
+
+    #[test]
+    fn set_replaces_value_in_place() {
+        let edited = set(SETTINGS, &["editor.fontSize"], &json!(16)).unwrap();
+        assert_eq!(edited, SETTINGS.replace("\"editor.fontSize\": 14,", "\"editor.fontSize\": 16,"));
+
+        let edited = set(SETTINGS, &["chatgpt.config", "model"], &json!("gpt-5-codex")).unwrap();
+        assert_eq!(edited, SETTINGS.replace("\"model\": \"gpt-5\"", "\"model\": \"gpt-5-codex\""));
+    }
+
+    #[test]
+    fn set_inserts_member_with_document_style() {
+        let edited = set(SETTINGS, &["chatgpt.config", "preferred_auth_method"], &json!("apikey")).unwrap();
+        assert!(edited.contains("    \"model\": \"gpt-5\",\n    \"preferred_auth_method\": \"apikey\"\n  },\n"));
+
+        let edited = set(SETTINGS, &["chatgpt.apiBase"], &json!("https://relay.example/v1")).unwrap();
+        assert!(edited.ends_with("  },\n  \"chatgpt.apiBase\": \"https://relay.example/v1\"\n}\n"));
+        assert!(edited.contains("// 字号") && edited.contains("/* 主题 */"));
+
+        let crlf = "{\r\n\t\"a\": 1\r\n}\r\n";
+        assert_eq!(
+            set(crlf, &["b", "c"], &json!(true)).unwrap(),
+            "{\r\n\t\"a\": 1,\r\n\t\"b\": {\r\n\t\t\"c\": true\r\n\t}\r\n}\r\n"
+        );
+
+        assert_eq!(set("{\"a\": 1}", &["b"], &json!(2)).unwrap(), "{\"a\": 1, \"b\": 2}");
+        assert_eq!(set("{}", &["b"], &json!(2)).unwrap(), "{\n    \"b\": 2\n}");
+    }
+
+    #[test]
+    fn set_keeps_trailing_comments_on_their_member() {
+        let content = "{\n  \"a\": 1 /* x */\n}\n";
+        assert_eq!(set(content, &["k"], &json!(2)).unwrap(), "{\n  \"a\": 1, /* x */\n  \"k\": 2\n}\n");
+
+        let content = "{\n  \"a\": 1 // x\n}\n";
+        assert_eq!(set(content, &["k"], &json!(2)).unwrap(), "{\n  \"a\": 1, // x\n  \"k\": 2\n}\n");
+
+        let content = "{\"a\": 1 /* x */}";
+        assert_eq!(set(content, &["k"], &json!(2)).unwrap(), "{\"a\": 1, /* x */ \"k\": 2}");
+    }
+
+    #[test]
+    fn set_keeps_comments_of_comment_only_document() {
+        let content = "// 用户设置\n/* 暂无 */\n";
+        assert_eq!(
+            set(content, &["a"], &json!(1)).unwrap(),
+            "// 用户设置\n/* 暂无 */\n{\n    \"a\": 1\n}\n"
+        );
+        assert_eq!(set("", &["a", "b"], &json!(1)).unwrap(), "{\n    \"a\": {\n        \"b\": 1\n    }\n}\n");
+
+        let object_with_comment = "{\n  // 暂无\n}\n";
+        assert_eq!(set(object_with_comment, &["a"], &json!(1)).unwrap(), "{\n  // 暂无\n  \"a\": 1\n}\n");
+    }
+
+    #[test]
+    fn remove_deletes_lines_and_fixes_commas() {
+        let edited = remove(SETTINGS, &["editor.fontSize"]).unwrap();
+        assert_eq!(edited, SETTINGS.replace("  \"editor.fontSize\": 14, // 字号\n", ""));
+
+        let content = "{\n  \"a\": 1,\n  \"b\": 2\n}";
+        assert_eq!(remove(content, &["b"]).unwrap(), "{\n  \"a\": 1\n}");
+
+        let duplicates = "{\"a\": 1, \"b\": 2, \"a\": 3}";
+        assert_eq!(remove(duplicates, &["a"]).unwrap(), "{\"b\": 2}");
+
+        assert_eq!(remove(SETTINGS, &["chatgpt.config", "missing"]).unwrap(), SETTINGS);
+        assert_eq!(remove(SETTINGS, &["missing", "model"]).unwrap(), SETTINGS);
+    }
+}
@@ -1,11 +1,31 @@
+
 // 88code-desktop modules
+mod backup;
+pub mod cli;
 mod claude_config;
 mod codex_config;
+#[cfg(feature = "gui")]
 mod commands;
 mod config;
 mod env_manager;
+mod gemini_config;
+#[cfg(feature = "gui")]
+mod health;
+mod jsonc;
+mod probe;
+mod profiles;
+mod projects;
+#[cfg(feature = "gui")]
+mod proxy;
+mod switcher;
+#[cfg(test)]
+mod test_support;
+mod transaction;
+mod uninstall;
+mod vault;
 mod vscode;
 
+#[cfg(feature = "gui")]
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
@@ -17,17 +37,68 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
+        .setup(|app| {
+            health::start(app.handle().clone());
+            proxy::start();
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             commands::configure_claude_code,
             commands::configure_codex,
             commands::get_config_paths,
             commands::get_current_claude_config,
             commands::get_current_codex_auth,
+            commands::get_codex_providers,
+            commands::configure_codex_provider,
+            commands::get_codex_profiles,
+            commands::save_codex_profile,
+            commands::rename_codex_profile,
+            commands::delete_codex_profile,
+            commands::set_default_codex_profile,
+            commands::cleanup_env_vars,
+            commands::get_shell_integrations,
+            commands::install_shell_integration,
+            commands::uninstall_shell_integration,
+            commands::unconfigure_tool,
+            commands::reset_all_config,
+            commands::configure_gemini,
+            commands::get_current_gemini_config,
             commands::configure_vscode_claude,
             commands::configure_vscode_codex,
             commands::get_vscode_paths,
+            commands::list_editors,
+            commands::configure_editors,
+            commands::list_vscode_profiles,
+            commands::configure_vscode_profiles,
             commands::configure_claude_with_content,
             commands::configure_codex_with_content,
+            commands::list_profiles,
+            commands::create_profile,
+            commands::update_profile,
+            commands::delete_profile,
+            commands::import_profiles,
+            commands::activate_profile,
+            commands::vault_status,
+            commands::vault_init,
+            commands::vault_unlock,
+            commands::vault_lock,
+            commands::vault_set_auto_lock,
+            commands::vault_store_secret,
+            commands::vault_delete_secret,
+            commands::protect_profile_key,
+            commands::list_backups,
+            commands::diff_backup,
+            commands::restore_backup,
+            commands::probe_endpoint,
+            commands::probe_profile,
+            commands::get_health_report,
+            commands::run_health_check,
+            commands::set_auto_switch,
+            commands::set_health_interval,
+            commands::get_proxy_status,
+            commands::set_proxy_mode,
+            commands::activate_profile_for_project,
+            commands::list_project_overrides,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,344 @@
+use crate::config::{DEFAULT_CLAUDE_BASE_URL, DEFAULT_CODEX_BASE_URL, DEFAULT_GEMINI_BASE_URL};
+use crate::profiles::Tool;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+/// 默认探测超时（毫秒）
+pub const DEFAULT_PROBE_TIMEOUT_MS: u64 = 10_000;
+
+/// Anthropic API 版本头
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// models 接口不可用且配置未指定模型时，用于 messages 探测的模型
+const CLAUDE_PROBE_MODEL: &str = "claude-3-5-haiku-20241022";
+
+/// 探测结果分类
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProbeOutcome {
+    /// 连接正常且密钥有效
+    Ok,
+    /// 服务可达，但密钥无效或无权限（401/403）
+    AuthFailed,
+    /// 服务可达，但返回了其他错误状态码
+    HttpError,
+    /// 请求超时
+    Timeout,
+    /// DNS、连接、TLS 等网络错误
+    NetworkError,
+}
+
+/// 一次探测的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct ProbeResult {
+    pub outcome: ProbeOutcome,
+    /// 实际请求的地址
+    pub url: String,
+    /// HTTP 状态码（网络错误时为空）
+    pub status: Option<u16>,
+    pub latency_ms: u64,
+    /// 接口返回的模型列表（如果有）
+    pub models: Vec<String>,
+    pub message: String,
+}
+
+/// 去掉 Base URL 两端空白，为空时使用工具的默认值
+pub(crate) fn base_url_or_default(tool: Tool, base_url: &str) -> String {
+    let base_url = base_url.trim();
+    if !base_url.is_empty() {
+        return base_url.to_string();
+    }
+    match tool {
+        Tool::Claude => DEFAULT_CLAUDE_BASE_URL,
+        Tool::Codex => DEFAULT_CODEX_BASE_URL,
+        Tool::Gemini => DEFAULT_GEMINI_BASE_URL,
+    }
+    .to_string()
+}
+
+/// 根据工具补全默认 Base URL 并去掉结尾的 /
+pub(crate) fn normalize_base_url(tool: Tool, base_url: &str) -> String {
+    base_url_or_default(tool, base_url).trim_end_matches('/').to_string()
+}
+
+/// 从响应中提取模型 ID（兼容 Anthropic/OpenAI 的 data[].id 与 Gemini 的 models[].name）
+fn extract_models(body: &Value) -> Vec<String> {
+    if let Some(data) = body.get("data").and_then(|v| v.as_array()) {
+        return data
+            .iter()
+            .filter_map(|m| m.get("id").and_then(|v| v.as_str()))
+            .map(|s| s.to_string())
+            .collect();
+    }
+    if let Some(models) = body.get("models").and_then(|v| v.as_array()) {
+        return models
+            .iter()
+            .filter_map(|m| m.get("name").and_then(|v| v.as_str()))
+            .map(|s| s.trim_start_matches("models/").to_string())
+            .collect();
+    }
+    Vec::new()
+}
+
+/// 为请求添加对应服务的认证头
+pub(crate) fn authorize(tool: Tool, request: reqwest::RequestBuilder, api_key: &str) -> reqwest::RequestBuilder {
+    match tool {
+        Tool::Claude => request
+            .header("x-api-key", api_key)
+            .bearer_auth(api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION),
+        Tool::Codex => request.bearer_auth(api_key),
+        Tool::Gemini => request.header("x-goog-api-key", api_key),
+    }
+}
+
+fn models_url(tool: Tool, base_url: &str) -> String {
+    match tool {
+        Tool::Claude => format!("{}/v1/models", base_url),
+        Tool::Codex => format!("{}/models", base_url),
+        Tool::Gemini => format!("{}/v1beta/models", base_url),
+    }
+}
+
+/// 发送请求并将结果归类
+async fn send(url: String, request: reqwest::RequestBuilder, started: Instant) -> ProbeResult {
+    match request.send().await {
+        Ok(response) => {
+            let status = response.status();
+            let body: Value = response.json().await.unwrap_or(Value::Null);
+            let latency_ms = started.elapsed().as_millis() as u64;
+
+            let (outcome, message) = if status.is_success() {
+                (ProbeOutcome::Ok, "连接成功".to_string())
+            } else if status.as_u16() == 401 || status.as_u16() == 403 {
+                (ProbeOutcome::AuthFailed, format!("认证失败 (HTTP {})", status.as_u16()))
+            } else {
+                let detail = body
+                    .pointer("/error/message")
+                    .and_then(|v| v.as_str())
+                    .map(|m| format!(": {}", m))
+                    .unwrap_or_default();
+                (ProbeOutcome::HttpError, format!("HTTP {}{}", status.as_u16(), detail))
+            };
+
+            ProbeResult {
+                outcome,
+                url,
+                status: Some(status.as_u16()),
+                latency_ms,
+                models: if status.is_success() { extract_models(&body) } else { Vec::new() },
+                message,
+            }
+        }
+        Err(e) => {
+            let outcome = if e.is_timeout() {
+                ProbeOutcome::Timeout
+            } else {
+                ProbeOutcome::NetworkError
+            };
+            ProbeResult {
+                outcome,
+                url,
+                status: None,
+                latency_ms: started.elapsed().as_millis() as u64,
+                models: Vec::new(),
+                message: format!("网络错误: {}", e),
+            }
+        }
+    }
+}
+
+/// 探测服务连通性和密钥有效性：
+/// 先请求 models 接口；Claude 中转站不提供 models 接口（404/405）时改用最小的 messages 请求，
+/// 模型优先使用配置中的模型（`model`），未指定时使用内置的探测模型
+pub async fn probe(
+    tool: Tool,
+    base_url: &str,
+    api_key: &str,
+    model: Option<&str>,
+    timeout_ms: Option<u64>,
+) -> ProbeResult {
+    let base_url = normalize_base_url(tool, base_url);
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(DEFAULT_PROBE_TIMEOUT_MS));
+
+    let client = match reqwest::Client::builder().timeout(timeout).build() {
+        Ok(client) => client,
+        Err(e) => {
+            return ProbeResult {
+                outcome: ProbeOutcome::NetworkError,
+                url: base_url,
+                status: None,
+                latency_ms: 0,
+                models: Vec::new(),
+                message: format!("创建 HTTP 客户端失败: {}", e),
+            }
+        }
+    };
+
+    let url = models_url(tool, &base_url);
+    let started = Instant::now();
+    let request = authorize(tool, client.get(&url), api_key);
+    let result = send(url, request, started).await;
+
+    if tool == Tool::Claude && matches!(result.status, Some(404) | Some(405)) {
+        let url = format!("{}/v1/messages", base_url);
+        let body = json!({
+            "model": model.map(str::trim).filter(|m| !m.is_empty()).unwrap_or(CLAUDE_PROBE_MODEL),
+            "max_tokens": 1,
+            "messages": [{ "role": "user", "content": "ping" }],
+        });
+        let started = Instant::now();
+        let request = authorize(tool, client.post(&url), api_key).json(&body);
+        return send(url, request, started).await;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use http_body_util::{BodyExt, Full};
+    use hyper::body::Incoming;
+    use hyper::server::conn::http1;
+    use hyper::service::service_fn;
+    use hyper::{HeaderMap, Request, Response};
+    use hyper_util::rt::TokioIo;
+    use std::convert::Infallible;
+    use tokio::net::TcpListener;
+
+    type Route = fn(&str, &HeaderMap, &Value) -> (u16, Value);
+
+    /// 启动本地模拟中转站，按请求路径、请求头和 JSON 请求体返回 (状态码, JSON)，返回其 Base URL
+    async fn mock_server(route: Route, delay: Duration) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else { return };
+                tokio::spawn(async move {
+                    let service = service_fn(move |req: Request<Incoming>| async move {
+                        tokio::time::sleep(delay).await;
+                        let (parts, body) = req.into_parts();
+                        let body = body.collect().await.map(|b| b.to_bytes()).unwrap_or_default();
+                        let body = serde_json::from_slice(&body).unwrap_or(Value::Null);
+                        let (status, body) = route(parts.uri.path(), &parts.headers, &body);
+                        let response = Response::builder()
+                            .status(status)
+                            .header("content-type", "application/json")
+                            .body(Full::new(Bytes::from(body.to_string())))
+                            .unwrap();
+                        Ok::<_, Infallible>(response)
+                    });
+                    let _ = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await;
+                });
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    fn header<'a>(headers: &'a HeaderMap, name: &str) -> &'a str {
+        headers.get(name).and_then(|v| v.to_str().ok()).unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn classifies_success_and_extracts_models() {
+        let base_url = mock_server(
+            |path, headers, _| match (path, header(headers, "authorization")) {
+                ("/v1/models", "Bearer sk-good") => (200, json!({"data": [{"id": "gpt-5"}, {"id": "gpt-5-mini"}]})),
+                _ => (401, json!({})),
+            },
+            Duration::ZERO,
+        )
+        .await;
+
+        let result = probe(Tool::Codex, &format!("{}/v1/", base_url), "sk-good", None, None).await;
+        assert_eq!(result.outcome, ProbeOutcome::Ok);
+        assert_eq!(result.status, Some(200));
+        assert_eq!(result.url, format!("{}/v1/models", base_url));
+        assert_eq!(result.models, ["gpt-5", "gpt-5-mini"]);
+    }
+
+    #[tokio::test]
+    async fn classifies_auth_and_http_errors() {
+        let base_url = mock_server(
+            |path, headers, _| match (path, header(headers, "x-goog-api-key")) {
+                ("/v1beta/models", "bad") => (403, json!({"error": {"message": "forbidden"}})),
+                ("/v1beta/models", _) => (500, json!({"error": {"message": "upstream down"}})),
+                _ => (404, json!({})),
+            },
+            Duration::ZERO,
+        )
+        .await;
+
+        let result = probe(Tool::Gemini, &base_url, "bad", None, None).await;
+        assert_eq!(result.outcome, ProbeOutcome::AuthFailed);
+        assert_eq!(result.status, Some(403));
+
+        let result = probe(Tool::Gemini, &base_url, "other", None, None).await;
+        assert_eq!(result.outcome, ProbeOutcome::HttpError);
+        assert_eq!(result.message, "HTTP 500: upstream down");
+        assert!(result.models.is_empty());
+    }
+
+    #[tokio::test]
+    async fn claude_falls_back_to_messages_without_models_endpoint() {
+        let base_url = mock_server(
+            |path, headers, _| match path {
+                "/v1/messages" if header(headers, "x-api-key") == "sk-ant" => (200, json!({"id": "msg_1"})),
+                "/v1/messages" => (401, json!({})),
+                _ => (404, json!({})),
+            },
+            Duration::ZERO,
+        )
+        .await;
+
+        let result = probe(Tool::Claude, &base_url, "sk-ant", None, None).await;
+        assert_eq!(result.outcome, ProbeOutcome::Ok);
+        assert_eq!(result.url, format!("{}/v1/messages", base_url));
+
+        let result = probe(Tool::Claude, &base_url, "sk-wrong", None, None).await;
+        assert_eq!(result.outcome, ProbeOutcome::AuthFailed);
+    }
+
+    #[tokio::test]
+    async fn claude_messages_probe_uses_configured_model() {
+        let base_url = mock_server(
+            |path, _, body| match (path, body["model"].as_str()) {
+                ("/v1/messages", Some("relay-haiku")) => (200, json!({"id": "msg_1"})),
+                ("/v1/messages", Some(CLAUDE_PROBE_MODEL)) => (200, json!({"id": "msg_2"})),
+                ("/v1/messages", _) => (400, json!({"error": {"message": "unknown model"}})),
+                _ => (404, json!({})),
+            },
+            Duration::ZERO,
+        )
+        .await;
+
+        let result = probe(Tool::Claude, &base_url, "sk-ant", Some("relay-haiku"), None).await;
+        assert_eq!(result.outcome, ProbeOutcome::Ok);
+
+        let result = probe(Tool::Claude, &base_url, "sk-ant", Some("relay-opus"), None).await;
+        assert_eq!(result.message, "HTTP 400: unknown model");
+
+        // 未配置模型（或为空）时回退到内置探测模型
+        let result = probe(Tool::Claude, &base_url, "sk-ant", Some("  "), None).await;
+        assert_eq!(result.outcome, ProbeOutcome::Ok);
+    }
+
+    #[tokio::test]
+    async fn classifies_timeout_and_network_errors() {
+        let slow = mock_server(|_, _, _| (200, json!({"data": []})), Duration::from_secs(5)).await;
+        let result = probe(Tool::Codex, &slow, "sk", None, Some(200)).await;
+        assert_eq!(result.outcome, ProbeOutcome::Timeout);
+        assert_eq!(result.status, None);
+
+        // 绑定后立即释放端口，连接会被拒绝
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let result = probe(Tool::Codex, &format!("http://{}", addr), "sk", None, Some(2_000)).await;
+        assert_eq!(result.outcome, ProbeOutcome::NetworkError);
+    }
+}
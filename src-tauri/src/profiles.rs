@@ -0,0 +1,564 @@
+use crate::claude_config::{ClaudeModels, PermissionRules};
+use crate::config::{get_profiles_path, read_json_file, write_json_file};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "gui")]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// 配置库文件格式版本，结构变化时递增并在 `migrate` 中处理
+pub const PROFILE_STORE_VERSION: u32 = 1;
+
+/// 默认健康检查间隔（秒）
+pub const DEFAULT_HEALTH_INTERVAL_SECS: u64 = 300;
+
+/// 本地故障转移代理的默认端口
+pub const DEFAULT_PROXY_PORT: u16 = 15721;
+
+/// 串行化配置库的读-改-写，避免并发命令互相覆盖
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+/// 配置所属的工具
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tool {
+    Claude,
+    Codex,
+    Gemini,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 3] = [Tool::Claude, Tool::Codex, Tool::Gemini];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Tool::Claude => "claude",
+            Tool::Codex => "codex",
+            Tool::Gemini => "gemini",
+        }
+    }
+}
+
+/// 单个站点配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub id: String,
+    pub tool: Tool,
+    pub name: String,
+    pub base_url: String,
+    #[serde(default)]
+    pub api_key: String,
+    /// 高级配置中的自定义内容（Claude 为 settings.json，Codex 为 authJson/configToml）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_content: Option<String>,
+    /// Codex 读取密钥的环境变量名，为空时使用 key88
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_key: Option<String>,
+    /// Claude 使用的模型名与超时，切换时写入 settings.json 的 env
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub models: Option<ClaudeModels>,
+    #[serde(default)]
+    pub created_at: u64,
+    #[serde(default)]
+    pub updated_at: u64,
+    /// 保留未知字段，防止版本更新时丢失新字段
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// 每个工具当前激活的配置 ID
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ActiveProfiles {
+    #[serde(default)]
+    pub claude: Option<String>,
+    #[serde(default)]
+    pub codex: Option<String>,
+    #[serde(default)]
+    pub gemini: Option<String>,
+}
+
+impl ActiveProfiles {
+    pub fn get(&self, tool: Tool) -> Option<&String> {
+        match tool {
+            Tool::Claude => self.claude.as_ref(),
+            Tool::Codex => self.codex.as_ref(),
+            Tool::Gemini => self.gemini.as_ref(),
+        }
+    }
+
+    fn slot_mut(&mut self, tool: Tool) -> &mut Option<String> {
+        match tool {
+            Tool::Claude => &mut self.claude,
+            Tool::Codex => &mut self.codex,
+            Tool::Gemini => &mut self.gemini,
+        }
+    }
+}
+
+/// 按延迟自动选择站点的设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutoSwitchSettings {
+    /// 启用自动切换的工具
+    #[serde(default)]
+    pub tools: Vec<Tool>,
+    /// 健康检查间隔（秒）
+    #[serde(default = "default_health_interval")]
+    pub interval_secs: u64,
+}
+
+fn default_health_interval() -> u64 {
+    DEFAULT_HEALTH_INTERVAL_SECS
+}
+
+impl Default for AutoSwitchSettings {
+    fn default() -> Self {
+        Self {
+            tools: Vec::new(),
+            interval_secs: DEFAULT_HEALTH_INTERVAL_SECS,
+        }
+    }
+}
+
+/// 本地故障转移代理设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxySettings {
+    /// 监听端口（仅绑定 127.0.0.1）
+    #[serde(default = "default_proxy_port")]
+    pub port: u16,
+    /// 通过本地代理访问中转站的工具
+    #[serde(default)]
+    pub tools: Vec<Tool>,
+    /// 工具访问本地代理时使用的令牌
+    #[serde(default)]
+    pub token: String,
+}
+
+fn default_proxy_port() -> u16 {
+    DEFAULT_PROXY_PORT
+}
+
+impl Default for ProxySettings {
+    fn default() -> Self {
+        Self {
+            port: DEFAULT_PROXY_PORT,
+            tools: Vec::new(),
+            token: String::new(),
+        }
+    }
+}
+
+/// 某个项目目录使用的项目级配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectOverride {
+    /// 项目目录的绝对路径
+    pub path: String,
+    pub tool: Tool,
+    pub profile_id: String,
+    /// 写入项目 settings.local.json env 的变量（仅 Claude），下次切换前先删除
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub env_keys: Vec<String>,
+    /// 添加到项目 settings.local.json permissions 的规则（仅 Claude），下次切换前先删除
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub permission_rules: PermissionRules,
+    #[serde(default)]
+    pub updated_at: u64,
+}
+
+/// 磁盘上的配置库（profiles.json）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileStore {
+    pub version: u32,
+    #[serde(default)]
+    pub profiles: Vec<Profile>,
+    #[serde(default)]
+    pub active: ActiveProfiles,
+    #[serde(default)]
+    pub auto_switch: AutoSwitchSettings,
+    #[serde(default)]
+    pub proxy: ProxySettings,
+    #[serde(default)]
+    pub projects: Vec<ProjectOverride>,
+    /// XG-switch 设置过的用户环境变量，用于清理不再被引用的变量
+    #[serde(default)]
+    pub managed_env_keys: Vec<String>,
+    /// 当前写入 Claude settings.json env 的变量，下次切换前先删除
+    #[serde(default)]
+    pub claude_env_keys: Vec<String>,
+    /// 当前添加到 Claude settings.json permissions 的规则，下次切换前先删除
+    #[serde(default)]
+    pub claude_permission_rules: PermissionRules,
+}
+
+impl Default for ProfileStore {
+    fn default() -> Self {
+        Self {
+            version: PROFILE_STORE_VERSION,
+            profiles: Vec::new(),
+            active: ActiveProfiles::default(),
+            auto_switch: AutoSwitchSettings::default(),
+            proxy: ProxySettings::default(),
+            projects: Vec::new(),
+            managed_env_keys: Vec::new(),
+            claude_env_keys: Vec::new(),
+            claude_permission_rules: PermissionRules::new(),
+        }
+    }
+}
+
+/// 新建配置时前端传入的字段
+#[cfg(feature = "gui")]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileInput {
+    pub tool: Tool,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub base_url: String,
+    #[serde(default)]
+    pub api_key: String,
+    #[serde(default)]
+    pub config_content: Option<String>,
+    #[serde(default)]
+    pub env_key: Option<String>,
+    #[serde(default)]
+    pub models: Option<ClaudeModels>,
+}
+
+/// 更新配置时允许修改的字段，未提供的字段保持不变
+#[cfg(feature = "gui")]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileUpdate {
+    pub name: Option<String>,
+    pub base_url: Option<String>,
+    pub api_key: Option<String>,
+    pub config_content: Option<String>,
+    /// 传入空字符串表示恢复为默认的 key88
+    pub env_key: Option<String>,
+    /// 传入全部为空的对象表示清除
+    pub models: Option<ClaudeModels>,
+}
+
+/// 规范化环境变量名：去掉空白，空值视为未设置
+#[cfg(feature = "gui")]
+fn normalize_env_key(env_key: Option<String>) -> Result<Option<String>, String> {
+    let env_key = env_key
+        .map(|k| k.trim().to_string())
+        .filter(|k| !k.is_empty());
+    if let Some(key) = &env_key {
+        crate::env_manager::validate_env_key(key)?;
+    }
+    Ok(env_key)
+}
+
+/// 清理配置内容；Claude 配置的内容是 settings.json，需要通过校验
+#[cfg(feature = "gui")]
+fn normalize_config_content(tool: Tool, content: Option<String>) -> Result<Option<String>, String> {
+    let content = content.filter(|c| !c.trim().is_empty());
+    if let (Tool::Claude, Some(content)) = (tool, &content) {
+        crate::claude_config::parse_settings(content)?;
+    }
+    Ok(content)
+}
+
+/// 规范化模型设置；只有 Claude 配置使用
+#[cfg(feature = "gui")]
+fn normalize_models(tool: Tool, models: Option<ClaudeModels>) -> Result<Option<ClaudeModels>, String> {
+    match (tool, models) {
+        (Tool::Claude, Some(models)) => models.normalize(),
+        _ => Ok(None),
+    }
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// 生成唯一 ID（毫秒时间戳 + 进程内计数器）
+#[cfg(feature = "gui")]
+fn generate_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let seq = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    format!("profile_{}_{:x}{:x}", now_millis(), nanos, seq)
+}
+
+/// 将旧版本的配置库升级到当前版本
+fn migrate(store: &mut ProfileStore) -> Result<(), String> {
+    if store.version > PROFILE_STORE_VERSION {
+        return Err(format!(
+            "配置库版本 ({}) 高于当前程序支持的版本 ({})，请升级 XG-switch",
+            store.version, PROFILE_STORE_VERSION
+        ));
+    }
+    store.version = PROFILE_STORE_VERSION;
+    Ok(())
+}
+
+/// 读取配置库，不存在时返回空库
+pub fn load_store() -> Result<ProfileStore, String> {
+    let path = get_profiles_path();
+    if !path.exists() {
+        return Ok(ProfileStore::default());
+    }
+
+    let mut store: ProfileStore = read_json_file(&path)?;
+    migrate(&mut store)?;
+    Ok(store)
+}
+
+/// 保存配置库
+pub fn save_store(store: &ProfileStore) -> Result<(), String> {
+    write_json_file(&get_profiles_path(), store)
+}
+
+/// 在锁内读取、修改并保存配置库
+fn modify_store<R>(f: impl FnOnce(&mut ProfileStore) -> Result<R, String>) -> Result<R, String> {
+    let _guard = STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut store = load_store()?;
+    let result = f(&mut store)?;
+    save_store(&store)?;
+    Ok(result)
+}
+
+/// 列出所有配置
+pub fn list_profiles() -> Result<ProfileStore, String> {
+    let _guard = STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    load_store()
+}
+
+/// 按 ID 查找配置
+pub fn get_profile(id: &str) -> Result<Profile, String> {
+    list_profiles()?
+        .profiles
+        .into_iter()
+        .find(|p| p.id == id)
+        .ok_or_else(|| format!("配置不存在: {}", id))
+}
+
+/// 新建配置
+#[cfg(feature = "gui")]
+pub fn create_profile(input: ProfileInput) -> Result<Profile, String> {
+    modify_store(|store| {
+        let count = store.profiles.iter().filter(|p| p.tool == input.tool).count();
+        let name = if input.name.trim().is_empty() {
+            format!("配置 {}", count + 1)
+        } else {
+            input.name.trim().to_string()
+        };
+        let now = now_millis();
+
+        let profile = Profile {
+            id: generate_id(),
+            tool: input.tool,
+            name,
+            base_url: input.base_url.trim().to_string(),
+            api_key: input.api_key.trim().to_string(),
+            config_content: normalize_config_content(input.tool, input.config_content)?,
+            env_key: normalize_env_key(input.env_key)?,
+            models: normalize_models(input.tool, input.models)?,
+            created_at: now,
+            updated_at: now,
+            extra: HashMap::new(),
+        };
+
+        store.profiles.push(profile.clone());
+        log::info!("已新建 {} 配置: {}", profile.tool.as_str(), profile.name);
+        Ok(profile)
+    })
+}
+
+/// 更新配置
+#[cfg(feature = "gui")]
+pub fn update_profile(id: &str, updates: ProfileUpdate) -> Result<Profile, String> {
+    modify_store(|store| {
+        let profile = store
+            .profiles
+            .iter_mut()
+            .find(|p| p.id == id)
+            .ok_or_else(|| format!("配置不存在: {}", id))?;
+
+        if let Some(name) = updates.name {
+            profile.name = name.trim().to_string();
+        }
+        if let Some(base_url) = updates.base_url {
+            profile.base_url = base_url.trim().to_string();
+        }
+        if let Some(api_key) = updates.api_key {
+            profile.api_key = api_key.trim().to_string();
+        }
+        if let Some(content) = updates.config_content {
+            profile.config_content = normalize_config_content(profile.tool, Some(content))?;
+        }
+        if let Some(env_key) = updates.env_key {
+            profile.env_key = normalize_env_key(Some(env_key))?;
+        }
+        if let Some(models) = updates.models {
+            profile.models = normalize_models(profile.tool, Some(models))?;
+        }
+        profile.updated_at = now_millis();
+
+        Ok(profile.clone())
+    })
+}
+
+/// 删除配置，若为激活配置则同时清除激活状态
+#[cfg(feature = "gui")]
+pub fn delete_profile(id: &str) -> Result<(), String> {
+    modify_store(|store| {
+        let index = store
+            .profiles
+            .iter()
+            .position(|p| p.id == id)
+            .ok_or_else(|| format!("配置不存在: {}", id))?;
+        let removed = store.profiles.remove(index);
+
+        let slot = store.active.slot_mut(removed.tool);
+        if slot.as_deref() == Some(id) {
+            *slot = None;
+        }
+        Ok(())
+    })
+}
+
+/// 记录某个配置为其工具的当前激活配置
+pub fn mark_active(id: &str) -> Result<Profile, String> {
+    modify_store(|store| {
+        let profile = store
+            .profiles
+            .iter()
+            .find(|p| p.id == id)
+            .cloned()
+            .ok_or_else(|| format!("配置不存在: {}", id))?;
+        *store.active.slot_mut(profile.tool) = Some(profile.id.clone());
+        Ok(profile)
+    })
+}
+
+/// 批量导入配置（合并而不是替换），ID 冲突时重新生成
+#[cfg(feature = "gui")]
+pub fn import_profiles(profiles: Vec<Profile>) -> Result<usize, String> {
+    modify_store(|store| {
+        let count = profiles.len();
+        for mut profile in profiles {
+            if profile.id.is_empty() || store.profiles.iter().any(|p| p.id == profile.id) {
+                profile.id = generate_id();
+            }
+            if profile.created_at == 0 {
+                profile.created_at = now_millis();
+            }
+            store.profiles.push(profile);
+        }
+        Ok(count)
+    })
+}
+
+/// 开启或关闭某个工具的自动切换
+#[cfg(feature = "gui")]
+pub fn set_auto_switch(tool: Tool, enabled: bool) -> Result<AutoSwitchSettings, String> {
+    modify_store(|store| {
+        store.auto_switch.tools.retain(|t| *t != tool);
+        if enabled {
+            store.auto_switch.tools.push(tool);
+        }
+        Ok(store.auto_switch.clone())
+    })
+}
+
+/// 设置健康检查间隔（秒）
+#[cfg(feature = "gui")]
+pub fn set_health_interval(secs: u64) -> Result<AutoSwitchSettings, String> {
+    modify_store(|store| {
+        store.auto_switch.interval_secs = secs;
+        Ok(store.auto_switch.clone())
+    })
+}
+
+/// 开启或关闭某个工具的本地代理模式；令牌为空时使用传入的新令牌
+#[cfg(feature = "gui")]
+pub fn set_proxy_tool(tool: Tool, enabled: bool, new_token: &str) -> Result<ProxySettings, String> {
+    modify_store(|store| {
+        store.proxy.tools.retain(|t| *t != tool);
+        if enabled {
+            store.proxy.tools.push(tool);
+        }
+        if store.proxy.token.is_empty() {
+            store.proxy.token = new_token.to_string();
+        }
+        Ok(store.proxy.clone())
+    })
+}
+
+/// 记录项目目录使用的配置（同一项目同一工具只保留一条）
+pub fn set_project_override(
+    path: &str,
+    tool: Tool,
+    profile_id: &str,
+    env_keys: Vec<String>,
+    permission_rules: PermissionRules,
+) -> Result<ProjectOverride, String> {
+    modify_store(|store| {
+        store.projects.retain(|p| !(p.path == path && p.tool == tool));
+        let entry = ProjectOverride {
+            path: path.to_string(),
+            tool,
+            profile_id: profile_id.to_string(),
+            env_keys,
+            permission_rules,
+            updated_at: now_millis(),
+        };
+        store.projects.push(entry.clone());
+        Ok(entry)
+    })
+}
+
+/// 撤销工具的配置后清除其激活配置、代理模式和项目级配置记录（配置本身保留）
+pub fn forget_tool(tool: Tool) -> Result<(), String> {
+    modify_store(|store| {
+        *store.active.slot_mut(tool) = None;
+        store.proxy.tools.retain(|t| *t != tool);
+        store.projects.retain(|p| p.tool != tool);
+        if tool == Tool::Claude {
+            store.claude_env_keys.clear();
+            store.claude_permission_rules.clear();
+        }
+        Ok(())
+    })
+}
+
+/// 记录当前写入 Claude settings.json env 的变量和添加的权限规则
+pub fn set_claude_owned(keys: Vec<String>, rules: PermissionRules) -> Result<(), String> {
+    modify_store(|store| {
+        store.claude_env_keys = keys;
+        store.claude_permission_rules = rules;
+        Ok(())
+    })
+}
+
+/// 记录 XG-switch 设置过的环境变量
+pub fn add_managed_env_key(key: &str) -> Result<(), String> {
+    modify_store(|store| {
+        if !store.managed_env_keys.iter().any(|k| k == key) {
+            store.managed_env_keys.push(key.to_string());
+        }
+        Ok(())
+    })
+}
+
+/// 移除已清理的环境变量记录
+pub fn remove_managed_env_keys(keys: &[String]) -> Result<(), String> {
+    modify_store(|store| {
+        store.managed_env_keys.retain(|k| !keys.contains(k));
+        Ok(())
+    })
+}
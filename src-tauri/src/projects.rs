@@ -0,0 +1,157 @@
+use crate::claude_config::{self, PermissionRules};
+use crate::codex_config;
+use crate::config::{
+    get_project_claude_settings_path, get_project_codex_dir, get_project_gemini_dir,
+    read_json_file, DEFAULT_CLAUDE_BASE_URL, DEFAULT_CODEX_BASE_URL,
+};
+use crate::gemini_config;
+use crate::profiles::{self, Tool};
+use crate::switcher::{profile_claude_settings, resolve_api_key};
+use crate::transaction::Transaction;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// 项目级配置的当前状态（用于前端显示）
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectOverrideInfo {
+    pub path: String,
+    pub tool: Tool,
+    pub profile_id: String,
+    /// 配置已从配置库删除时为空
+    pub profile_name: Option<String>,
+    /// 项目中实际写入的配置文件或目录
+    pub config_path: String,
+    /// 项目配置中当前生效的 Base URL（文件被手动修改或删除后可能与配置库不一致）
+    pub base_url: Option<String>,
+    pub updated_at: u64,
+}
+
+/// 校验项目目录并转换为绝对路径
+fn resolve_project_dir(project_dir: &str) -> Result<PathBuf, String> {
+    let project_dir = project_dir.trim();
+    if project_dir.is_empty() {
+        return Err("项目目录不能为空".to_string());
+    }
+    let path = Path::new(project_dir)
+        .canonicalize()
+        .map_err(|e| format!("项目目录无效: {}", e))?;
+    if !path.is_dir() {
+        return Err(format!("不是目录: {}", path.display()));
+    }
+    Ok(path)
+}
+
+/// 工具在项目中使用的配置位置
+fn project_config_path(tool: Tool, project_dir: &Path) -> PathBuf {
+    match tool {
+        Tool::Claude => get_project_claude_settings_path(project_dir),
+        Tool::Codex => get_project_codex_dir(project_dir),
+        Tool::Gemini => get_project_gemini_dir(project_dir),
+    }
+}
+
+/// 读取项目配置中当前生效的 Base URL
+fn read_project_base_url(tool: Tool, project_dir: &Path) -> Option<String> {
+    let config_path = project_config_path(tool, project_dir);
+    match tool {
+        Tool::Claude => {
+            let settings: claude_config::ClaudeSettings = read_json_file(&config_path).ok()?;
+            settings.env.get("ANTHROPIC_BASE_URL").cloned()
+        }
+        Tool::Codex => codex_config::read_codex_base_url(&config_path.join("config.toml"))
+            .ok()
+            .flatten(),
+        Tool::Gemini => gemini_config::read_env_file(&config_path.join(".env"))
+            .ok()?
+            .remove(gemini_config::ENV_BASE_URL),
+    }
+}
+
+/// 将配置写入项目目录（只影响该项目），并记录到配置库
+pub fn apply_to_project(id: &str, project_dir: &str) -> Result<String, String> {
+    let profile = profiles::get_profile(id)?;
+    let project_dir = resolve_project_dir(project_dir)?;
+    let api_key = resolve_api_key(&profile.api_key)?;
+    let base_url = profile.base_url.trim();
+    let config_path = project_config_path(profile.tool, &project_dir);
+    let mut env_keys = Vec::new();
+    let mut permission_rules = PermissionRules::new();
+
+    let message = match profile.tool {
+        Tool::Claude => {
+            let base_url = if base_url.is_empty() { DEFAULT_CLAUDE_BASE_URL } else { base_url };
+            let settings = profile_claude_settings(&profile)?;
+            let path = project_dir.to_string_lossy();
+            let owned = profiles::list_profiles()?
+                .projects
+                .into_iter()
+                .find(|p| p.path == path && p.tool == Tool::Claude)
+                .map(|p| (p.env_keys, p.permission_rules))
+                .unwrap_or_default();
+            let update = claude_config::configure_claude_with(
+                &config_path,
+                base_url.to_string(),
+                api_key,
+                &owned.0,
+                &owned.1,
+                None,
+                settings.as_ref(),
+            )?;
+            env_keys = update.owned_keys;
+            permission_rules = update.owned_rules;
+            format!("已为项目配置 Claude Code: {}", config_path.display())
+        }
+        Tool::Codex => {
+            let base_url = if base_url.is_empty() { DEFAULT_CODEX_BASE_URL } else { base_url };
+            // 项目级配置不依赖全局环境变量，直接使用 auth.json 中的密钥
+            let mut tx = Transaction::new();
+            codex_config::stage_codex_config_in(&mut tx, &config_path, base_url, &api_key, None)?;
+            tx.commit()?;
+            format!(
+                "已为项目配置 Codex，请在项目中设置 CODEX_HOME={} 后运行 Codex",
+                config_path.display()
+            )
+        }
+        Tool::Gemini => {
+            let base_url = Some(base_url).filter(|u| !u.is_empty());
+            let mut tx = Transaction::new();
+            gemini_config::stage_gemini_config_in(&mut tx, &config_path, base_url, &api_key, None)?;
+            tx.commit()?;
+            format!("已为项目配置 Gemini CLI: {}", config_path.display())
+        }
+    };
+
+    let path = project_dir.to_string_lossy().to_string();
+    profiles::set_project_override(&path, profile.tool, &profile.id, env_keys, permission_rules)?;
+    log::info!("已为项目 {} 切换 {} 配置: {}", path, profile.tool.as_str(), profile.name);
+    Ok(message)
+}
+
+/// 列出所有设置了项目级配置的项目及其使用的配置
+pub fn list_project_overrides() -> Result<Vec<ProjectOverrideInfo>, String> {
+    let store = profiles::list_profiles()?;
+    let mut list: Vec<ProjectOverrideInfo> = store
+        .projects
+        .iter()
+        .map(|entry| {
+            let project_dir = Path::new(&entry.path);
+            ProjectOverrideInfo {
+                path: entry.path.clone(),
+                tool: entry.tool,
+                profile_id: entry.profile_id.clone(),
+                profile_name: store
+                    .profiles
+                    .iter()
+                    .find(|p| p.id == entry.profile_id)
+                    .map(|p| p.name.clone()),
+                config_path: project_config_path(entry.tool, project_dir)
+                    .to_string_lossy()
+                    .to_string(),
+                base_url: read_project_base_url(entry.tool, project_dir),
+                updated_at: entry.updated_at,
+            }
+        })
+        .collect();
+    list.sort_by(|a, b| a.path.cmp(&b.path).then(a.tool.as_str().cmp(b.tool.as_str())));
+    Ok(list)
+}
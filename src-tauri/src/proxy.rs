@@ -0,0 +1,553 @@
+use crate::config::get_profiles_path;
+use crate::health;
+use crate::probe;
+use crate::profiles::{self, Profile, ProfileStore, Tool};
+use crate::vault;
+use bytes::Bytes;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
+use futures_util::TryStreamExt;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full, StreamBody};
+use hyper::body::{Frame, Incoming};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::net::TcpListener;
+
+/// 等待上游返回响应头的超时；超时视为该中转站不可用并尝试下一个
+const UPSTREAM_HEADER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 连接上游的超时
+const UPSTREAM_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 不转发给上游的请求头（逐跳头与认证头）
+const SKIPPED_REQUEST_HEADERS: &[&str] = &[
+    "host",
+    "connection",
+    "content-length",
+    "transfer-encoding",
+    "authorization",
+    "x-api-key",
+    "x-goog-api-key",
+];
+
+/// 不回传给客户端的响应头
+const SKIPPED_RESPONSE_HEADERS: &[&str] = &["connection", "content-length", "transfer-encoding"];
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+static LISTEN_PORT: AtomicU16 = AtomicU16::new(0);
+
+/// 代理使用的配置库缓存，profiles.json 的修改时间或大小变化时重新读取
+static STORE_CACHE: Mutex<Option<CachedStore>> = Mutex::new(None);
+
+type ProxyBody = BoxBody<Bytes, std::io::Error>;
+
+struct CachedStore {
+    stamp: Option<(SystemTime, u64)>,
+    store: Arc<ProfileStore>,
+}
+
+/// 转发请求使用的 HTTP 客户端与上游超时
+#[derive(Clone)]
+struct Upstream {
+    client: reqwest::Client,
+    header_timeout: Duration,
+}
+
+impl Upstream {
+    fn new(header_timeout: Duration) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(UPSTREAM_CONNECT_TIMEOUT)
+            .build()
+            .map_err(|e| format!("创建 HTTP 客户端失败: {}", e))?;
+        Ok(Self { client, header_timeout })
+    }
+}
+
+/// 本地代理状态（用于前端显示）
+#[derive(Debug, Clone, Serialize)]
+pub struct ProxyStatus {
+    pub running: bool,
+    pub port: u16,
+    pub tools: Vec<Tool>,
+}
+
+/// 工具在本地代理上的路由前缀
+fn route_prefix(tool: Tool) -> &'static str {
+    match tool {
+        Tool::Claude => "/claude",
+        Tool::Codex => "/codex",
+        Tool::Gemini => "/gemini",
+    }
+}
+
+/// 工具连接本地代理时使用的 Base URL
+pub fn local_base_url(port: u16, tool: Tool) -> String {
+    format!("http://127.0.0.1:{}{}", port, route_prefix(tool))
+}
+
+/// 生成访问本地代理的随机令牌
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 24];
+    OsRng.fill_bytes(&mut bytes);
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("xg-local-{}", hex)
+}
+
+/// 获取代理状态
+pub fn status() -> Result<ProxyStatus, String> {
+    let store = profiles::list_profiles()?;
+    let running = RUNNING.load(Ordering::SeqCst);
+    Ok(ProxyStatus {
+        running,
+        port: if running {
+            LISTEN_PORT.load(Ordering::SeqCst)
+        } else {
+            store.proxy.port
+        },
+        tools: store.proxy.tools,
+    })
+}
+
+/// 读取配置库，文件未变化时复用上次读取的结果
+fn current_store() -> Result<Arc<ProfileStore>, String> {
+    let stamp = std::fs::metadata(get_profiles_path())
+        .ok()
+        .and_then(|m| Some((m.modified().ok()?, m.len())));
+    let mut cache = STORE_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(cached) = cache.as_ref().filter(|c| c.stamp.is_some() && c.stamp == stamp) {
+        return Ok(cached.store.clone());
+    }
+    let store = Arc::new(profiles::list_profiles()?);
+    *cache = Some(CachedStore {
+        stamp,
+        store: store.clone(),
+    });
+    Ok(store)
+}
+
+fn full(status: StatusCode, message: &str) -> Response<ProxyBody> {
+    let body = serde_json::json!({
+        "type": "error",
+        "error": { "type": "proxy_error", "message": message },
+    })
+    .to_string();
+    let mut response = Response::new(
+        Full::new(Bytes::from(body))
+            .map_err(|never| match never {})
+            .boxed(),
+    );
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert("content-type", HeaderValue::from_static("application/json"));
+    response
+}
+
+/// 从请求头中取出客户端使用的令牌
+fn client_token(headers: &HeaderMap) -> Option<&str> {
+    if let Some(key) = headers
+        .get("x-api-key")
+        .or_else(|| headers.get("x-goog-api-key"))
+        .and_then(|v| v.to_str().ok())
+    {
+        return Some(key);
+    }
+    headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+}
+
+/// 按优先级排列候选中转站：当前激活配置优先，其余按健康程度排序
+fn candidates(store: &ProfileStore, tool: Tool) -> Vec<Profile> {
+    let report = health::health_report(store);
+    let active = store.active.get(tool);
+
+    let mut list: Vec<&Profile> = store.profiles.iter().filter(|p| p.tool == tool).collect();
+    list.sort_by(|a, b| {
+        let a_active = Some(&a.id) == active;
+        let b_active = Some(&b.id) == active;
+        b_active.cmp(&a_active).then_with(|| {
+            let ha = report.iter().find(|h| h.profile_id == a.id);
+            let hb = report.iter().find(|h| h.profile_id == b.id);
+            match (ha, hb) {
+                (Some(ha), Some(hb)) => health::healthier(ha, hb),
+                _ => std::cmp::Ordering::Equal,
+            }
+        })
+    });
+    list.into_iter().cloned().collect()
+}
+
+/// 将请求转发给单个上游；返回 Err 表示应故障转移到下一个（网络错误、超时或 5xx）
+async fn forward(
+    upstream: &Upstream,
+    profile: &Profile,
+    method: &hyper::Method,
+    path_and_query: &str,
+    headers: &HeaderMap,
+    body: &Bytes,
+) -> Result<Response<ProxyBody>, String> {
+    let api_key = vault::resolve_secret(&profile.api_key)?;
+    let base_url = probe::normalize_base_url(profile.tool, &profile.base_url);
+    let url = format!("{}{}", base_url, path_and_query);
+
+    let method = reqwest::Method::from_bytes(method.as_str().as_bytes())
+        .map_err(|e| format!("不支持的请求方法: {}", e))?;
+    let mut request = upstream.client.request(method, &url).body(body.clone());
+    for (name, value) in headers {
+        if !SKIPPED_REQUEST_HEADERS.contains(&name.as_str()) {
+            request = request.header(name.as_str(), value.as_bytes());
+        }
+    }
+    let request = probe::authorize(profile.tool, request, api_key.trim());
+
+    let response = tokio::time::timeout(upstream.header_timeout, request.send())
+        .await
+        .map_err(|_| "等待上游响应超时".to_string())?
+        .map_err(|e| format!("请求上游失败: {}", e))?;
+
+    let status = response.status();
+    if status.is_server_error() {
+        return Err(format!("上游返回 HTTP {}", status.as_u16()));
+    }
+
+    let mut builder = Response::builder().status(status.as_u16());
+    for (name, value) in response.headers() {
+        if SKIPPED_RESPONSE_HEADERS.contains(&name.as_str()) {
+            continue;
+        }
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_str().as_bytes()),
+            HeaderValue::from_bytes(value.as_bytes()),
+        ) {
+            builder = builder.header(name, value);
+        }
+    }
+
+    // 流式回传响应体（支持 SSE）
+    let stream = response
+        .bytes_stream()
+        .map_ok(Frame::data)
+        .map_err(std::io::Error::other);
+    builder
+        .body(BodyExt::boxed(StreamBody::new(stream)))
+        .map_err(|e| format!("构建响应失败: {}", e))
+}
+
+async fn handle(
+    upstream: Upstream,
+    request: Request<Incoming>,
+) -> Result<Response<ProxyBody>, Infallible> {
+    match current_store() {
+        Ok(store) => Ok(dispatch(&upstream, &store, request).await),
+        Err(e) => Ok(full(StatusCode::INTERNAL_SERVER_ERROR, &e)),
+    }
+}
+
+/// 校验令牌后按候选顺序转发请求，上游不可用时故障转移到下一个
+async fn dispatch<B>(upstream: &Upstream, store: &ProfileStore, request: Request<B>) -> Response<ProxyBody>
+where
+    B: hyper::body::Body<Data = Bytes>,
+    B::Error: std::fmt::Display,
+{
+    let path = request.uri().path().to_string();
+    let tool = [Tool::Claude, Tool::Codex, Tool::Gemini].into_iter().find(|t| {
+        let prefix = route_prefix(*t);
+        path == prefix || path.starts_with(&format!("{}/", prefix))
+    });
+    let Some(tool) = tool else {
+        return full(StatusCode::NOT_FOUND, "未知的代理路径");
+    };
+
+    if store.proxy.token.is_empty() || client_token(request.headers()) != Some(store.proxy.token.as_str()) {
+        return full(StatusCode::UNAUTHORIZED, "本地代理令牌无效");
+    }
+
+    let rest = &path[route_prefix(tool).len()..];
+    let path_and_query = match request.uri().query() {
+        Some(query) => format!("{}?{}", rest, query),
+        None => rest.to_string(),
+    };
+
+    // 缓存请求体以便故障转移时重发
+    let (parts, body) = request.into_parts();
+    let body = match body.collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(e) => return full(StatusCode::BAD_REQUEST, &format!("读取请求体失败: {}", e)),
+    };
+
+    let mut errors = Vec::new();
+    for profile in candidates(store, tool) {
+        match forward(upstream, &profile, &parts.method, &path_and_query, &parts.headers, &body).await {
+            Ok(response) => {
+                if !errors.is_empty() {
+                    log::warn!("代理已故障转移到 {}", profile.name);
+                }
+                return response;
+            }
+            Err(e) => {
+                log::warn!("代理转发到 {} 失败: {}", profile.name, e);
+                errors.push(format!("{}: {}", profile.name, e));
+            }
+        }
+    }
+
+    let message = if errors.is_empty() {
+        format!("没有可用的 {} 配置", tool.as_str())
+    } else {
+        format!("所有中转站均不可用: {}", errors.join("; "))
+    };
+    full(StatusCode::BAD_GATEWAY, &message)
+}
+
+/// 启动本地代理（仅监听 127.0.0.1）
+#[cfg(feature = "gui")]
+pub fn start() {
+    tauri::async_runtime::spawn(async move {
+        let port = profiles::list_profiles()
+            .map(|s| s.proxy.port)
+            .unwrap_or(profiles::DEFAULT_PROXY_PORT);
+        let addr = SocketAddr::from(([127, 0, 0, 1], port));
+
+        let listener = match TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                log::warn!("本地代理启动失败 ({}): {}", addr, e);
+                return;
+            }
+        };
+        let upstream = match Upstream::new(UPSTREAM_HEADER_TIMEOUT) {
+            Ok(upstream) => upstream,
+            Err(e) => {
+                log::warn!("本地代理{}", e);
+                return;
+            }
+        };
+
+        RUNNING.store(true, Ordering::SeqCst);
+        LISTEN_PORT.store(port, Ordering::SeqCst);
+        log::info!("本地代理已启动: http://{}", addr);
+
+        loop {
+            let (stream, _) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    log::warn!("本地代理接受连接失败: {}", e);
+                    continue;
+                }
+            };
+            let upstream = upstream.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |req| handle(upstream.clone(), req));
+                if let Err(e) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    log::debug!("本地代理连接结束: {}", e);
+                }
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::stream;
+    use hyper::body::Body;
+    use serde_json::json;
+    use std::sync::atomic::AtomicUsize;
+
+    const TOKEN: &str = "xg-local-test";
+
+    /// 本地模拟中转站：以给定状态码分块返回 chunks，返回 Base URL 与收到的请求数
+    async fn mock_upstream(status: u16, delay: Duration, chunks: &'static [&'static str]) -> (String, Arc<AtomicUsize>) {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else { return };
+                let counter = counter.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |req: Request<Incoming>| {
+                        counter.fetch_add(1, Ordering::SeqCst);
+                        async move {
+                            tokio::time::sleep(delay).await;
+                            assert_eq!(req.headers()["authorization"], "Bearer sk-upstream");
+                            let frames: Vec<Result<Frame<Bytes>, Infallible>> =
+                                chunks.iter().map(|c| Ok(Frame::data(Bytes::from_static(c.as_bytes())))).collect();
+                            let response = Response::builder()
+                                .status(status)
+                                .header("content-type", "text/event-stream")
+                                .body(StreamBody::new(stream::iter(frames)))
+                                .unwrap();
+                            Ok::<_, Infallible>(response)
+                        }
+                    });
+                    let _ = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await;
+                });
+            }
+        });
+        (format!("http://{}", addr), hits)
+    }
+
+    fn profile(id: &str, tool: &str, base_url: &str) -> Profile {
+        serde_json::from_value(json!({
+            "id": id,
+            "tool": tool,
+            "name": id,
+            "baseUrl": base_url,
+            "apiKey": "sk-upstream",
+        }))
+        .unwrap()
+    }
+
+    fn store(profiles: Vec<Profile>, active: &str) -> ProfileStore {
+        let mut store = ProfileStore {
+            profiles,
+            ..ProfileStore::default()
+        };
+        store.active.codex = Some(active.to_string());
+        store.proxy.token = TOKEN.to_string();
+        store
+    }
+
+    fn request(token: Option<&str>) -> Request<Full<Bytes>> {
+        let mut builder = Request::post("/codex/responses?stream=true");
+        if let Some(token) = token {
+            builder = builder.header("authorization", format!("Bearer {}", token));
+        }
+        builder.body(Full::new(Bytes::from_static(b"{\"model\": \"gpt-5\"}"))).unwrap()
+    }
+
+    async fn body_text(response: Response<ProxyBody>) -> String {
+        String::from_utf8(response.into_body().collect().await.unwrap().to_bytes().to_vec()).unwrap()
+    }
+
+    fn upstream() -> Upstream {
+        Upstream::new(Duration::from_millis(500)).unwrap()
+    }
+
+    /// 绑定后立即释放端口，连接会被拒绝
+    async fn closed_url() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        format!("http://{}", addr)
+    }
+
+    #[test]
+    fn store_is_reloaded_only_after_it_changes() {
+        let _home = crate::test_support::temp_home();
+        profiles::save_store(&store(vec![profile("a", "codex", "")], "a")).unwrap();
+
+        let first = current_store().unwrap();
+        assert!(Arc::ptr_eq(&first, &current_store().unwrap()));
+
+        profiles::save_store(&store(vec![profile("a", "codex", ""), profile("b", "codex", "")], "b")).unwrap();
+        let reloaded = current_store().unwrap();
+        assert_eq!(reloaded.profiles.len(), 2);
+        assert_eq!(reloaded.active.codex.as_deref(), Some("b"));
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_or_wrong_token() {
+        let (base_url, hits) = mock_upstream(200, Duration::ZERO, &["ok"]).await;
+        let store = store(vec![profile("a", "codex", &base_url)], "a");
+
+        for token in [None, Some("xg-local-wrong")] {
+            let response = dispatch(&upstream(), &store, request(token)).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+
+        let response = dispatch(&upstream(), &store, request(Some(TOKEN))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn candidates_put_active_profile_first() {
+        let store = store(
+            vec![
+                profile("a", "codex", ""),
+                profile("b", "codex", ""),
+                profile("g", "gemini", ""),
+                profile("c", "codex", ""),
+            ],
+            "c",
+        );
+        let ids: Vec<String> = candidates(&store, Tool::Codex).into_iter().map(|p| p.id).collect();
+        assert_eq!(ids, ["c", "a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn fails_over_on_server_error_timeout_and_connect_error() {
+        let (failing, failing_hits) = mock_upstream(502, Duration::ZERO, &["bad gateway"]).await;
+        let (slow, slow_hits) = mock_upstream(200, Duration::from_secs(5), &["late"]).await;
+        let (healthy, healthy_hits) = mock_upstream(200, Duration::ZERO, &["ok"]).await;
+        let store = store(
+            vec![
+                profile("failing", "codex", &failing),
+                profile("closed", "codex", &closed_url().await),
+                profile("slow", "codex", &slow),
+                profile("healthy", "codex", &healthy),
+            ],
+            "failing",
+        );
+
+        let response = dispatch(&upstream(), &store, request(Some(TOKEN))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_text(response).await, "ok");
+        for hits in [failing_hits, slow_hits, healthy_hits] {
+            assert_eq!(hits.load(Ordering::SeqCst), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_returned_without_failover() {
+        let (rejecting, _) = mock_upstream(400, Duration::ZERO, &["{\"error\": \"bad request\"}"]).await;
+        let (healthy, healthy_hits) = mock_upstream(200, Duration::ZERO, &["ok"]).await;
+        let store = store(
+            vec![profile("rejecting", "codex", &rejecting), profile("healthy", "codex", &healthy)],
+            "rejecting",
+        );
+
+        let response = dispatch(&upstream(), &store, request(Some(TOKEN))).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(body_text(response).await, "{\"error\": \"bad request\"}");
+        assert_eq!(healthy_hits.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn all_upstreams_down_returns_bad_gateway() {
+        let store = store(vec![profile("closed", "codex", &closed_url().await)], "closed");
+        let response = dispatch(&upstream(), &store, request(Some(TOKEN))).await;
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert!(body_text(response).await.contains("所有中转站均不可用"));
+    }
+
+    #[tokio::test]
+    async fn streams_response_body_unchanged() {
+        const EVENTS: &[&str] = &["event: delta\ndata: {\"text\": \"你\"}\n\n", "event: delta\ndata: {\"text\": \"好\"}\n\n", "data: [DONE]\n\n"];
+        let (base_url, _) = mock_upstream(200, Duration::ZERO, EVENTS).await;
+        let store = store(vec![profile("a", "codex", &base_url)], "a");
+
+        let response = dispatch(&upstream(), &store, request(Some(TOKEN))).await;
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+        assert!(response.body().size_hint().exact().is_none());
+        assert_eq!(body_text(response).await, EVENTS.concat());
+    }
+}
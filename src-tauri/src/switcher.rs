@@ -0,0 +1,257 @@
+use crate::claude_config::{self, ClaudeSettings};
+use crate::codex_config;
+use crate::env_manager;
+use crate::gemini_config;
+use crate::profiles::{self, Profile, Tool};
+#[cfg(feature = "gui")]
+use crate::proxy;
+use crate::transaction::Transaction;
+use crate::vault;
+
+/// 校验 API 密钥并解析 vault:<name> 形式的密钥引用
+pub(crate) fn resolve_api_key(api_key: &str) -> Result<String, String> {
+    if api_key.trim().is_empty() {
+        return Err("API 密钥不能为空".to_string());
+    }
+    Ok(vault::resolve_secret(api_key)?.trim().to_string())
+}
+
+/// 配置 Claude Code
+#[cfg(feature = "gui")]
+pub fn apply_claude(base_url: &str, api_key: &str) -> Result<String, String> {
+    apply_claude_with(base_url, api_key, None, None)
+}
+
+/// 配置 Claude Code，并把上一个配置携带的设置（模型、hooks、权限等）切换为新配置的设置
+pub fn apply_claude_with(
+    base_url: &str,
+    api_key: &str,
+    previous: Option<&ClaudeSettings>,
+    next: Option<&ClaudeSettings>,
+) -> Result<String, String> {
+    let api_key = resolve_api_key(api_key)?;
+
+    // 如果 base_url 为空，使用 Claude 默认值
+    let base_url = crate::probe::base_url_or_default(Tool::Claude, base_url);
+
+    let store = profiles::list_profiles()?;
+    let update = claude_config::configure_claude_code(
+        base_url,
+        api_key,
+        &store.claude_env_keys,
+        &store.claude_permission_rules,
+        previous,
+        next,
+    )?;
+    profiles::set_claude_owned(update.owned_keys, update.owned_rules)?;
+
+    if update.diff.is_empty() {
+        return Ok("Claude Code 配置成功！".to_string());
+    }
+    Ok(format!("Claude Code 配置成功！env 变化: {}", update.diff.summary()))
+}
+
+/// 配置 Codex 并设置环境变量，env_key 为空时使用 key88
+pub fn apply_codex(base_url: &str, api_key: &str, env_key: Option<&str>) -> Result<String, String> {
+    let api_key = resolve_api_key(api_key)?;
+    let env_key = env_key
+        .map(str::trim)
+        .filter(|k| !k.is_empty())
+        .unwrap_or(env_manager::DEFAULT_ENV_KEY);
+
+    // 如果 base_url 为空，使用 Codex 默认值
+    let base_url = crate::probe::base_url_or_default(Tool::Codex, base_url);
+
+    // 配置 Codex 并设置环境变量，任一步失败则全部回滚
+    let mut tx = Transaction::new();
+    codex_config::stage_codex_config(&mut tx, &base_url, &api_key, env_key)?;
+    env_manager::stage_env(&mut tx, env_key, api_key)?;
+    tx.commit()?;
+
+    profiles::add_managed_env_key(env_key)?;
+    if let Err(e) = cleanup_env_keys() {
+        log::warn!("清理环境变量失败: {}", e);
+    }
+
+    #[cfg(windows)]
+    {
+        Ok(format!("Codex 配置成功！环境变量 {} 已设置，请重启 Codex 以使环境变量生效。", env_key))
+    }
+
+    #[cfg(not(windows))]
+    {
+        Ok(format!("Codex 配置成功！环境变量 {} 已写入 ~/.config/xg-switch/env.sh，新开的终端即可生效（已启用 shell-init 钩子的终端会自动重新加载）。", env_key))
+    }
+}
+
+/// 删除 XG-switch 设置过、但已没有 Codex 配置或 config.toml 提供方引用的环境变量
+pub fn cleanup_env_keys() -> Result<Vec<String>, String> {
+    let store = profiles::list_profiles()?;
+    let mut referenced: Vec<String> = store
+        .profiles
+        .iter()
+        .filter(|p| p.tool == Tool::Codex)
+        .map(|p| p.env_key.clone().unwrap_or_else(|| env_manager::DEFAULT_ENV_KEY.to_string()))
+        .collect();
+    referenced.extend(
+        codex_config::list_providers()?
+            .providers
+            .into_iter()
+            .filter_map(|p| p.env_key),
+    );
+
+    let mut removed = Vec::new();
+    for key in &store.managed_env_keys {
+        if referenced.contains(key) {
+            continue;
+        }
+        env_manager::remove_env(key)?;
+        log::info!("已清理不再使用的环境变量: {}", key);
+        removed.push(key.clone());
+    }
+
+    if !removed.is_empty() {
+        profiles::remove_managed_env_keys(&removed)?;
+    }
+    Ok(removed)
+}
+
+/// 配置 Gemini CLI
+pub fn apply_gemini(base_url: &str, api_key: &str, model: Option<&str>) -> Result<String, String> {
+    let api_key = resolve_api_key(api_key)?;
+
+    // base_url 为空时使用 Google 官方服务
+    let base_url = Some(base_url.trim()).filter(|u| !u.is_empty());
+    let model = model.map(str::trim).filter(|m| !m.is_empty());
+
+    gemini_config::configure_gemini(base_url, &api_key, model)?;
+
+    Ok("Gemini 配置成功！请重新启动 Gemini CLI 以使配置生效。".to_string())
+}
+
+/// 让工具在当前终端使用该配置所需的环境变量（不修改任何配置文件）
+pub fn profile_env_vars(profile: &Profile) -> Result<Vec<(String, String)>, String> {
+    let api_key = resolve_api_key(&profile.api_key)?;
+    let base_url = profile.base_url.trim();
+
+    let vars = match profile.tool {
+        Tool::Claude => {
+            let mut vars = vec![
+                ("ANTHROPIC_BASE_URL".to_string(), crate::probe::base_url_or_default(Tool::Claude, base_url)),
+                ("ANTHROPIC_AUTH_TOKEN".to_string(), api_key),
+            ];
+            if let Some(models) = &profile.models {
+                vars.extend(models.env_vars());
+            }
+            vars
+        }
+        Tool::Codex => {
+            let env_key = profile
+                .env_key
+                .clone()
+                .unwrap_or_else(|| env_manager::DEFAULT_ENV_KEY.to_string());
+            vec![(env_key, api_key)]
+        }
+        Tool::Gemini => {
+            let mut vars = vec![(gemini_config::ENV_API_KEY.to_string(), api_key)];
+            if !base_url.is_empty() {
+                vars.push((gemini_config::ENV_BASE_URL.to_string(), base_url.to_string()));
+            }
+            vars
+        }
+    };
+    Ok(vars)
+}
+
+/// Claude 配置携带的设置：配置内容（settings.json）加上模型设置对应的环境变量
+pub fn profile_claude_settings(profile: &Profile) -> Result<Option<ClaudeSettings>, String> {
+    if profile.tool != Tool::Claude {
+        return Ok(None);
+    }
+    let mut settings = match profile.config_content.as_deref() {
+        Some(content) => Some(
+            claude_config::parse_settings(content)
+                .map_err(|e| format!("配置 {} 的内容无效: {}", profile.name, e))?,
+        ),
+        None => None,
+    };
+    if let Some(models) = &profile.models {
+        settings.get_or_insert_default().env.extend(models.env_vars());
+    }
+    Ok(settings)
+}
+
+/// 应用配置库中的配置并将其标记为当前激活配置
+pub fn activate_profile(id: &str) -> Result<String, String> {
+    let profile = profiles::get_profile(id)?;
+    let store = profiles::list_profiles()?;
+
+    // 代理模式下工具始终指向本地代理，只需切换代理优先使用的配置
+    if store.proxy.tools.contains(&profile.tool) {
+        profiles::mark_active(&profile.id)?;
+        log::info!("已切换 {} 配置（通过本地代理）: {}", profile.tool.as_str(), profile.name);
+        return Ok(format!("已切换到 {}（通过本地代理），无需重启工具。", profile.name));
+    }
+
+    let message = match profile.tool {
+        Tool::Claude => {
+            // 上一个配置的内容无法解析时只跳过撤销，不影响切换
+            let previous = store
+                .active
+                .get(Tool::Claude)
+                .and_then(|id| store.profiles.iter().find(|p| &p.id == id))
+                .and_then(|p| profile_claude_settings(p).unwrap_or_default());
+            let next = profile_claude_settings(&profile)?;
+            apply_claude_with(&profile.base_url, &profile.api_key, previous.as_ref(), next.as_ref())?
+        }
+        Tool::Codex => apply_codex(&profile.base_url, &profile.api_key, profile.env_key.as_deref())?,
+        Tool::Gemini => apply_gemini(&profile.base_url, &profile.api_key, None)?,
+    };
+
+    profiles::mark_active(&profile.id)?;
+    log::info!("已切换 {} 配置: {}", profile.tool.as_str(), profile.name);
+    Ok(message)
+}
+
+/// 开启或关闭工具的本地代理模式：
+/// 开启时把工具的 Base URL 指向本地代理，关闭时重新应用当前激活的配置
+#[cfg(feature = "gui")]
+pub fn set_proxy_mode(tool: Tool, enabled: bool) -> Result<String, String> {
+    let store = profiles::list_profiles()?;
+    let active = store
+        .active
+        .get(tool)
+        .and_then(|id| store.profiles.iter().find(|p| &p.id == id))
+        .cloned();
+
+    if !enabled {
+        profiles::set_proxy_tool(tool, false, "")?;
+        return match active {
+            Some(profile) => activate_profile(&profile.id),
+            None => Ok(format!("已关闭 {} 的本地代理模式", tool.as_str())),
+        };
+    }
+
+    if !store.profiles.iter().any(|p| p.tool == tool) {
+        return Err(format!("请先添加至少一个 {} 配置", tool.as_str()));
+    }
+
+    let settings = profiles::set_proxy_tool(tool, true, &proxy::generate_token())?;
+    let base_url = proxy::local_base_url(settings.port, tool);
+    let result = match tool {
+        Tool::Claude => apply_claude(&base_url, &settings.token),
+        Tool::Codex => apply_codex(&base_url, &settings.token, None),
+        Tool::Gemini => apply_gemini(&base_url, &settings.token, None),
+    };
+    if let Err(e) = result {
+        profiles::set_proxy_tool(tool, false, "")?;
+        return Err(e);
+    }
+
+    log::info!("已开启 {} 的本地代理模式: {}", tool.as_str(), base_url);
+    Ok(format!(
+        "已开启本地代理模式，{} 将通过 {} 访问中转站，当前配置不可用时自动故障转移。",
+        tool.as_str(),
+        base_url
+    ))
+}
@@ -0,0 +1,51 @@
+//! 测试共用的临时主目录：HOME 与 XDG 目录都指向同一个临时目录，
+//! 读写用户配置的测试持有同一把锁，避免并行测试互相覆盖环境变量
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+static HOME_LOCK: Mutex<()> = Mutex::new(());
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+pub struct TempHome {
+    path: PathBuf,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl TempHome {
+    /// 临时主目录下的路径（不创建文件）
+    pub fn path_of(&self, relative: &str) -> PathBuf {
+        self.path.join(relative)
+    }
+
+    /// 在临时主目录下写入文件（自动创建父目录），返回完整路径
+    pub fn write(&self, relative: &str, content: &str) -> PathBuf {
+        let path = self.path_of(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+}
+
+impl Drop for TempHome {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// 创建空的临时主目录并切换 HOME、XDG_CONFIG_HOME、XDG_DATA_HOME
+pub fn temp_home() -> TempHome {
+    let guard = HOME_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = std::env::temp_dir().join(format!(
+        "xg-switch-test-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    std::env::set_var("HOME", &path);
+    std::env::set_var("XDG_CONFIG_HOME", path.join(".config"));
+    std::env::set_var("XDG_DATA_HOME", path.join(".local/share"));
+    TempHome { path, _guard: guard }
+}
@@ -0,0 +1,180 @@
+use crate::config::atomic_write;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 事务中的一个非文件步骤（例如设置环境变量）
+struct Step {
+    description: String,
+    action: Box<dyn FnOnce() -> Result<(), String> + Send>,
+}
+
+/// 一次切换涉及的多文件事务：先暂存所有改动，提交时依次写入，
+/// 任一步骤失败则把所有涉及的文件恢复为提交前的内容。
+#[derive(Default)]
+pub struct Transaction {
+    writes: Vec<(PathBuf, Vec<u8>)>,
+    /// 由步骤间接修改、需要在回滚时恢复的文件
+    guarded: Vec<PathBuf>,
+    steps: Vec<Step>,
+}
+
+impl Transaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 暂存一次文件写入；同一路径多次写入时以最后一次为准
+    pub fn write(&mut self, path: &Path, data: Vec<u8>) {
+        self.writes.retain(|(p, _)| p != path);
+        self.writes.push((path.to_path_buf(), data));
+    }
+
+    /// 暂存文本文件写入
+    pub fn write_text(&mut self, path: &Path, data: &str) {
+        self.write(path, data.as_bytes().to_vec());
+    }
+
+    /// 暂存 JSON 文件写入
+    pub fn write_json<T: Serialize>(&mut self, path: &Path, data: &T) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(data)
+            .map_err(|e| format!("序列化 JSON 失败: {}", e))?;
+        self.write(path, json.into_bytes());
+        Ok(())
+    }
+
+    /// 声明某个文件会被后续步骤修改，回滚时需要恢复
+    pub fn guard(&mut self, path: &Path) {
+        if !self.guarded.iter().any(|p| p == path) {
+            self.guarded.push(path.to_path_buf());
+        }
+    }
+
+    /// 追加一个在所有文件写入之后执行的步骤。
+    /// 回滚只恢复文件（写入的文件和 `guard` 声明的文件），步骤在文件之外的副作用不会撤销，
+    /// 例如 Windows 下用 setx 写入注册表的用户环境变量
+    pub fn step(
+        &mut self,
+        description: impl Into<String>,
+        action: impl FnOnce() -> Result<(), String> + Send + 'static,
+    ) {
+        self.steps.push(Step {
+            description: description.into(),
+            action: Box::new(action),
+        });
+    }
+
+    /// 提交事务：全部成功，或回滚到提交前的状态
+    pub fn commit(self) -> Result<(), String> {
+        // 记录所有涉及文件的原始内容（None 表示原本不存在）
+        let mut originals: Vec<(PathBuf, Option<Vec<u8>>)> = Vec::new();
+        let paths = self
+            .writes
+            .iter()
+            .map(|(p, _)| p)
+            .chain(self.guarded.iter());
+        for path in paths {
+            if originals.iter().any(|(p, _)| p == path) {
+                continue;
+            }
+            let original = match fs::read(path) {
+                Ok(data) => Some(data),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(format!("读取 {} 失败: {}", path.display(), e)),
+            };
+            originals.push((path.clone(), original));
+        }
+
+        let result = Self::apply(self.writes, self.steps);
+        if let Err(e) = result {
+            log::warn!("切换失败，正在回滚: {}", e);
+            let rollback_errors = Self::rollback(&originals);
+            if rollback_errors.is_empty() {
+                return Err(format!("{}（已回滚全部改动）", e));
+            }
+            return Err(format!("{}；回滚时出错: {}", e, rollback_errors.join("; ")));
+        }
+
+        Ok(())
+    }
+
+    fn apply(writes: Vec<(PathBuf, Vec<u8>)>, steps: Vec<Step>) -> Result<(), String> {
+        for (path, data) in &writes {
+            atomic_write(path, data).map_err(|e| format!("写入 {} 失败: {}", path.display(), e))?;
+        }
+        for step in steps {
+            (step.action)().map_err(|e| format!("{}失败: {}", step.description, e))?;
+        }
+        Ok(())
+    }
+
+    /// 逆序恢复原始内容，返回恢复失败的信息
+    fn rollback(originals: &[(PathBuf, Option<Vec<u8>>)]) -> Vec<String> {
+        let mut errors = Vec::new();
+        for (path, original) in originals.iter().rev() {
+            let current = fs::read(path).ok();
+            if current.as_ref() == original.as_ref() {
+                continue;
+            }
+
+            let result = match original {
+                Some(data) => atomic_write(path, data),
+                None => fs::remove_file(path).map_err(|e| format!("删除文件失败: {}", e)),
+            };
+            if let Err(e) = result {
+                errors.push(format!("{}: {}", path.display(), e));
+            }
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_home;
+
+    #[test]
+    fn commit_writes_files_and_runs_steps() {
+        let home = temp_home();
+        let existing = home.write("existing.json", "{}");
+        let created = home.path_of("dir/created.toml");
+
+        let mut tx = Transaction::new();
+        tx.write_text(&existing, "old");
+        tx.write_text(&existing, "{\"a\": 1}");
+        tx.write_text(&created, "a = 1\n");
+        let marker = existing.with_file_name("marker");
+        let step_marker = marker.clone();
+        tx.step("写入标记", move || fs::write(&step_marker, "done").map_err(|e| e.to_string()));
+        tx.commit().unwrap();
+
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "{\"a\": 1}");
+        assert_eq!(fs::read_to_string(&created).unwrap(), "a = 1\n");
+        assert_eq!(fs::read_to_string(&marker).unwrap(), "done");
+    }
+
+    #[test]
+    fn failed_step_restores_existing_and_deletes_created_files() {
+        let home = temp_home();
+        let original = "{\r\n  // 用户注释\r\n  \"a\": 1\r\n}";
+        let existing = home.write("settings.json", original);
+        let guarded = home.write("env.sh", "export A=1\n");
+        let created = home.path_of("new/config.toml");
+
+        let mut tx = Transaction::new();
+        tx.write_text(&existing, "{}");
+        tx.write_text(&created, "a = 1\n");
+        tx.guard(&guarded);
+        let step_path = guarded.clone();
+        tx.step("修改环境变量文件", move || fs::write(&step_path, "export A=2\n").map_err(|e| e.to_string()));
+        tx.step("设置环境变量", || Err("模拟失败".to_string()));
+
+        let err = tx.commit().unwrap_err();
+        assert!(err.starts_with("设置环境变量失败: 模拟失败"), "{}", err);
+        assert!(err.ends_with("（已回滚全部改动）"), "{}", err);
+        assert_eq!(fs::read(&existing).unwrap(), original.as_bytes());
+        assert_eq!(fs::read_to_string(&guarded).unwrap(), "export A=1\n");
+        assert!(!created.exists());
+    }
+}
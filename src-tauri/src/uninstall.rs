@@ -0,0 +1,284 @@
+use crate::backup;
+use crate::claude_config::{self, PermissionRules};
+use crate::codex_config;
+use crate::config::{
+    get_claude_config_dir, get_claude_settings_path, get_codex_config_dir, get_gemini_env_path, get_gemini_settings_path, get_project_claude_settings_path,
+    get_project_codex_dir, get_project_gemini_dir, get_shell_env_dir,
+};
+use crate::env_manager;
+use crate::gemini_config;
+use crate::profiles::{self, Tool};
+use crate::transaction::Transaction;
+use crate::vscode;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 撤销配置的结果（用于前端显示）
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UnconfigureReport {
+    /// 从首次写入前的快照恢复的文件
+    pub restored: Vec<String>,
+    /// 删除了 XG-switch 写入内容的文件
+    pub cleaned: Vec<String>,
+    /// 清理后已没有其他内容而删除的文件
+    pub deleted: Vec<String>,
+    /// 删除的环境变量
+    pub env_vars: Vec<String>,
+    /// 无法处理而跳过的文件及原因
+    pub skipped: Vec<String>,
+}
+
+impl UnconfigureReport {
+    fn merge(&mut self, other: UnconfigureReport) {
+        self.restored.extend(other.restored);
+        self.cleaned.extend(other.cleaned);
+        self.deleted.extend(other.deleted);
+        self.env_vars.extend(other.env_vars);
+        self.skipped.extend(other.skipped);
+    }
+}
+
+/// 从文件内容中删除 XG-switch 写入的部分，返回 None 表示删除文件
+type Cleaner = Box<dyn Fn(&str) -> Result<Option<String>, String>>;
+
+/// XG-switch 可能修改过的文件及其清理方式
+struct Target {
+    path: PathBuf,
+    clean: Cleaner,
+    /// 配置库中记录了 XG-switch 对该文件的写入（托管的键、项目级配置）
+    recorded: bool,
+}
+
+impl Target {
+    fn new(path: PathBuf, clean: impl Fn(&str) -> Result<Option<String>, String> + 'static) -> Self {
+        Self {
+            path,
+            clean: Box::new(clean),
+            recorded: false,
+        }
+    }
+
+    fn recorded(mut self, recorded: bool) -> Self {
+        self.recorded = recorded;
+        self
+    }
+}
+
+fn claude_settings(path: PathBuf, owned_keys: &[String], owned_rules: &PermissionRules) -> Target {
+    let recorded = !owned_keys.is_empty() || !owned_rules.is_empty();
+    let owned_keys = owned_keys.to_vec();
+    let owned_rules = owned_rules.clone();
+    Target::new(path, move |content| {
+        claude_config::remove_managed_settings(content, &owned_keys, &owned_rules)
+    })
+    .recorded(recorded)
+}
+
+fn codex_files(dir: &Path, env_keys: &[String], recorded: bool) -> Vec<Target> {
+    let env_keys = env_keys.to_vec();
+    vec![
+        Target::new(dir.join("config.toml"), move |content| {
+            codex_config::remove_managed_config_toml(content, &env_keys).map(Some)
+        })
+        .recorded(recorded),
+        Target::new(dir.join("auth.json"), codex_config::remove_managed_auth).recorded(recorded),
+    ]
+}
+
+fn gemini_files(settings_path: PathBuf, env_path: PathBuf, recorded: bool) -> Vec<Target> {
+    vec![
+        Target::new(settings_path, gemini_config::remove_managed_settings).recorded(recorded),
+        Target::new(env_path, |content| Ok(gemini_config::remove_managed_env(content))).recorded(recorded),
+    ]
+}
+
+/// 工具的全局配置、VSCode 扩展配置和项目级配置
+fn targets(tool: Tool, env_keys: &[String]) -> Result<Vec<Target>, String> {
+    let store = profiles::list_profiles()?;
+    let projects = store.projects.iter().filter(|p| p.tool == tool);
+
+    let mut targets = Vec::new();
+    match tool {
+        Tool::Claude => {
+            targets.push(claude_settings(
+                get_claude_settings_path(),
+                &store.claude_env_keys,
+                &store.claude_permission_rules,
+            ));
+            targets.push(Target::new(
+                get_claude_config_dir().join("config.json"),
+                vscode::remove_claude_api_key,
+            ));
+            for path in vscode::all_settings_paths() {
+                targets.push(Target::new(path, |content| vscode::remove_claude_settings(content).map(Some)));
+            }
+            for project in projects {
+                targets.push(
+                    claude_settings(
+                        get_project_claude_settings_path(Path::new(&project.path)),
+                        &project.env_keys,
+                        &project.permission_rules,
+                    )
+                    .recorded(true),
+                );
+            }
+        }
+        Tool::Codex => {
+            targets.extend(codex_files(&get_codex_config_dir(), env_keys, !store.managed_env_keys.is_empty()));
+            for path in vscode::all_settings_paths() {
+                targets.push(Target::new(path, |content| vscode::remove_codex_settings(content).map(Some)));
+            }
+            for project in projects {
+                targets.extend(codex_files(&get_project_codex_dir(Path::new(&project.path)), env_keys, true));
+            }
+        }
+        Tool::Gemini => {
+            targets.extend(gemini_files(get_gemini_settings_path(), get_gemini_env_path(), false));
+            for project in projects {
+                let dir = get_project_gemini_dir(Path::new(&project.path));
+                targets.extend(gemini_files(dir.join("settings.json"), dir.join(".env"), true));
+            }
+        }
+    }
+    Ok(targets)
+}
+
+/// 撤销 XG-switch 对某个工具所做的修改：
+/// restore 为 true 时把文件恢复为第一次被修改前的快照（没有快照的文件仍按键清理），
+/// 否则只删除 XG-switch 写入的键，用户自己的设置保持不变。
+/// 只处理 XG-switch 写入过的文件：有首次修改前的快照、配置库记录了写入，或该工具有正在使用的配置
+pub fn unconfigure_tool(tool: Tool, restore: bool) -> Result<UnconfigureReport, String> {
+    let store = profiles::list_profiles()?;
+    let mut env_keys = store.managed_env_keys.clone();
+    if tool == Tool::Codex && !env_keys.iter().any(|k| k == env_manager::DEFAULT_ENV_KEY) {
+        env_keys.push(env_manager::DEFAULT_ENV_KEY.to_string());
+    }
+
+    let active = store.active.get(tool).is_some();
+    let mut report = UnconfigureReport::default();
+    let mut tx = Transaction::new();
+
+    for target in targets(tool, &env_keys)? {
+        let path = target.path;
+        let display = path.to_string_lossy().to_string();
+        let original = backup::original_snapshot(&path)?;
+        if original.is_none() && !target.recorded && !active {
+            continue;
+        }
+
+        if restore {
+            if let Some(snapshot) = original {
+                tx.guard(&path);
+                tx.step(format!("恢复 {}", display), move || {
+                    backup::restore_snapshot(&snapshot.id).map(|_| ())
+                });
+                report.restored.push(display);
+                continue;
+            }
+        }
+
+        if !path.exists() {
+            continue;
+        }
+        let content = fs::read_to_string(&path).map_err(|e| format!("读取 {} 失败: {}", display, e))?;
+        match (target.clean)(&content) {
+            Ok(Some(cleaned)) if cleaned == content => {}
+            Ok(Some(cleaned)) => {
+                tx.write_text(&path, &cleaned);
+                report.cleaned.push(display);
+            }
+            Ok(None) => {
+                tx.guard(&path);
+                let remove_path = path.clone();
+                tx.step(format!("删除 {}", display), move || {
+                    backup::snapshot_before_write(&remove_path)?;
+                    fs::remove_file(&remove_path).map_err(|e| format!("删除文件失败: {}", e))
+                });
+                report.deleted.push(display);
+            }
+            Err(e) => {
+                log::warn!("跳过 {}: {}", display, e);
+                report.skipped.push(format!("{}: {}", display, e));
+            }
+        }
+    }
+
+    tx.commit()?;
+
+    // Codex 读取密钥的环境变量
+    if tool == Tool::Codex {
+        for key in &env_keys {
+            match env_manager::remove_env(key) {
+                // key88 可能是旧版本设置的，没有记录在配置库中
+                Ok(()) if store.managed_env_keys.contains(key) => report.env_vars.push(key.clone()),
+                Ok(()) => {}
+                Err(e) => log::warn!("删除环境变量 {} 失败: {}", key, e),
+            }
+        }
+        profiles::remove_managed_env_keys(&env_keys)?;
+    }
+
+    profiles::forget_tool(tool)?;
+    log::info!("已撤销 {} 的配置: {:?}", tool.as_str(), report);
+    Ok(report)
+}
+
+/// 撤销 XG-switch 对所有工具所做的修改，并移除 shell 集成与环境变量文件；配置库本身保留
+pub fn reset_all(restore: bool) -> Result<UnconfigureReport, String> {
+    let mut report = UnconfigureReport::default();
+    for tool in Tool::ALL {
+        report.merge(unconfigure_tool(tool, restore)?);
+    }
+
+    if !cfg!(windows) {
+        report.cleaned.extend(env_manager::uninstall_shell_integration()?);
+        if env_manager::remove_env_files()? {
+            report.deleted.push(get_shell_env_dir().to_string_lossy().to_string());
+        }
+    }
+
+    log::info!("已撤销 XG-switch 的全部修改");
+    Ok(report)
+}
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use super::*;
+    use crate::config::atomic_write;
+    use crate::test_support::temp_home;
+
+    #[test]
+    fn leaves_files_never_configured_untouched() {
+        let home = temp_home();
+        let files = [
+            home.write(".codex/auth.json", "{\n  \"OPENAI_API_KEY\": \"sk-user\"\n}"),
+            home.write(".gemini/.env", "GEMINI_API_KEY=\"user-key\"\n"),
+            home.write(".claude/config.json", "{\"primaryApiKey\": \"sk-ant-user\"}"),
+            home.write(
+                ".claude/settings.json",
+                "{\"env\": {\"ANTHROPIC_BASE_URL\": \"https://mine\", \"ANTHROPIC_AUTH_TOKEN\": \"t\"}}",
+            ),
+            home.write(".config/Code/User/settings.json", "{\"chatgpt.apiBase\": \"https://mine\"}"),
+        ];
+        let before: Vec<Vec<u8>> = files.iter().map(|f| fs::read(f).unwrap()).collect();
+
+        let report = reset_all(false).unwrap();
+        assert!(report.cleaned.is_empty() && report.deleted.is_empty() && report.restored.is_empty());
+        for (file, content) in files.iter().zip(before) {
+            assert_eq!(fs::read(file).unwrap(), content, "{} 被修改", file.display());
+        }
+    }
+
+    #[test]
+    fn cleans_files_written_by_xg_switch() {
+        let home = temp_home();
+        let auth = home.write(".codex/auth.json", "{\"tokens\": {}}");
+        atomic_write(&auth, b"{\"tokens\": {}, \"OPENAI_API_KEY\": \"sk-relay\"}").unwrap();
+
+        let report = unconfigure_tool(Tool::Codex, false).unwrap();
+        assert_eq!(report.cleaned, [auth.to_string_lossy().to_string()]);
+        let auth: serde_json::Value = serde_json::from_slice(&fs::read(&auth).unwrap()).unwrap();
+        assert_eq!(auth, serde_json::json!({"tokens": {}}));
+    }
+}
@@ -0,0 +1,394 @@
+use crate::config::{get_vault_path, read_json_file};
+#[cfg(feature = "gui")]
+use crate::config::write_json_file;
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+#[cfg(feature = "gui")]
+use chacha20poly1305::aead::rand_core::RngCore;
+#[cfg(feature = "gui")]
+use chacha20poly1305::aead::OsRng;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+/// 密钥库文件格式版本
+const VAULT_VERSION: u32 = 1;
+
+/// 密钥引用前缀，例如 `vault:claude-main`
+pub const SECRET_REF_PREFIX: &str = "vault:";
+
+/// 用于校验口令的固定明文
+const VERIFIER_PLAINTEXT: &[u8] = b"xg-switch-vault";
+
+/// 默认自动锁定时间（秒）
+const DEFAULT_AUTO_LOCK_SECS: u64 = 15 * 60;
+
+/// Argon2id 参数，随文件保存以便日后调整
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KdfParams {
+    salt: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+/// 一段加密数据（nonce 与密文均为 base64）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EncryptedBlob {
+    nonce: String,
+    ciphertext: String,
+}
+
+/// 磁盘上的密钥库（vault.json）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct VaultFile {
+    version: u32,
+    kdf: KdfParams,
+    verifier: EncryptedBlob,
+    #[serde(default = "default_auto_lock_secs")]
+    auto_lock_secs: u64,
+    #[serde(default)]
+    secrets: BTreeMap<String, EncryptedBlob>,
+}
+
+fn default_auto_lock_secs() -> u64 {
+    DEFAULT_AUTO_LOCK_SECS
+}
+
+/// 解锁后保存在内存中的派生密钥
+struct UnlockedVault {
+    key: Zeroizing<[u8; 32]>,
+    last_used: Instant,
+    auto_lock: Duration,
+}
+
+static VAULT: Mutex<Option<UnlockedVault>> = Mutex::new(None);
+
+/// 密钥库状态（用于前端显示）
+#[cfg(feature = "gui")]
+#[derive(Debug, Clone, Serialize)]
+pub struct VaultStatus {
+    pub initialized: bool,
+    pub unlocked: bool,
+    pub auto_lock_secs: u64,
+    pub secrets: Vec<String>,
+}
+
+fn derive_key(passphrase: &str, kdf: &KdfParams) -> Result<Zeroizing<[u8; 32]>, String> {
+    let salt = BASE64
+        .decode(&kdf.salt)
+        .map_err(|e| format!("密钥库 salt 格式错误: {}", e))?;
+    let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(32))
+        .map_err(|e| format!("密钥库 KDF 参数错误: {}", e))?;
+
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), &salt, key.as_mut())
+        .map_err(|e| format!("派生密钥失败: {}", e))?;
+    Ok(key)
+}
+
+#[cfg(feature = "gui")]
+fn encrypt(key: &[u8; 32], plaintext: &[u8]) -> Result<EncryptedBlob, String> {
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let mut nonce = [0u8; 12];
+    OsRng.fill_bytes(&mut nonce);
+
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| "加密失败".to_string())?;
+
+    Ok(EncryptedBlob {
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    })
+}
+
+fn decrypt(key: &[u8; 32], blob: &EncryptedBlob) -> Result<Zeroizing<Vec<u8>>, String> {
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let nonce = BASE64
+        .decode(&blob.nonce)
+        .map_err(|e| format!("nonce 格式错误: {}", e))?;
+    if nonce.len() != 12 {
+        return Err("nonce 长度错误".to_string());
+    }
+    let ciphertext = BASE64
+        .decode(&blob.ciphertext)
+        .map_err(|e| format!("密文格式错误: {}", e))?;
+
+    cipher
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map(Zeroizing::new)
+        .map_err(|_| "解密失败".to_string())
+}
+
+fn load_vault_file() -> Result<Option<VaultFile>, String> {
+    let path = get_vault_path();
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let file: VaultFile = read_json_file(&path)?;
+    if file.version > VAULT_VERSION {
+        return Err(format!(
+            "密钥库版本 ({}) 高于当前程序支持的版本 ({})",
+            file.version, VAULT_VERSION
+        ));
+    }
+    Ok(Some(file))
+}
+
+fn require_vault_file() -> Result<VaultFile, String> {
+    load_vault_file()?.ok_or_else(|| "密钥库尚未初始化".to_string())
+}
+
+/// 空闲超过自动锁定时间时清除内存中的密钥
+fn expire_idle(vault: &mut Option<UnlockedVault>) {
+    if vault.as_ref().is_some_and(|v| v.last_used.elapsed() >= v.auto_lock) {
+        log::info!("密钥库空闲超时，已自动锁定");
+        *vault = None;
+    }
+}
+
+/// 在已解锁状态下取出密钥，超时则自动锁定
+fn with_key<R>(f: impl FnOnce(&[u8; 32]) -> Result<R, String>) -> Result<R, String> {
+    let mut guard = VAULT.lock().unwrap_or_else(|e| e.into_inner());
+    expire_idle(&mut guard);
+
+    let vault = guard
+        .as_mut()
+        .ok_or_else(|| "密钥库已锁定，请先解锁".to_string())?;
+    vault.last_used = Instant::now();
+    f(&vault.key)
+}
+
+/// 密钥库是否处于解锁状态；只查询状态，不刷新空闲计时
+#[cfg(feature = "gui")]
+fn is_unlocked() -> bool {
+    let mut guard = VAULT.lock().unwrap_or_else(|e| e.into_inner());
+    expire_idle(&mut guard);
+    guard.is_some()
+}
+
+/// 初始化密钥库（仅在不存在时）
+#[cfg(feature = "gui")]
+pub fn init_vault(passphrase: &str) -> Result<(), String> {
+    if passphrase.is_empty() {
+        return Err("口令不能为空".to_string());
+    }
+    if load_vault_file()?.is_some() {
+        return Err("密钥库已存在".to_string());
+    }
+
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let defaults = Params::default();
+    let kdf = KdfParams {
+        salt: BASE64.encode(salt),
+        m_cost: defaults.m_cost(),
+        t_cost: defaults.t_cost(),
+        p_cost: defaults.p_cost(),
+    };
+
+    let key = derive_key(passphrase, &kdf)?;
+    let file = VaultFile {
+        version: VAULT_VERSION,
+        verifier: encrypt(&key, VERIFIER_PLAINTEXT)?,
+        kdf,
+        auto_lock_secs: DEFAULT_AUTO_LOCK_SECS,
+        secrets: BTreeMap::new(),
+    };
+    write_json_file(&get_vault_path(), &file)?;
+
+    *VAULT.lock().unwrap_or_else(|e| e.into_inner()) = Some(UnlockedVault {
+        key,
+        last_used: Instant::now(),
+        auto_lock: Duration::from_secs(file.auto_lock_secs),
+    });
+
+    log::info!("密钥库已创建: {:?}", get_vault_path());
+    Ok(())
+}
+
+/// 使用口令解锁密钥库
+pub fn unlock_vault(passphrase: &str) -> Result<(), String> {
+    let file = require_vault_file()?;
+    let key = derive_key(passphrase, &file.kdf)?;
+
+    let verifier = decrypt(&key, &file.verifier).map_err(|_| "口令错误".to_string())?;
+    if verifier.as_slice() != VERIFIER_PLAINTEXT {
+        return Err("口令错误".to_string());
+    }
+
+    *VAULT.lock().unwrap_or_else(|e| e.into_inner()) = Some(UnlockedVault {
+        key,
+        last_used: Instant::now(),
+        auto_lock: Duration::from_secs(file.auto_lock_secs),
+    });
+
+    log::info!("密钥库已解锁");
+    Ok(())
+}
+
+/// 锁定密钥库，清除内存中的密钥
+#[cfg(feature = "gui")]
+pub fn lock_vault() {
+    *VAULT.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    log::info!("密钥库已锁定");
+}
+
+/// 设置自动锁定时间（秒）
+#[cfg(feature = "gui")]
+pub fn set_auto_lock(secs: u64) -> Result<(), String> {
+    if secs == 0 {
+        return Err("自动锁定时间必须大于 0".to_string());
+    }
+
+    let mut file = require_vault_file()?;
+    file.auto_lock_secs = secs;
+    write_json_file(&get_vault_path(), &file)?;
+
+    if let Some(vault) = VAULT.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        vault.auto_lock = Duration::from_secs(secs);
+    }
+    Ok(())
+}
+
+/// 获取密钥库状态
+#[cfg(feature = "gui")]
+pub fn vault_status() -> Result<VaultStatus, String> {
+    let file = load_vault_file()?;
+    let unlocked = is_unlocked();
+
+    Ok(match file {
+        Some(file) => VaultStatus {
+            initialized: true,
+            unlocked,
+            auto_lock_secs: file.auto_lock_secs,
+            secrets: file.secrets.keys().cloned().collect(),
+        },
+        None => VaultStatus {
+            initialized: false,
+            unlocked: false,
+            auto_lock_secs: DEFAULT_AUTO_LOCK_SECS,
+            secrets: Vec::new(),
+        },
+    })
+}
+
+/// 存储（或覆盖）一个密钥，返回可用于配置的引用
+#[cfg(feature = "gui")]
+pub fn store_secret(name: &str, value: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("密钥名称不能为空".to_string());
+    }
+
+    let mut file = require_vault_file()?;
+    let blob = with_key(|key| encrypt(key, value.as_bytes()))?;
+    file.secrets.insert(name.to_string(), blob);
+    write_json_file(&get_vault_path(), &file)?;
+
+    Ok(secret_ref(name))
+}
+
+/// 删除一个密钥
+#[cfg(feature = "gui")]
+pub fn delete_secret(name: &str) -> Result<(), String> {
+    let mut file = require_vault_file()?;
+    if file.secrets.remove(name).is_none() {
+        return Err(format!("密钥不存在: {}", name));
+    }
+    write_json_file(&get_vault_path(), &file)
+}
+
+/// 读取一个密钥的明文
+pub fn get_secret(name: &str) -> Result<String, String> {
+    let file = require_vault_file()?;
+    let blob = file
+        .secrets
+        .get(name)
+        .ok_or_else(|| format!("密钥不存在: {}", name))?;
+
+    let plaintext = with_key(|key| decrypt(key, blob))?;
+    String::from_utf8(plaintext.to_vec()).map_err(|_| "密钥内容不是有效的 UTF-8".to_string())
+}
+
+/// 生成密钥引用字符串
+#[cfg(feature = "gui")]
+pub fn secret_ref(name: &str) -> String {
+    format!("{}{}", SECRET_REF_PREFIX, name)
+}
+
+/// 解析 API 密钥：`vault:<name>` 从密钥库读取，否则原样返回
+pub fn resolve_secret(value: &str) -> Result<String, String> {
+    match value.trim().strip_prefix(SECRET_REF_PREFIX) {
+        Some(name) => get_secret(name.trim()),
+        None => Ok(value.to_string()),
+    }
+}
+
+#[cfg(all(test, feature = "gui"))]
+mod tests {
+    use super::*;
+
+    fn test_kdf() -> KdfParams {
+        KdfParams {
+            salt: BASE64.encode([7u8; 16]),
+            m_cost: 256,
+            t_cost: 1,
+            p_cost: 1,
+        }
+    }
+
+    #[test]
+    fn encrypt_decrypt_round_trip() {
+        let key = derive_key("correct horse", &test_kdf()).unwrap();
+        let blob = encrypt(&key, b"sk-ant-secret").unwrap();
+        assert_ne!(blob.ciphertext, BASE64.encode(b"sk-ant-secret"));
+        assert_eq!(decrypt(&key, &blob).unwrap().as_slice(), b"sk-ant-secret");
+
+        // 每次加密使用新的 nonce
+        let again = encrypt(&key, b"sk-ant-secret").unwrap();
+        assert_ne!(blob.nonce, again.nonce);
+    }
+
+    #[test]
+    fn decrypt_rejects_wrong_passphrase_and_tampering() {
+        let key = derive_key("correct horse", &test_kdf()).unwrap();
+        let blob = encrypt(&key, VERIFIER_PLAINTEXT).unwrap();
+
+        let wrong = derive_key("battery staple", &test_kdf()).unwrap();
+        assert!(decrypt(&wrong, &blob).is_err());
+
+        let mut ciphertext = BASE64.decode(&blob.ciphertext).unwrap();
+        ciphertext[0] ^= 1;
+        let tampered = EncryptedBlob {
+            nonce: blob.nonce.clone(),
+            ciphertext: BASE64.encode(ciphertext),
+        };
+        assert!(decrypt(&key, &tampered).is_err());
+    }
+
+    #[test]
+    fn status_check_does_not_extend_auto_lock() {
+        let key = derive_key("correct horse", &test_kdf()).unwrap();
+        let last_used = Instant::now() - Duration::from_secs(5);
+        *VAULT.lock().unwrap() = Some(UnlockedVault {
+            key,
+            last_used,
+            auto_lock: Duration::from_secs(60),
+        });
+        assert!(is_unlocked());
+        assert_eq!(VAULT.lock().unwrap().as_ref().unwrap().last_used, last_used);
+
+        VAULT.lock().unwrap().as_mut().unwrap().auto_lock = Duration::from_secs(1);
+        assert!(!is_unlocked());
+        assert!(VAULT.lock().unwrap().is_none());
+    }
+}
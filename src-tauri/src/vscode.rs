@@ -1,131 +1,150 @@
-use std::path::PathBuf;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
 use serde_json::{json, Value};
 use std::fs;
-use crate::config::{get_claude_config_dir, get_claude_settings_path, write_json_file};
-
-/// 移除 JSON/JSONC 中的注释（简单实现）
-/// 处理单行注释 // 和多行注释 /* */
-fn strip_json_comments(content: &str) -> String {
-    let mut result = String::new();
-    let mut chars = content.chars().peekable();
-    let mut in_string = false;
-    let mut escape_next = false;
-
-    while let Some(ch) = chars.next() {
-        if escape_next {
-            result.push(ch);
-            escape_next = false;
-            continue;
-        }
+use crate::config::{get_claude_config_dir, write_private_json_file};
+use crate::jsonc;
+use crate::profiles::Tool;
+
+/// VS Code 系编辑器：(ID, 显示名称, 配置目录名称)，按优先级排序
+const EDITOR_PRODUCTS: [(&str, &str, &str); 8] = [
+    ("code", "VS Code", "Code"),
+    ("code-insiders", "VS Code Insiders", "Code - Insiders"),
+    ("vscodium", "VSCodium", "VSCodium"),
+    ("code-oss", "Code - OSS", "Code - OSS"),
+    ("cursor", "Cursor", "Cursor"),
+    ("windsurf", "Windsurf", "Windsurf"),
+    ("trae", "Trae", "Trae"),
+    ("positron", "Positron", "Positron"),
+];
+
+/// Flatpak 应用 ID 与对应的编辑器 ID
+#[cfg(all(unix, not(target_os = "macos")))]
+const FLATPAK_APPS: [(&str, &str); 3] = [
+    ("com.visualstudio.code", "code"),
+    ("com.vscodium.codium", "vscodium"),
+    ("com.visualstudio.code-oss", "code-oss"),
+];
+
+/// 远程开发时 VS Code Server 的数据目录（位于用户主目录下）与对应的编辑器 ID
+#[cfg(unix)]
+const SERVER_DIRS: [(&str, &str); 5] = [
+    (".vscode-server", "code"),
+    (".vscode-server-insiders", "code-insiders"),
+    (".vscodium-server", "vscodium"),
+    (".cursor-server", "cursor"),
+    (".windsurf-server", "windsurf"),
+];
+
+/// 编辑器的安装方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstallKind {
+    Native,
+    Flatpak,
+    /// 远程主机上的 VS Code Server，写入机器级设置 data/Machine/settings.json
+    Server,
+}
 
-        if ch == '\\' && in_string {
-            result.push(ch);
-            escape_next = true;
-            continue;
-        }
+/// 一个编辑器安装及其用户 settings.json
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditorInstall {
+    /// 如 "cursor"、"code-flatpak"
+    pub id: String,
+    pub name: String,
+    pub kind: InstallKind,
+    /// 编辑器的配置目录，存在即视为已安装
+    pub config_dir: String,
+    pub settings_path: String,
+    pub settings_exists: bool,
+}
 
-        if ch == '"' {
-            in_string = !in_string;
-            result.push(ch);
-            continue;
-        }
+impl EditorInstall {
+    fn new(product: &str, kind: InstallKind, config_dir: PathBuf) -> Option<Self> {
+        let (id, name, _) = EDITOR_PRODUCTS.iter().find(|(id, _, _)| *id == product)?;
+        let (id, name) = match kind {
+            InstallKind::Native => (id.to_string(), name.to_string()),
+            InstallKind::Flatpak => (format!("{}-flatpak", id), format!("{} (Flatpak)", name)),
+            InstallKind::Server => (format!("{}-server", id), format!("{} Server", name)),
+        };
+        let settings_path = match kind {
+            InstallKind::Server => config_dir.join("data").join("Machine").join("settings.json"),
+            _ => config_dir.join("User").join("settings.json"),
+        };
+        Some(Self {
+            id,
+            name,
+            kind,
+            config_dir: config_dir.to_string_lossy().to_string(),
+            settings_exists: settings_path.is_file(),
+            settings_path: settings_path.to_string_lossy().to_string(),
+        })
+    }
+}
 
-        if in_string {
-            result.push(ch);
-            continue;
+/// 所有可能的编辑器安装位置（不论是否存在）
+fn editor_locations() -> Vec<EditorInstall> {
+    let mut editors = Vec::new();
+
+    // macOS: ~/Library/Application Support/<产品>
+    // Windows: %APPDATA%\<产品>
+    // Linux: ~/.config/<产品>
+    if let Some(config) = dirs::config_dir() {
+        for (id, _, dir) in EDITOR_PRODUCTS {
+            editors.extend(EditorInstall::new(id, InstallKind::Native, config.join(dir)));
         }
+    }
 
-        // 处理注释（仅在非字符串中）
-        if ch == '/' {
-            if let Some(&next_ch) = chars.peek() {
-                if next_ch == '/' {
-                    // 单行注释，跳到行尾
-                    chars.next(); // 消费第二个 /
-                    for c in chars.by_ref() {
-                        if c == '\n' {
-                            result.push('\n');
-                            break;
-                        }
-                    }
-                    continue;
-                } else if next_ch == '*' {
-                    // 多行注释，跳到 */
-                    chars.next(); // 消费 *
-                    let mut found_end = false;
-                    while let Some(c) = chars.next() {
-                        if c == '*' {
-                            if let Some(&n) = chars.peek() {
-                                if n == '/' {
-                                    chars.next(); // 消费 /
-                                    found_end = true;
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                    if found_end {
-                        result.push(' '); // 用空格替代注释
-                    }
-                    continue;
-                }
+    #[cfg(all(unix, not(target_os = "macos")))]
+    if let Some(home) = dirs::home_dir() {
+        // Flatpak: ~/.var/app/<应用 ID>/config/<产品>
+        for (app, product) in FLATPAK_APPS {
+            for (id, _, dir) in EDITOR_PRODUCTS.iter().filter(|(id, _, _)| *id == product) {
+                let config_dir = home.join(".var/app").join(app).join("config").join(dir);
+                editors.extend(EditorInstall::new(id, InstallKind::Flatpak, config_dir));
             }
         }
+        // Snap 版 VS Code/VSCodium 是 classic 约束，直接使用 ~/.config/<产品>，已按原生安装处理
+    }
 
-        result.push(ch);
+    // VS Code Server: ~/.vscode-server/data/Machine/settings.json
+    #[cfg(unix)]
+    if let Some(home) = dirs::home_dir() {
+        for (dir, product) in SERVER_DIRS {
+            editors.extend(EditorInstall::new(product, InstallKind::Server, home.join(dir)));
+        }
     }
 
-    result
+    editors
 }
 
-/// 枚举可能的 VS Code 发行版配置目录名称
-fn vscode_product_dirs() -> Vec<&'static str> {
-    vec![
-        "Code",            // VS Code Stable
-        "Code - Insiders", // VS Code Insiders
-        "VSCodium",        // VSCodium
-        "Code - OSS",      // OSS 发行版
-    ]
+/// 列出本机安装的 VS Code 系编辑器（配置目录存在的）
+pub fn discover_editors() -> Vec<EditorInstall> {
+    editor_locations()
+        .into_iter()
+        .filter(|e| Path::new(&e.config_dir).is_dir())
+        .collect()
 }
 
-/// 获取 VS Code 用户 settings.json 的候选路径列表（按优先级排序）
+/// 获取 VS Code 系编辑器用户 settings.json 的候选路径列表（按优先级排序）
 pub fn candidate_settings_paths() -> Vec<PathBuf> {
-    let mut paths = Vec::new();
-
-    #[cfg(target_os = "macos")]
-    {
-        if let Some(home) = dirs::home_dir() {
-            for prod in vscode_product_dirs() {
-                paths.push(
-                    home.join("Library")
-                        .join("Application Support")
-                        .join(prod)
-                        .join("User")
-                        .join("settings.json"),
-                );
-            }
-        }
-    }
-
-    #[cfg(target_os = "windows")]
-    {
-        // Windows: %APPDATA%\Code\User\settings.json
-        if let Some(roaming) = dirs::config_dir() {
-            for prod in vscode_product_dirs() {
-                paths.push(roaming.join(prod).join("User").join("settings.json"));
-            }
-        }
-    }
+    editor_locations()
+        .into_iter()
+        .map(|e| PathBuf::from(e.settings_path))
+        .collect()
+}
 
-    #[cfg(all(unix, not(target_os = "macos")))]
-    {
-        // Linux: ~/.config/Code/User/settings.json
-        if let Some(config) = dirs::config_dir() {
-            for prod in vscode_product_dirs() {
-                paths.push(config.join(prod).join("User").join("settings.json"));
-            }
+/// 候选路径加上各编辑器 profile 的 settings.json（撤销配置时逐个清理）
+pub fn all_settings_paths() -> Vec<PathBuf> {
+    let mut paths = candidate_settings_paths();
+    for profile in discover_profiles() {
+        let path = PathBuf::from(profile.settings_path);
+        if !paths.contains(&path) {
+            paths.push(path);
         }
     }
-
     paths
 }
 
@@ -141,114 +160,585 @@ pub fn find_existing_settings() -> Option<PathBuf> {
     None
 }
 
+/// 在 ~/.claude/config.json 中写入 primaryApiKey，保留其他字段；文件无法解析时不做修改。
+/// 文件包含密钥，Unix 下权限为 0600
+fn write_claude_api_key(api_key: &str) -> Result<PathBuf, String> {
+    let config_path = get_claude_config_dir().join("config.json");
+    let mut config: serde_json::Map<String, Value> = if config_path.exists() {
+        let content = fs::read_to_string(&config_path).map_err(|e| format!("读取 config.json 失败: {}", e))?;
+        if content.trim().is_empty() {
+            serde_json::Map::new()
+        } else {
+            serde_json::from_str(&content)
+                .map_err(|e| format!("解析 {} 失败，未做修改: {}", config_path.display(), e))?
+        }
+    } else {
+        serde_json::Map::new()
+    };
+    config.insert("primaryApiKey".to_string(), Value::String(api_key.to_string()));
+    write_private_json_file(&config_path, &config)?;
+    Ok(config_path)
+}
+
 /// 配置 VSCode Claude 扩展
-/// 功能：在 ~/.claude/config.json 中写入 {"primaryApiKey": "key"}
-pub fn configure_vscode_claude(api_key: String, _base_url: String) -> Result<String, String> {
-    // 1. 检查客户端配置是否存在
-    let settings_path = get_claude_settings_path();
-    if !settings_path.exists() {
-        return Err("请先配置 Claude Code 客户端！需要先完成客户端配置才能配置 VSCode。".to_string());
+/// 功能：在 ~/.claude/config.json 中写入 primaryApiKey，并在编辑器 settings.json 的
+/// claudeCode.environmentVariables 中写入 ANTHROPIC_BASE_URL / ANTHROPIC_AUTH_TOKEN
+pub fn configure_vscode_claude(api_key: String, base_url: String) -> Result<String, String> {
+    // 如果 base_url 为空，使用 Claude 默认值
+    let base_url = crate::probe::base_url_or_default(Tool::Claude, &base_url);
+
+    let config_path = write_claude_api_key(&api_key)?;
+    let settings_path = default_settings_path()?;
+    write_claude_settings(&settings_path, &base_url, &api_key)?;
+
+    Ok(format!(
+        "VSCode 配置成功！已写入: {}\n已配置 Claude Code 扩展使用: {}（{}）\n请重新加载 VSCode 窗口以使配置生效。",
+        config_path.display(),
+        base_url,
+        settings_path.display()
+    ))
+}
+
+/// 未指定编辑器时写入的 settings.json：第一个已存在的，其次是已安装的编辑器（远程主机上通常只有
+/// VS Code Server），否则使用第一个候选路径（通常是 Code Stable）
+fn default_settings_path() -> Result<PathBuf, String> {
+    if let Some(path) = find_existing_settings() {
+        return Ok(path);
     }
+    let path = match discover_editors().into_iter().next() {
+        Some(editor) => PathBuf::from(editor.settings_path),
+        None => candidate_settings_paths()
+            .into_iter()
+            .next()
+            .ok_or_else(|| "无法确定 VSCode 配置目录路径。".to_string())?,
+    };
 
-    // 2. 获取 ~/.claude/config.json 路径
-    let config_dir = get_claude_config_dir();
-    let config_path = config_dir.join("config.json");
+    // 确保父目录存在
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("创建 VSCode 配置目录失败: {}", e))?;
+    }
 
-    // 3. 创建配置内容
-    let config_content = json!({
-        "primaryApiKey": api_key
-    });
+    log::info!("VSCode settings.json 不存在，将创建新文件: {:?}", path);
+    Ok(path)
+}
+
+/// 配置 VSCode Codex 扩展（配置 ChatGPT 扩展）
+/// 功能：在 VSCode settings.json 中写入 ChatGPT 扩展配置；API Key 通过环境变量 env_key 传递，不写入 settings.json
+pub fn configure_vscode_codex(base_url: String, env_key: &str) -> Result<String, String> {
+    let settings_path = default_settings_path()?;
+    write_codex_settings(&settings_path, &base_url)?;
 
-    // 4. 写入配置文件
-    write_json_file(&config_path, &config_content)?;
+    log::info!("已配置 ChatGPT 扩展使用自定义服务: {}, 密钥从环境变量 {} 读取", base_url, env_key);
 
     Ok(format!(
-        "VSCode 配置成功！已写入: {}\n请重新加载 VSCode 窗口以使配置生效。",
-        config_path.display()
+        "VSCode 配置成功！路径: {}\n已配置 ChatGPT 扩展使用自定义服务: {}\n请重新加载 VSCode 窗口以使配置生效。",
+        settings_path.display(),
+        base_url
     ))
 }
 
-/// 配置 VSCode Codex 扩展（配置 ChatGPT 扩展）
-/// 功能：在 VSCode settings.json 中写入 ChatGPT 扩展配置
-pub fn configure_vscode_codex(base_url: String, api_key: String) -> Result<String, String> {
-    // 查找或创建 settings.json 路径
-    let settings_path = if let Some(path) = find_existing_settings() {
-        path
+/// 在指定的 settings.json 中写入 ChatGPT 扩展配置
+fn write_codex_settings(settings_path: &Path, base_url: &str) -> Result<(), String> {
+    // 读取现有设置（如果文件存在），JSONC 格式无法解析时不做修改，避免覆盖用户的设置
+    let content = if settings_path.exists() {
+        fs::read_to_string(settings_path).map_err(|e| format!("读取 VSCode 设置失败: {}", e))?
     } else {
-        // 如果找不到现有配置，使用第一个候选路径（通常是 Code Stable）
-        let candidates = candidate_settings_paths();
-        if candidates.is_empty() {
-            return Err("无法确定 VSCode 配置目录路径。".to_string());
-        }
-        let path = candidates[0].clone();
+        String::new()
+    };
+
+    // 只改动 ChatGPT 扩展的配置项，其余内容（包括注释和格式）保持不变
+    let updated = jsonc::set(&content, &["chatgpt.apiBase"], &Value::String(base_url.to_string()))
+        .and_then(|c| jsonc::set(&c, &["chatgpt.config", "preferred_auth_method"], &json!("apikey")))
+        .map_err(|e| format!("解析 {} 失败，未做修改: {}", settings_path.display(), e))?;
 
-        // 确保父目录存在
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)
-                .map_err(|e| format!("创建 VSCode 配置目录失败: {}", e))?;
+    if updated != content {
+        crate::config::atomic_write(settings_path, updated.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// 按 ID 查找已安装的编辑器；ids 为空或包含 "all" 时返回全部已安装的编辑器
+pub fn resolve_editors(ids: &[String]) -> Result<Vec<EditorInstall>, String> {
+    let installed = discover_editors();
+    if installed.is_empty() {
+        return Err("未找到已安装的 VS Code 系编辑器。".to_string());
+    }
+    if ids.is_empty() || ids.iter().any(|id| id == "all") {
+        return Ok(installed);
+    }
+    ids.iter()
+        .map(|id| {
+            installed.iter().find(|e| &e.id == id).cloned().ok_or_else(|| {
+                let ids: Vec<&str> = installed.iter().map(|e| e.id.as_str()).collect();
+                format!("未找到编辑器: {}（已安装: {}）", id, ids.join(", "))
+            })
+        })
+        .collect()
+}
+
+/// 为多个编辑器配置 Claude 或 Codex 扩展；editor_ids 为空或包含 "all" 时配置所有已安装的编辑器。
+/// 某个编辑器失败不影响其他编辑器，有失败时返回包含全部结果的错误
+pub fn configure_editors(tool: Tool, editor_ids: &[String], base_url: String, api_key: String) -> Result<String, String> {
+    let editors = resolve_editors(editor_ids)?;
+    let mut lines = Vec::new();
+    if tool == Tool::Claude {
+        let config_path = write_claude_api_key(&api_key)?;
+        lines.push(format!("✓ ~/.claude/config.json: {}", config_path.display()));
+    }
+
+    let mut failed = false;
+    for editor in &editors {
+        match write_tool_settings(tool, Path::new(&editor.settings_path), &base_url, &api_key) {
+            Ok(()) => lines.push(format!("✓ {}: {}", editor.name, editor.settings_path)),
+            Err(e) => {
+                failed = true;
+                lines.push(format!("✗ {}: {}", editor.name, e));
+            }
         }
+    }
 
-        log::info!("VSCode settings.json 不存在，将创建新文件: {:?}", path);
-        path
-    };
+    let summary = lines.join("\n");
+    if failed {
+        return Err(format!("部分编辑器配置失败：\n{}", summary));
+    }
+    Ok(format!(
+        "编辑器配置成功！已配置 {} 扩展使用自定义服务: {}\n{}\n请重新加载编辑器窗口以使配置生效。",
+        tool.as_str(),
+        base_url,
+        summary
+    ))
+}
+
+/// 默认 profile 的名称（直接使用 User/settings.json）
+pub const DEFAULT_PROFILE_NAME: &str = "Default";
+
+/// Claude Code 扩展读取环境变量的设置项
+const CLAUDE_ENV_SETTING: &str = "claudeCode.environmentVariables";
+
+/// 编辑器中的一个 profile（VS Code 的“配置文件”）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditorProfile {
+    pub editor_id: String,
+    pub editor_name: String,
+    pub name: String,
+    /// User/profiles 下的目录名，默认 profile 为空
+    pub location: String,
+    /// 该 profile 实际使用的 settings.json
+    pub settings_path: String,
+    /// profile 沿用默认 profile 的设置时为 true，此时写入的是 User/settings.json
+    pub shares_default_settings: bool,
+}
+
+fn default_profile(editor: &EditorInstall) -> EditorProfile {
+    EditorProfile {
+        editor_id: editor.id.clone(),
+        editor_name: editor.name.clone(),
+        name: DEFAULT_PROFILE_NAME.to_string(),
+        location: String::new(),
+        settings_path: editor.settings_path.clone(),
+        shares_default_settings: false,
+    }
+}
+
+/// 读取编辑器的 profile 列表：默认 profile（VS Code Server 为机器级设置）加上 globalStorage/storage.json 中的 userDataProfiles
+pub fn list_editor_profiles(editor: &EditorInstall) -> Result<Vec<EditorProfile>, String> {
+    let user_dir = Path::new(&editor.config_dir).join("User");
+    let default_settings = editor.settings_path.clone();
+    let mut profiles = vec![default_profile(editor)];
 
-    // 读取现有设置（如果文件存在），否则使用空对象
-    let mut settings: Value = if settings_path.exists() {
-        let content = fs::read_to_string(&settings_path)
-            .map_err(|e| format!("读取 VSCode 设置失败: {}", e))?;
+    // VS Code Server 只有机器级设置，profile 保存在本地客户端
+    if editor.kind == InstallKind::Server {
+        return Ok(profiles);
+    }
 
-        // 移除注释后再解析（处理 JSONC 格式）
-        let cleaned_content = strip_json_comments(&content);
+    let storage_path = user_dir.join("globalStorage").join("storage.json");
+    if !storage_path.exists() {
+        return Ok(profiles);
+    }
+    let content = fs::read_to_string(&storage_path)
+        .map_err(|e| format!("读取 {} 失败: {}", storage_path.display(), e))?;
+    let storage = jsonc::parse(&content).map_err(|e| format!("解析 {} 失败: {}", storage_path.display(), e))?;
+
+    let entries = storage.get("userDataProfiles").and_then(|v| v.as_array());
+    for entry in entries.into_iter().flatten() {
+        let (Some(name), Some(location)) = (
+            entry.get("name").and_then(|v| v.as_str()),
+            entry.get("location").and_then(|v| v.as_str()),
+        ) else {
+            continue;
+        };
+        let shares_default_settings = entry.pointer("/useDefaultFlags/settings").and_then(|v| v.as_bool()) == Some(true);
+        let settings_path = if shares_default_settings {
+            default_settings.clone()
+        } else {
+            user_dir.join("profiles").join(location).join("settings.json").to_string_lossy().to_string()
+        };
+        profiles.push(EditorProfile {
+            editor_id: editor.id.clone(),
+            editor_name: editor.name.clone(),
+            name: name.to_string(),
+            location: location.to_string(),
+            settings_path,
+            shares_default_settings,
+        });
+    }
+    Ok(profiles)
+}
 
-        match serde_json::from_str(&cleaned_content) {
-            Ok(v) => v,
+/// 列出所有已安装编辑器的 profile（storage.json 无法读取的编辑器只列出默认 profile）
+pub fn discover_profiles() -> Vec<EditorProfile> {
+    let mut profiles = Vec::new();
+    for editor in discover_editors() {
+        match list_editor_profiles(&editor) {
+            Ok(list) => profiles.extend(list),
             Err(e) => {
-                log::warn!("解析 VSCode settings.json 失败，将使用空配置: {}", e);
-                log::warn!("原始内容长度: {}, 清理后长度: {}", content.len(), cleaned_content.len());
-                json!({})
+                log::warn!("{}", e);
+                profiles.push(default_profile(&editor));
             }
         }
-    } else {
-        json!({})
-    };
+    }
+    profiles
+}
 
-    // 更新 ChatGPT 扩展配置
-    if let Some(obj) = settings.as_object_mut() {
-        // 设置 API Base URL（使用用户提供的 URL）
-        obj.insert(
-            "chatgpt.apiBase".to_string(),
-            Value::String(base_url.clone()),
-        );
+/// 在指定编辑器（为空表示全部已安装的编辑器）中按名称查找 profile，名称不区分大小写
+pub fn resolve_profiles(editor_ids: &[String], names: &[String]) -> Result<Vec<EditorProfile>, String> {
+    let mut available = Vec::new();
+    for editor in resolve_editors(editor_ids)? {
+        available.extend(list_editor_profiles(&editor)?);
+    }
 
-        // 设置认证方式为 apikey
-        let mut config_obj = serde_json::Map::new();
-        config_obj.insert(
-            "preferred_auth_method".to_string(),
-            Value::String("apikey".to_string()),
-        );
-        obj.insert("chatgpt.config".to_string(), Value::Object(config_obj));
+    let mut selected: Vec<EditorProfile> = Vec::new();
+    for name in names {
+        let matched: Vec<&EditorProfile> = available.iter().filter(|p| p.name.eq_ignore_ascii_case(name)).collect();
+        if matched.is_empty() {
+            let known: BTreeSet<&str> = available.iter().map(|p| p.name.as_str()).collect();
+            let known: Vec<&str> = known.into_iter().collect();
+            return Err(format!("未找到 VSCode profile: {}（可用: {}）", name, known.join(", ")));
+        }
+        for profile in matched {
+            if !selected.iter().any(|p| p.settings_path == profile.settings_path) {
+                selected.push(profile.clone());
+            }
+        }
+    }
+    Ok(selected)
+}
+
+/// 在指定的 settings.json 中设置 Claude Code 扩展的环境变量，保留其他变量
+fn write_claude_settings(settings_path: &Path, base_url: &str, api_key: &str) -> Result<(), String> {
+    let content = if settings_path.exists() {
+        fs::read_to_string(settings_path).map_err(|e| format!("读取 VSCode 设置失败: {}", e))?
+    } else {
+        String::new()
+    };
+    let parse_error = |e: String| format!("解析 {} 失败，未做修改: {}", settings_path.display(), e);
+    let settings = jsonc::parse(&content).map_err(parse_error)?;
+
+    let updates = [("ANTHROPIC_BASE_URL", base_url), ("ANTHROPIC_AUTH_TOKEN", api_key)];
+    let mut vars: Vec<Value> = settings
+        .get(CLAUDE_ENV_SETTING)
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+    vars.retain(|v| {
+        let name = v.get("name").and_then(|n| n.as_str());
+        !updates.iter().any(|(key, _)| name == Some(key))
+    });
+    for (name, value) in updates {
+        vars.push(json!({ "name": name, "value": value }));
+    }
 
-        // 注意：API Key 通过环境变量 key88 传递，不直接写入 settings.json
-        log::info!("已配置 ChatGPT 扩展使用自定义服务: {}, 请确保环境变量 key88={}", base_url, api_key);
+    let updated = jsonc::set(&content, &[CLAUDE_ENV_SETTING], &Value::Array(vars)).map_err(parse_error)?;
+    if updated != content {
+        crate::config::atomic_write(settings_path, updated.as_bytes())?;
     }
+    Ok(())
+}
 
-    // 写入配置
-    let json_str = serde_json::to_string_pretty(&settings)
-        .map_err(|e| format!("序列化设置失败: {}", e))?;
+/// 在 settings.json 中写入工具对应扩展的配置（Codex 的密钥通过环境变量传递，不写入文件）
+fn write_tool_settings(tool: Tool, settings_path: &Path, base_url: &str, api_key: &str) -> Result<(), String> {
+    match tool {
+        Tool::Claude => write_claude_settings(settings_path, base_url, api_key),
+        Tool::Codex => write_codex_settings(settings_path, base_url),
+        Tool::Gemini => Err("Gemini 没有 VSCode 扩展配置".to_string()),
+    }
+}
 
-    crate::config::atomic_write(&settings_path, json_str.as_bytes())?;
+/// 为选定的 profile 写入 Claude 或 Codex 扩展配置；某个 profile 失败不影响其他 profile
+pub fn configure_profiles(
+    tool: Tool,
+    profiles: &[EditorProfile],
+    base_url: String,
+    api_key: String,
+) -> Result<String, String> {
+    let mut lines = Vec::new();
+    let mut failed = false;
+    for profile in profiles {
+        let path = Path::new(&profile.settings_path);
+        let result = write_tool_settings(tool, path, &base_url, &api_key);
+        let label = if profile.shares_default_settings {
+            format!("{} / {}（沿用默认设置）", profile.editor_name, profile.name)
+        } else {
+            format!("{} / {}", profile.editor_name, profile.name)
+        };
+        match result {
+            Ok(()) => lines.push(format!("✓ {}: {}", label, profile.settings_path)),
+            Err(e) => {
+                failed = true;
+                lines.push(format!("✗ {}: {}", label, e));
+            }
+        }
+    }
 
+    let summary = lines.join("\n");
+    if failed {
+        return Err(format!("部分 profile 配置失败：\n{}", summary));
+    }
     Ok(format!(
-        "VSCode 配置成功！路径: {}\n已配置 ChatGPT 扩展使用自定义服务: {}\n请重新加载 VSCode 窗口以使配置生效。",
-        settings_path.display(),
-        base_url
+        "已为 {} 个 VSCode profile 配置 {}: {}\n{}\n请重新加载编辑器窗口以使配置生效。",
+        profiles.len(),
+        tool.as_str(),
+        base_url,
+        summary
     ))
 }
 
+/// 从 ~/.claude/config.json 内容中删除 primaryApiKey；返回 None 表示文件已没有其他内容，可以删除
+pub fn remove_claude_api_key(content: &str) -> Result<Option<String>, String> {
+    let mut config: serde_json::Map<String, Value> =
+        serde_json::from_str(content).map_err(|e| format!("解析 config.json 失败: {}", e))?;
+    config.remove("primaryApiKey");
+    if config.is_empty() {
+        return Ok(None);
+    }
+    serde_json::to_string_pretty(&config)
+        .map(Some)
+        .map_err(|e| format!("序列化 config.json 失败: {}", e))
+}
+
+/// 从 VSCode settings.json 内容中删除 chatgpt.* 配置，注释和其余内容保持不变；
+/// 没有相关配置时返回原内容，解析失败时返回错误而不是覆盖用户的设置
+pub fn remove_codex_settings(content: &str) -> Result<String, String> {
+    let parse_error = |e: String| format!("解析 VSCode settings.json 失败: {}", e);
+    let mut content = jsonc::remove(content, &["chatgpt.apiBase"]).map_err(parse_error)?;
+    content = jsonc::remove(&content, &["chatgpt.config", "preferred_auth_method"]).map_err(parse_error)?;
+
+    // chatgpt.config 中没有其他配置时整项删除
+    let settings = jsonc::parse(&content).map_err(parse_error)?;
+    if settings.get("chatgpt.config").and_then(|v| v.as_object()).is_some_and(|o| o.is_empty()) {
+        content = jsonc::remove(&content, &["chatgpt.config"]).map_err(parse_error)?;
+    }
+    Ok(content)
+}
+
+/// 从 VSCode settings.json 内容中删除 XG-switch 写入 claudeCode.environmentVariables 的变量，
+/// 注释和其余内容保持不变；解析失败时返回错误而不是覆盖用户的设置
+pub fn remove_claude_settings(content: &str) -> Result<String, String> {
+    let parse_error = |e: String| format!("解析 VSCode settings.json 失败: {}", e);
+    let settings = jsonc::parse(content).map_err(parse_error)?;
+    let Some(vars) = settings.get(CLAUDE_ENV_SETTING).and_then(|v| v.as_array()) else {
+        return Ok(content.to_string());
+    };
+
+    let is_managed = |v: &Value| {
+        v.get("name")
+            .and_then(|n| n.as_str())
+            .is_some_and(|n| n == "ANTHROPIC_BASE_URL" || n == "ANTHROPIC_AUTH_TOKEN")
+    };
+    if !vars.iter().any(is_managed) {
+        return Ok(content.to_string());
+    }
+    let remaining: Vec<Value> = vars.iter().filter(|v| !is_managed(v)).cloned().collect();
+    if remaining.is_empty() {
+        return jsonc::remove(content, &[CLAUDE_ENV_SETTING]).map_err(parse_error);
+    }
+    jsonc::set(content, &[CLAUDE_ENV_SETTING], &Value::Array(remaining)).map_err(parse_error)
+}
+
 /// 获取 VSCode 配置路径信息
+#[cfg(feature = "gui")]
 pub fn get_vscode_paths_info() -> Vec<String> {
     candidate_settings_paths()
         .into_iter()
         .map(|p| p.to_string_lossy().to_string())
         .collect()
 }
+
+#[cfg(all(test, unix, not(target_os = "macos")))]
+mod tests {
+    use super::*;
+    use crate::test_support::{temp_home, TempHome};
+
+    const STORAGE: &str = r#"{
+  // VS Code 自己写入的其他状态
+  "theme": "vs-dark",
+  "userDataProfiles": [
+    { "location": "-5a1b2c3d", "name": "Work" },
+    { "location": "4e5f6a7b", "name": "Shared", "useDefaultFlags": { "settings": true, "keybindings": true } },
+    { "location": "broken" },
+  ],
+}"#;
+
+    fn code(home: &TempHome) -> EditorInstall {
+        home.write(".config/Code/User/settings.json", "{}");
+        discover_editors().into_iter().find(|e| e.id == "code").unwrap()
+    }
+
+    fn read_json(path: &Path) -> Value {
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    fn names(profiles: &[EditorProfile]) -> Vec<&str> {
+        profiles.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    #[test]
+    fn lists_default_profile_without_storage() {
+        let home = temp_home();
+        let profiles = list_editor_profiles(&code(&home)).unwrap();
+        assert_eq!(names(&profiles), [DEFAULT_PROFILE_NAME]);
+        assert_eq!(profiles[0].settings_path, home.path_of(".config/Code/User/settings.json").to_string_lossy());
+    }
+
+    #[test]
+    fn lists_user_data_profiles_from_storage() {
+        let home = temp_home();
+        let editor = code(&home);
+        home.write(".config/Code/User/globalStorage/storage.json", STORAGE);
+
+        let profiles = list_editor_profiles(&editor).unwrap();
+        assert_eq!(names(&profiles), [DEFAULT_PROFILE_NAME, "Work", "Shared"]);
+        assert_eq!(
+            profiles[1].settings_path,
+            home.path_of(".config/Code/User/profiles/-5a1b2c3d/settings.json").to_string_lossy()
+        );
+        assert!(!profiles[1].shares_default_settings);
+        // 沿用默认设置的 profile 写入默认的 settings.json
+        assert!(profiles[2].shares_default_settings);
+        assert_eq!(profiles[2].settings_path, editor.settings_path);
+    }
+
+    #[test]
+    fn malformed_storage_is_an_error() {
+        let home = temp_home();
+        let editor = code(&home);
+        home.write(".config/Code/User/globalStorage/storage.json", "{\"userDataProfiles\": [");
+        let err = list_editor_profiles(&editor).unwrap_err();
+        assert!(err.starts_with("解析 "), "{}", err);
+
+        // 汇总所有编辑器时退回到默认 profile
+        assert_eq!(names(&discover_profiles()), [DEFAULT_PROFILE_NAME]);
+    }
+
+    #[test]
+    fn unknown_profile_error_lists_each_name_once() {
+        let home = temp_home();
+        code(&home);
+        home.write(".config/Code/User/globalStorage/storage.json", STORAGE);
+        home.write(".config/Cursor/User/globalStorage/storage.json", STORAGE);
+
+        let err = resolve_profiles(&[], &["Missing".to_string()]).unwrap_err();
+        assert_eq!(err, "未找到 VSCode profile: Missing（可用: Default, Shared, Work）");
+
+        // 同名 profile 在每个编辑器中各选一次，共享默认设置的不重复写入
+        let selected = resolve_profiles(&[], &["work".to_string(), "shared".to_string(), "default".to_string()]).unwrap();
+        let ids: Vec<(&str, &str)> = selected.iter().map(|p| (p.editor_id.as_str(), p.name.as_str())).collect();
+        assert_eq!(ids, [("code", "Work"), ("cursor", "Work"), ("code", "Shared"), ("cursor", "Shared")]);
+    }
+
+    #[test]
+    fn claude_settings_replace_managed_vars_and_keep_others() {
+        let home = temp_home();
+        let path = home.write(
+            ".config/Code/User/settings.json",
+            r#"{
+  // 字号
+  "editor.fontSize": 14,
+  "claudeCode.environmentVariables": [
+    { "name": "ANTHROPIC_BASE_URL", "value": "https://old.example" },
+    { "name": "HTTPS_PROXY", "value": "http://127.0.0.1:7890" },
+    { "name": "ANTHROPIC_AUTH_TOKEN", "value": "sk-old" }
+  ]
+}"#,
+        );
+
+        write_claude_settings(&path, "https://relay.example/api", "sk-new").unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("// 字号"));
+        let settings = jsonc::parse(&content).unwrap();
+        assert_eq!(settings["editor.fontSize"], json!(14));
+        assert_eq!(
+            settings[CLAUDE_ENV_SETTING],
+            json!([
+                { "name": "HTTPS_PROXY", "value": "http://127.0.0.1:7890" },
+                { "name": "ANTHROPIC_BASE_URL", "value": "https://relay.example/api" },
+                { "name": "ANTHROPIC_AUTH_TOKEN", "value": "sk-new" }
+            ])
+        );
+
+        // 撤销时只删除 XG-switch 写入的变量
+        let cleaned = jsonc::parse(&remove_claude_settings(&content).unwrap()).unwrap();
+        assert_eq!(cleaned[CLAUDE_ENV_SETTING], json!([{ "name": "HTTPS_PROXY", "value": "http://127.0.0.1:7890" }]));
+    }
+
+    #[test]
+    fn claude_api_key_keeps_other_fields_and_is_private() {
+        let home = temp_home();
+        let path = home.write(".claude/config.json", r#"{"primaryApiKey": "sk-old", "customApiKeyResponses": {"approved": ["x"]}}"#);
+
+        assert_eq!(write_claude_api_key("sk-new").unwrap(), path);
+        assert_eq!(
+            read_json(&path),
+            json!({"primaryApiKey": "sk-new", "customApiKeyResponses": {"approved": ["x"]}})
+        );
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        // 无法解析时不做修改
+        home.write(".claude/config.json", "{ broken");
+        assert!(write_claude_api_key("sk-new").is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{ broken");
+    }
+
+    #[test]
+    fn discovers_native_flatpak_and_server_installs() {
+        let home = temp_home();
+        home.write(".config/Cursor/User/settings.json", "{}");
+        std::fs::create_dir_all(home.path_of(".var/app/com.vscodium.codium/config/VSCodium/User")).unwrap();
+        std::fs::create_dir_all(home.path_of(".vscode-server/data/Machine")).unwrap();
+        // Snap 版使用 ~/.config，~/snap 下的目录不再单独识别
+        std::fs::create_dir_all(home.path_of("snap/code/current/.config/Code/User")).unwrap();
+
+        let editors = discover_editors();
+        let found: Vec<(&str, InstallKind, &str, bool)> = editors
+            .iter()
+            .map(|e| (e.id.as_str(), e.kind, e.settings_path.as_str(), e.settings_exists))
+            .collect();
+        let path = |relative: &str| home.path_of(relative).to_string_lossy().to_string();
+        assert_eq!(
+            found,
+            [
+                ("cursor", InstallKind::Native, path(".config/Cursor/User/settings.json").as_str(), true),
+                (
+                    "vscodium-flatpak",
+                    InstallKind::Flatpak,
+                    path(".var/app/com.vscodium.codium/config/VSCodium/User/settings.json").as_str(),
+                    false
+                ),
+                ("code-server", InstallKind::Server, path(".vscode-server/data/Machine/settings.json").as_str(), false),
+            ]
+        );
+        assert_eq!(editors[1].name, "VSCodium (Flatpak)");
+        assert_eq!(editors[2].name, "VS Code Server");
+        assert!(candidate_settings_paths().iter().all(|p| !p.starts_with(home.path_of("snap"))));
+
+        // 远程主机上只有 VS Code Server 时默认写入机器级设置
+        std::fs::remove_dir_all(home.path_of(".config/Cursor")).unwrap();
+        std::fs::remove_dir_all(home.path_of(".var")).unwrap();
+        assert_eq!(default_settings_path().unwrap(), home.path_of(".vscode-server/data/Machine/settings.json"));
+        let profiles = list_editor_profiles(&discover_editors()[0]).unwrap();
+        assert_eq!(names(&profiles), [DEFAULT_PROFILE_NAME]);
+    }
+}
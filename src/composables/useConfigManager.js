@@ -1,5 +1,6 @@
 import { ref, computed } from 'vue';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 
 // 配置数据结构
 const configStore = ref({
@@ -15,76 +16,95 @@ const activeConfigs = ref({
   gemini: null
 });
 
-// 本地存储键名
+// 旧版本使用的本地存储键名（仅用于迁移）
 const STORAGE_KEY = 'xg_switch_configs';
 const ACTIVE_KEY = 'xg_switch_active';
 
-// 加载配置
-function loadConfigs() {
-  try {
-    const stored = localStorage.getItem(STORAGE_KEY);
-    if (stored) {
-      configStore.value = JSON.parse(stored);
+// 将后端配置库转换为按工具分组的结构
+function applyStore(store) {
+  const grouped = {
+    claude_configs: [],
+    codex_configs: [],
+    gemini_configs: []
+  };
+
+  for (const profile of store.profiles) {
+    const list = grouped[`${profile.tool}_configs`];
+    if (list) {
+      list.push({ ...profile, isActive: store.active[profile.tool] === profile.id });
     }
+  }
+
+  configStore.value = grouped;
+  activeConfigs.value = {
+    claude: store.active.claude ?? null,
+    codex: store.active.codex ?? null,
+    gemini: store.active.gemini ?? null
+  };
+}
 
-    const active = localStorage.getItem(ACTIVE_KEY);
-    if (active) {
-      activeConfigs.value = JSON.parse(active);
+// 将旧版 localStorage 中的配置迁移到后端配置库
+async function migrateLegacyStorage() {
+  const stored = localStorage.getItem(STORAGE_KEY);
+  if (!stored) return;
+
+  const legacy = JSON.parse(stored);
+  const profiles = [];
+  for (const tool of ['claude', 'codex', 'gemini']) {
+    for (const config of legacy[`${tool}_configs`] || []) {
+      const { isActive, ...rest } = config;
+      profiles.push({ ...rest, tool });
     }
-  } catch (error) {
-    console.error('加载配置失败:', error);
   }
+
+  if (profiles.length > 0) {
+    await invoke('import_profiles', { profiles });
+  }
+
+  localStorage.removeItem(STORAGE_KEY);
+  localStorage.removeItem(ACTIVE_KEY);
 }
 
-// 保存配置到localStorage
-function saveToStorage() {
+// 从后端加载配置
+async function loadConfigs() {
   try {
-    localStorage.setItem(STORAGE_KEY, JSON.stringify(configStore.value));
-    localStorage.setItem(ACTIVE_KEY, JSON.stringify(activeConfigs.value));
+    await migrateLegacyStorage();
+    applyStore(await invoke('list_profiles'));
   } catch (error) {
-    console.error('保存配置失败:', error);
+    console.error('加载配置失败:', error);
   }
 }
 
-// 生成唯一ID
-function generateId() {
-  return `config_${Date.now()}_${Math.random().toString(36).substr(2, 9)}`;
+// 新建配置并刷新列表
+async function createProfile(tool, name, baseUrl, apiKey, configContent, envKey = null) {
+  const profile = await invoke('create_profile', {
+    profile: { tool, name, baseUrl, apiKey, configContent, envKey }
+  });
+  await loadConfigs();
+  return profile;
 }
 
 // 保存Claude配置
 function saveClaudeConfig(name, baseUrl, apiKey, configContent = null) {
-  const config = {
-    id: generateId(),
-    name: name || `配置 ${configStore.value.claude_configs.length + 1}`,
+  return createProfile(
+    'claude',
+    name,
     baseUrl,
     apiKey,
-    configContent: configContent || generateDefaultClaudeConfig(baseUrl, apiKey),
-    createdAt: Date.now(),
-    isActive: false
-  };
-
-  configStore.value.claude_configs.push(config);
-  saveToStorage();
-
-  return config;
+    configContent || generateDefaultClaudeConfig(baseUrl, apiKey)
+  );
 }
 
 // 保存Codex配置
-function saveCodexConfig(name, baseUrl, apiKey, configContent = null) {
-  const config = {
-    id: generateId(),
-    name: name || `配置 ${configStore.value.codex_configs.length + 1}`,
+function saveCodexConfig(name, baseUrl, apiKey, configContent = null, envKey = null) {
+  return createProfile(
+    'codex',
+    name,
     baseUrl,
     apiKey,
-    configContent: configContent || generateDefaultCodexConfig(baseUrl, apiKey),
-    createdAt: Date.now(),
-    isActive: false
-  };
-
-  configStore.value.codex_configs.push(config);
-  saveToStorage();
-
-  return config;
+    configContent || generateDefaultCodexConfig(baseUrl, apiKey),
+    envKey
+  );
 }
 
 // 生成默认的Claude配置内容
@@ -119,124 +139,67 @@ function generateDefaultCodexConfig(baseUrl, apiKey) {
 
 // 保存Gemini配置
 function saveGeminiConfig(name, baseUrl, apiKey) {
-  const config = {
-    id: generateId(),
-    name: name || `配置 ${configStore.value.gemini_configs.length + 1}`,
-    baseUrl,
-    apiKey,
-    createdAt: Date.now(),
-    isActive: false
-  };
-
-  configStore.value.gemini_configs.push(config);
-  saveToStorage();
-
-  return config;
+  return createProfile('gemini', name, baseUrl, apiKey, null);
 }
 
-// 切换Claude配置
-async function switchClaudeConfig(configId) {
-  const config = configStore.value.claude_configs.find(c => c.id === configId);
-  if (!config) return { success: false, message: '配置不存在' };
-
+// 应用并激活配置
+async function activateProfile(configId, successMessage) {
   try {
-    // 调用后端应用配置
-    await invoke('configure_claude_code', {
-      baseUrl: config.baseUrl,
-      apiKey: config.apiKey
-    });
-
-    // 更新激活状态
-    configStore.value.claude_configs.forEach(c => {
-      c.isActive = c.id === configId;
-    });
-
-    activeConfigs.value.claude = configId;
-    saveToStorage();
-
-    return { success: true, message: 'Claude配置已切换' };
+    await invoke('activate_profile', { id: configId });
+    await loadConfigs();
+    return { success: true, message: successMessage };
   } catch (error) {
     return { success: false, message: error.toString() };
   }
 }
 
-// 切换Codex配置
-async function switchCodexConfig(configId) {
-  const config = configStore.value.codex_configs.find(c => c.id === configId);
-  if (!config) return { success: false, message: '配置不存在' };
-
-  try {
-    // 调用后端应用配置
-    await invoke('configure_codex', {
-      baseUrl: config.baseUrl,
-      apiKey: config.apiKey
-    });
-
-    // 更新激活状态
-    configStore.value.codex_configs.forEach(c => {
-      c.isActive = c.id === configId;
-    });
-
-    activeConfigs.value.codex = configId;
-    saveToStorage();
+// 切换Claude配置
+function switchClaudeConfig(configId) {
+  return activateProfile(configId, 'Claude配置已切换');
+}
 
-    return { success: true, message: 'Codex配置已切换' };
-  } catch (error) {
-    return { success: false, message: error.toString() };
-  }
+// 切换Codex配置
+function switchCodexConfig(configId) {
+  return activateProfile(configId, 'Codex配置已切换');
 }
 
 // 切换Gemini配置
-async function switchGeminiConfig(configId) {
-  const config = configStore.value.gemini_configs.find(c => c.id === configId);
-  if (!config) return { success: false, message: '配置不存在' };
-
-  // Gemini暂时只保存，不实际切换
-  configStore.value.gemini_configs.forEach(c => {
-    c.isActive = c.id === configId;
-  });
-
-  activeConfigs.value.gemini = configId;
-  saveToStorage();
-
-  return { success: true, message: 'Gemini配置已保存（功能开发中）' };
+function switchGeminiConfig(configId) {
+  return activateProfile(configId, 'Gemini配置已切换');
 }
 
 // 删除配置
-function deleteConfig(type, configId) {
-  const configKey = `${type}_configs`;
-  const configs = configStore.value[configKey];
-
-  if (!configs) return false;
-
-  const index = configs.findIndex(c => c.id === configId);
-  if (index === -1) return false;
-
-  configs.splice(index, 1);
-
-  // 如果删除的是激活的配置，清除激活状态
-  if (activeConfigs.value[type] === configId) {
-    activeConfigs.value[type] = null;
+async function deleteConfig(type, configId) {
+  try {
+    await invoke('delete_profile', { id: configId });
+    await loadConfigs();
+    return true;
+  } catch (error) {
+    console.error('删除配置失败:', error);
+    return false;
   }
-
-  saveToStorage();
-  return true;
 }
 
 // 更新配置
-function updateConfig(type, configId, updates) {
-  const configKey = `${type}_configs`;
-  const configs = configStore.value[configKey];
-
-  if (!configs) return false;
-
-  const config = configs.find(c => c.id === configId);
-  if (!config) return false;
-
-  Object.assign(config, updates);
-  saveToStorage();
-
-  return true;
+async function updateConfig(type, configId, updates) {
+  try {
+    await invoke('update_profile', {
+      id: configId,
+      updates: {
+        name: updates.name,
+        baseUrl: updates.baseUrl,
+        apiKey: updates.apiKey,
+        configContent: updates.configContent,
+        envKey: updates.envKey,
+        models: updates.models
+      }
+    });
+    await loadConfigs();
+    return true;
+  } catch (error) {
+    console.error('更新配置失败:', error);
+    return false;
+  }
 }
 
 // 获取所有Claude配置
@@ -288,17 +251,16 @@ async function importConfigs(file) {
     const data = JSON.parse(text);
 
     // 合并配置而不是替换
-    if (data.claude_configs) {
-      configStore.value.claude_configs.push(...data.claude_configs);
-    }
-    if (data.codex_configs) {
-      configStore.value.codex_configs.push(...data.codex_configs);
-    }
-    if (data.gemini_configs) {
-      configStore.value.gemini_configs.push(...data.gemini_configs);
+    const profiles = [];
+    for (const tool of ['claude', 'codex', 'gemini']) {
+      for (const config of data[`${tool}_configs`] || []) {
+        const { isActive, ...rest } = config;
+        profiles.push({ ...rest, tool });
+      }
     }
 
-    saveToStorage();
+    await invoke('import_profiles', { profiles });
+    await loadConfigs();
     return { success: true, message: '配置导入成功' };
   } catch (error) {
     return { success: false, message: '配置文件格式错误' };
@@ -308,6 +270,9 @@ async function importConfigs(file) {
 // 初始化加载
 loadConfigs();
 
+// 后台健康检查自动切换配置后刷新列表
+listen('profile-auto-switched', () => loadConfigs());
+
 // 导出函数
 export function useConfigManager() {
   return {
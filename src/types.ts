@@ -5,6 +5,9 @@ export interface ConfigPaths {
   codex_dir: string;
   codex_auth: string;
   codex_config: string;
+  gemini_dir: string;
+  gemini_settings: string;
+  gemini_env: string;
 }
 
 // Claude 配置
@@ -25,6 +28,14 @@ export interface CodexAuth {
   OPENAI_API_KEY: string;
 }
 
+// Gemini 配置
+export interface GeminiConfig {
+  base_url: string | null;
+  api_key: string | null;
+  model: string | null;
+  auth_type: string | null;
+}
+
 // 通知类型
 export type NotificationType = "success" | "error" | "info";
 
@@ -34,4 +45,4 @@ export interface Notification {
 }
 
 // 面板类型
-export type PanelType = "claude" | "codex";
+export type PanelType = "claude" | "codex" | "gemini";